@@ -0,0 +1,125 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use quote::{quote,format_ident};
+use syn::{parse_macro_input,DeriveInput,Data,Fields,Type,Expr,Lit};
+
+//vertex format of a field type, for fields without an explicit format
+fn infer_format(ty:&Type)->Option<&'static str>{
+    let scalar=|ty:&Type| match ty{
+        Type::Path(path)=>path.path.get_ident().map(|ident| ident.to_string()),
+        _=>None,
+    };
+    match ty{
+        Type::Array(array)=>{
+            let length=match &array.len{
+                Expr::Lit(expr)=>match &expr.lit{
+                    Lit::Int(length)=>length.base10_parse::<usize>().ok()?,
+                    _=>return None,
+                },
+                _=>return None,
+            };
+            Some(match (scalar(&array.elem)?.as_str(),length){
+                ("f32",2)=>"R32G32_SFLOAT",
+                ("f32",3)=>"R32G32B32_SFLOAT",
+                ("f32",4)=>"R32G32B32A32_SFLOAT",
+                ("u32",2)=>"R32G32_UINT",
+                ("u32",3)=>"R32G32B32_UINT",
+                ("u32",4)=>"R32G32B32A32_UINT",
+                ("u8",4)=>"R8G8B8A8_UNORM",
+                _=>return None,
+            })
+        },
+        _=>Some(match scalar(ty)?.as_str(){
+            "f32"=>"R32_SFLOAT",
+            "u32"=>"R32_UINT",
+            _=>return None,
+        }),
+    }
+}
+
+//implements dyra's VertexLayout for a #[repr(C)] struct.
+//the generated code refers to dyra by its crate name (dyra itself declares `extern crate self as dyra` for this), and needs nothing else in scope.
+//each field that is read by shaders is marked with its shader location (a constant from vertex_layout), and optionally its format:
+//  #[vertex(POSITION)] position:[f32;3],
+//  #[vertex(COLOR,format=R8G8B8A8_UNORM)] color:[u8;4],
+//an attribute can span several fields (like x,y,z,w), in which case the first one is marked with the format of all of them.
+//formats are inferred for f32, u32, and arrays of f32, u32 and u8.
+#[proc_macro_derive(VertexLayout,attributes(vertex))]
+pub fn derive_vertex_layout(input:TokenStream)->TokenStream{
+    let input=parse_macro_input!(input as DeriveInput);
+    let name=&input.ident;
+
+    if !input.generics.params.is_empty(){
+        return syn::Error::new_spanned(&input.generics,"VertexLayout cannot be derived for generic types").to_compile_error().into();
+    }
+    let repr_c=input.attrs.iter().any(|attribute|{
+        let mut c=false;
+        if attribute.path().is_ident("repr"){
+            let _=attribute.parse_nested_meta(|meta|{
+                c|=meta.path.is_ident("C");
+                Ok(())
+            });
+        }
+        c
+    });
+    if !repr_c{
+        return syn::Error::new_spanned(name,"VertexLayout requires #[repr(C)], so that attributes spanning several fields are contiguous").to_compile_error().into();
+    }
+    let fields=match &input.data{
+        Data::Struct(data)=>match &data.fields{
+            Fields::Named(fields)=>&fields.named,
+            _=>return syn::Error::new_spanned(name,"VertexLayout requires named fields").to_compile_error().into(),
+        },
+        _=>return syn::Error::new_spanned(name,"VertexLayout can only be derived for structs").to_compile_error().into(),
+    };
+
+    let mut attributes=Vec::new();
+    for field in fields.iter(){
+        for attribute in field.attrs.iter().filter(|attribute| attribute.path().is_ident("vertex")){
+            let mut location=None;
+            let mut format=None;
+            let parsed=attribute.parse_nested_meta(|meta|{
+                if meta.path.is_ident("format"){
+                    let value:syn::Ident=meta.value()?.parse()?;
+                    format=Some(value);
+                }else if let Some(ident)=meta.path.get_ident(){
+                    location=Some(ident.clone());
+                }else{
+                    return Err(meta.error("expected a location like POSITION, or format=..."));
+                }
+                Ok(())
+            });
+            if let Err(error)=parsed{
+                return error.to_compile_error().into();
+            }
+            let field_name=field.ident.as_ref().unwrap();
+            let location=match location{
+                Some(location)=>location,
+                None=>return syn::Error::new_spanned(attribute,"vertex attribute needs a location, e.g. #[vertex(POSITION)]").to_compile_error().into(),
+            };
+            let format=match format.or_else(|| infer_format(&field.ty).map(|format| format_ident!("{}",format))){
+                Some(format)=>format,
+                None=>return syn::Error::new_spanned(&field.ty,"cannot infer the vertex format of this type, add format=... to the vertex attribute").to_compile_error().into(),
+            };
+            attributes.push(quote!{
+                ::dyra::vertex_layout::VertexAttribute{
+                    location: ::dyra::vertex_layout::#location,
+                    format: ::dyra::vertex_layout::Format::#format,
+                    offset: ::dyra::vertex_layout::offset_of!(#name,#field_name) as u32,
+                }
+            });
+        }
+    }
+
+    quote!(
+        impl ::dyra::vertex_layout::VertexLayout for #name{
+            fn layout()-> ::dyra::vertex_layout::VertexLayoutDescription{
+                ::dyra::vertex_layout::VertexLayoutDescription{
+                    stride: ::std::mem::size_of::<#name>() as u32,
+                    attributes:vec![#(#attributes),*],
+                }
+            }
+        }
+    ).into()
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(set=0,binding=0) uniform samplerCube u_Cubemap;
+
+layout(location=0) in vec3 v_Direction;
+
+layout(location=0) out vec4 o_Color;
+
+void main(){
+    //cubemap faces are defined for a left handed coordinate system, the world is right handed
+    o_Color=texture(u_Cubemap,vec3(v_Direction.xy,-v_Direction.z));
+}
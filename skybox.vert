@@ -0,0 +1,42 @@
+#version 450
+
+layout(push_constant) uniform PushConstants{
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+}constants;
+
+out gl_PerVertex{
+    vec4 gl_Position;
+};
+
+layout(location=0) out vec3 v_Direction;
+
+//unit cube around the camera, drawn without vertex buffers
+const vec3 corners[8]=vec3[8](
+    vec3(-1.0,-1.0,-1.0),
+    vec3(1.0,-1.0,-1.0),
+    vec3(-1.0,1.0,-1.0),
+    vec3(1.0,1.0,-1.0),
+    vec3(-1.0,-1.0,1.0),
+    vec3(1.0,-1.0,1.0),
+    vec3(-1.0,1.0,1.0),
+    vec3(1.0,1.0,1.0)
+);
+const int indices[36]=int[36](
+    0,1,2, 2,1,3,
+    4,6,5, 5,6,7,
+    0,2,4, 4,2,6,
+    1,5,3, 3,5,7,
+    0,4,1, 1,4,5,
+    2,3,6, 6,3,7
+);
+
+void main(){
+    vec3 position=corners[indices[gl_VertexIndex]];
+    v_Direction=position;
+    //only the rotation of the view is applied, so the cube stays centered on the camera
+    vec4 clip_position=constants.projection*mat4(mat3(constants.view))*vec4(position,1.0);
+    //z=w puts the cube onto the far plane, so it is not clipped by a near plane beyond it (the skybox is drawn without depth test)
+    gl_Position=clip_position.xyww;
+}
@@ -0,0 +1,282 @@
+use ash::vk;
+
+use crate::{Decoder,Image,Vertex};
+use crate::decoder::VertexIndices;
+use crate::color::TextureUsage;
+use crate::mipmap;
+
+//where a sprite was placed in an atlas
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct AtlasRegion{
+    //index into Atlas::pages
+    pub page:usize,
+    //texture coordinates of the top left and bottom right corner of the sprite, without its padding
+    pub uv_min:[f32;2],
+    pub uv_max:[f32;2],
+    //size of the sprite in texels
+    pub width:u32,
+    pub height:u32,
+}
+impl AtlasRegion{
+    //two triangles covering the rectangle from min (top left) to max (bottom right), textured with the sprite
+    //positions are in normalized device coordinates, as used by the 2d pipeline
+    pub fn quad(&self,min:[f32;2],max:[f32;2])->(Vec<Vertex>,Vec<VertexIndices>){
+        let vertices=vec![
+            Vertex::new(min[0],min[1],0.0,1.0,self.uv_min[0],self.uv_min[1]),
+            Vertex::new(min[0],max[1],0.0,1.0,self.uv_min[0],self.uv_max[1]),
+            Vertex::new(max[0],max[1],0.0,1.0,self.uv_max[0],self.uv_max[1]),
+            Vertex::new(max[0],min[1],0.0,1.0,self.uv_max[0],self.uv_min[1]),
+        ];
+        //counter clockwise on screen
+        let vertex_indices=vec![
+            VertexIndices::new(0,1,2),
+            VertexIndices::new(0,2,3),
+        ];
+        (vertices,vertex_indices)
+    }
+}
+
+//row of sprites on a page, as high as its first (tallest) sprite
+struct Shelf{
+    y:u32,
+    height:u32,
+    used_width:u32,
+}
+
+//page being packed, shelves are stacked from the top
+struct Page{
+    shelves:Vec<Shelf>,
+    used_height:u32,
+}
+
+//collects sprites and packs them into square pages of r8g8b8a8 texels.
+//every sprite is surrounded by padding that repeats its edge texels, so that filtering (and the first few mip levels) does not pick up neighbouring sprites.
+pub struct AtlasBuilder{
+    pub page_size:u32,
+    pub padding:u32,
+    sprites:Vec<image::RgbaImage>,
+}
+impl AtlasBuilder{
+    pub fn new(page_size:u32,padding:u32)->Self{
+        Self{
+            page_size,
+            padding,
+            sprites:Vec::new(),
+        }
+    }
+
+    //add a sprite, returns its index into the regions of the packed atlas
+    pub fn add_image(&mut self,native_image:&image::DynamicImage)->usize{
+        let sprite=native_image.to_rgba8();
+        if self.cell_extent(sprite.width())>self.page_size || self.cell_extent(sprite.height())>self.page_size{
+            panic!("sprite is {}x{}, which does not fit into a {} texel page with {} texels padding",sprite.width(),sprite.height(),self.page_size,self.padding);
+        }
+        self.sprites.push(sprite);
+        self.sprites.len()-1
+    }
+
+    pub fn add_file(&mut self,filename:&str)->usize{
+        let native_image=image::open(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+        self.add_image(&native_image)
+    }
+
+    //add tightly packed r8g8b8a8 pixels
+    pub fn add_pixels(&mut self,width:u32,height:u32,pixels:&[u8])->usize{
+        let sprite=image::RgbaImage::from_raw(width,height,pixels.to_vec())
+            .unwrap_or_else(|| panic!("sprite is {}x{}, which requires {} bytes, but got {}",width,height,width*height*4,pixels.len()));
+        self.add_image(&image::DynamicImage::ImageRgba8(sprite))
+    }
+
+    //number of mip levels that stay within the padding of each sprite
+    pub fn mip_levels(&self)->u32{
+        if self.padding==0{
+            1
+        }else{
+            (self.padding.ilog2()+1).min(mipmap::mip_level_count(self.page_size,self.page_size))
+        }
+    }
+
+    //size of a sprite with its padding on both sides, rounded up so that cells start on multiples of the size of a texel in the last mip level
+    fn cell_extent(&self,size:u32)->u32{
+        let alignment=1<<(self.mip_levels()-1);
+        (size+2*self.padding).div_ceil(alignment)*alignment
+    }
+
+    //place all sprites on shelves, tallest first, opening a new page when a sprite fits on none of the existing ones.
+    //returns the pages and the region of each sprite, in the order they were added
+    pub fn pack(&self)->(Vec<image::RgbaImage>,Vec<AtlasRegion>){
+        let mut order:Vec<usize>=(0..self.sprites.len()).collect();
+        order.sort_by_key(|&index| (std::cmp::Reverse(self.sprites[index].height()),std::cmp::Reverse(self.sprites[index].width()),index));
+
+        let mut pages:Vec<Page>=Vec::new();
+        let mut placements=vec![(0,0,0);self.sprites.len()];
+        for index in order{
+            let (cell_width,cell_height)=(self.cell_extent(self.sprites[index].width()),self.cell_extent(self.sprites[index].height()));
+            let mut placement=None;
+            'pages: for (page_index,page) in pages.iter_mut().enumerate(){
+                for shelf in page.shelves.iter_mut(){
+                    if cell_height<=shelf.height && shelf.used_width+cell_width<=self.page_size{
+                        placement=Some((page_index,shelf.used_width,shelf.y));
+                        shelf.used_width+=cell_width;
+                        break 'pages;
+                    }
+                }
+                if page.used_height+cell_height<=self.page_size{
+                    placement=Some((page_index,0,page.used_height));
+                    page.shelves.push(Shelf{
+                        y:page.used_height,
+                        height:cell_height,
+                        used_width:cell_width,
+                    });
+                    page.used_height+=cell_height;
+                    break 'pages;
+                }
+            }
+            placements[index]=placement.unwrap_or_else(||{
+                pages.push(Page{
+                    shelves:vec![Shelf{
+                        y:0,
+                        height:cell_height,
+                        used_width:cell_width,
+                    }],
+                    used_height:cell_height,
+                });
+                (pages.len()-1,0,0)
+            });
+        }
+
+        let mut page_images:Vec<image::RgbaImage>=pages.iter().map(|_| image::RgbaImage::new(self.page_size,self.page_size)).collect();
+        let page_size=self.page_size as f32;
+        let regions=self.sprites.iter().zip(placements.iter()).map(|(sprite,&(page,x,y))|{
+            let (width,height)=sprite.dimensions();
+            //edge bleeding: padding texels repeat the nearest texel of the sprite
+            let padding=self.padding as i64;
+            for cell_y in 0..height+2*self.padding{
+                for cell_x in 0..width+2*self.padding{
+                    let source_x=(cell_x as i64-padding).clamp(0,width as i64-1) as u32;
+                    let source_y=(cell_y as i64-padding).clamp(0,height as i64-1) as u32;
+                    page_images[page].put_pixel(x+cell_x,y+cell_y,*sprite.get_pixel(source_x,source_y));
+                }
+            }
+            let (left,top)=(x+self.padding,y+self.padding);
+            AtlasRegion{
+                page,
+                uv_min:[left as f32/page_size,top as f32/page_size],
+                uv_max:[(left+width) as f32/page_size,(top+height) as f32/page_size],
+                width,
+                height,
+            }
+        }).collect();
+        (page_images,regions)
+    }
+}
+
+//packed sprites, uploaded as one texture per page
+pub struct Atlas{
+    pub pages:Vec<std::sync::Arc<Image>>,
+    pub regions:Vec<AtlasRegion>,
+}
+impl Atlas{
+    //texture and region of a sprite, by the index returned when it was added to the builder
+    pub fn sprite(&self,index:usize)->(std::sync::Arc<Image>,AtlasRegion){
+        let region=self.regions[index];
+        (self.pages[region.page].clone(),region)
+    }
+
+    //sprite covering the rectangle from min (top left) to max (bottom right), in normalized device coordinates
+    pub fn place(&self,index:usize,min:[f32;2],max:[f32;2])->Sprite{
+        let (texture,region)=self.sprite(index);
+        Sprite{
+            texture,
+            region,
+            min,
+            max,
+        }
+    }
+}
+
+//sprite drawn over the scene by Painter::draw with the 2d pipeline, in the order given
+#[derive(Debug,Clone)]
+pub struct Sprite{
+    //atlas page containing the sprite
+    pub texture:std::sync::Arc<Image>,
+    pub region:AtlasRegion,
+    //top left and bottom right corner, in normalized device coordinates
+    pub min:[f32;2],
+    pub max:[f32;2],
+}
+
+impl Decoder{
+    //pack the sprites and upload the pages, which are cached as "name#0", "name#1" etc.
+    //pages get only as many mip levels as the padding allows
+    pub fn create_atlas(&mut self,name:&str,builder:&AtlasBuilder,usage:TextureUsage,command_buffer:vk::CommandBuffer)->Atlas{
+        let (page_images,regions)=builder.pack();
+        let pages=page_images.iter().enumerate().map(|(page,page_image)|{
+            let pixels=page_image.as_raw();
+            let generated=mipmap::generate_mips_rgba8(builder.page_size,builder.page_size,pixels,usage==TextureUsage::Color,builder.mip_levels());
+            let levels:Vec<&[u8]>=std::iter::once(&pixels[..]).chain(generated.iter().map(|level| &level[..])).collect();
+            self.create_texture_with_mips(format!("{}#{}",name,page),usage.rgba8_format(),builder.page_size,builder.page_size,&levels,command_buffer)
+        }).collect();
+        Atlas{
+            pages,
+            regions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //sprite filled with one color per sprite
+    fn sprite(width:u32,height:u32,index:u8)->Vec<u8>{
+        [index,255-index,index/2,255].repeat((width*height) as usize)
+    }
+
+    #[test]
+    fn packed_sprites_do_not_overlap(){
+        let mut builder=AtlasBuilder::new(64,2);
+        let sizes=[(20,10),(5,30),(16,16),(40,8),(3,3),(30,20),(12,40),(25,25),(8,8),(50,5)];
+        for (index,&(width,height)) in sizes.iter().enumerate(){
+            builder.add_pixels(width,height,&sprite(width,height,index as u8));
+        }
+        let (pages,regions)=builder.pack();
+        assert!(pages.len()>1);
+        assert_eq!(regions.len(),sizes.len());
+
+        //padded cells in texels
+        let page_size=builder.page_size as f32;
+        let cells:Vec<(usize,[u32;4])>=regions.iter().map(|region|{
+            let (left,top)=((region.uv_min[0]*page_size).round() as u32,(region.uv_min[1]*page_size).round() as u32);
+            (region.page,[left-builder.padding,top-builder.padding,left+region.width+builder.padding,top+region.height+builder.padding])
+        }).collect();
+        for (index,((page,[x0,y0,x1,y1]),region)) in cells.iter().zip(regions.iter()).enumerate(){
+            assert_eq!((region.width,region.height),sizes[index]);
+            assert!(*page<pages.len() && *x1<=builder.page_size && *y1<=builder.page_size);
+            for (other_page,[ox0,oy0,ox1,oy1]) in cells[index+1..].iter(){
+                assert!(page!=other_page || x1<=ox0 || ox1<=x0 || y1<=oy0 || oy1<=y0,"sprites overlap");
+            }
+            //the cell, including its padding, holds only the color of its sprite
+            for y in *y0..*y1{
+                for x in *x0..*x1{
+                    assert_eq!(pages[*page].get_pixel(x,y).0,[index as u8,255-index as u8,index as u8/2,255]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edge_bleeding(){
+        let mut builder=AtlasBuilder::new(16,2);
+        builder.add_pixels(2,1,&[255,0,0,255,0,0,255,255]);
+        let (pages,regions)=builder.pack();
+        let (left,top)=((regions[0].uv_min[0]*16.0) as u32,(regions[0].uv_min[1]*16.0) as u32);
+        assert_eq!((left,top),(2,2));
+        //padding repeats the nearest texel, including the corners
+        assert_eq!(pages[0].get_pixel(0,0).0,[255,0,0,255]);
+        assert_eq!(pages[0].get_pixel(5,4).0,[0,0,255,255]);
+        assert_eq!(pages[0].get_pixel(2,3).0,[255,0,0,255]);
+        //outside of the cell the page stays transparent
+        assert_eq!(pages[0].get_pixel(6,0).0,[0,0,0,0]);
+    }
+}
@@ -0,0 +1,602 @@
+use ash::vk;
+
+//cpu decompression of bc1-bc5 and bc7 blocks into r8g8b8a8 texels, and of bc6h blocks into r16g16b16a16 half float texels,
+//for devices without support for a block compressed format.
+//each block covers 4x4 texels, stored row by row.
+type Block=[[u8;4];16];
+type BlockDecoder=Box<dyn Fn(&[u8])->Block>;
+
+//weights of interpolated endpoints (out of 64) by index bit count, used by bc7
+const WEIGHTS_2:[u32;4]=[0,21,43,64];
+const WEIGHTS_3:[u32;8]=[0,9,18,27,37,46,55,64];
+const WEIGHTS_4:[u32;16]=[0,4,9,13,17,21,26,30,34,38,43,47,51,55,60,64];
+
+//subset of each texel (bit set: subset 1) for the 64 two subset partitions
+const PARTITIONS_2:[u16;64]=[
+    0xcccc,0x8888,0xeeee,0xecc8,0xc880,0xfeec,0xfec8,0xec80,0xc800,0xffec,0xfe80,0xe800,0xffe8,0xff00,0xfff0,0xf000,
+    0xf710,0x008e,0x7100,0x08ce,0x008c,0x7310,0x3100,0x8cce,0x088c,0x3110,0x6666,0x366c,0x17e8,0x0ff0,0x718e,0x399c,
+    0xaaaa,0xf0f0,0x5a5a,0x33cc,0x3c3c,0x55aa,0x9696,0xa55a,0x73ce,0x13c8,0x324c,0x3bdc,0x6996,0xc33c,0x9966,0x0660,
+    0x0272,0x04e4,0x4e40,0x2720,0xc936,0x936c,0x39c6,0x639c,0x9336,0x9cc6,0x817e,0xe718,0xccf0,0x0fcc,0x7744,0xee22,
+];
+
+//subset of each texel for the 64 three subset partitions
+const PARTITIONS_3:[[u8;16];64]=[
+    [0,0,1,1,0,0,1,1,0,2,2,1,2,2,2,2],[0,0,0,1,0,0,1,1,2,2,1,1,2,2,2,1],[0,0,0,0,2,0,0,1,2,2,1,1,2,2,1,1],[0,2,2,2,0,0,2,2,0,0,1,1,0,1,1,1],
+    [0,0,0,0,0,0,0,0,1,1,2,2,1,1,2,2],[0,0,1,1,0,0,1,1,0,0,2,2,0,0,2,2],[0,0,2,2,0,0,2,2,1,1,1,1,1,1,1,1],[0,0,1,1,0,0,1,1,2,2,1,1,2,2,1,1],
+    [0,0,0,0,0,0,0,0,1,1,1,1,2,2,2,2],[0,0,0,0,1,1,1,1,1,1,1,1,2,2,2,2],[0,0,0,0,1,1,1,1,2,2,2,2,2,2,2,2],[0,0,1,2,0,0,1,2,0,0,1,2,0,0,1,2],
+    [0,1,1,2,0,1,1,2,0,1,1,2,0,1,1,2],[0,1,2,2,0,1,2,2,0,1,2,2,0,1,2,2],[0,0,1,1,0,1,1,2,1,1,2,2,1,2,2,2],[0,0,1,1,2,0,0,1,2,2,0,0,2,2,2,0],
+    [0,0,0,1,0,0,1,1,0,1,1,2,1,1,2,2],[0,1,1,1,0,0,1,1,2,0,0,1,2,2,0,0],[0,0,0,0,1,1,2,2,1,1,2,2,1,1,2,2],[0,0,2,2,0,0,2,2,0,0,2,2,1,1,1,1],
+    [0,1,1,1,0,1,1,1,0,2,2,2,0,2,2,2],[0,0,0,1,0,0,0,1,2,2,2,1,2,2,2,1],[0,0,0,0,0,0,1,1,0,1,2,2,0,1,2,2],[0,0,0,0,1,1,0,0,2,2,1,0,2,2,1,0],
+    [0,1,2,2,0,1,2,2,0,0,1,1,0,0,0,0],[0,0,1,2,0,0,1,2,1,1,2,2,2,2,2,2],[0,1,1,0,1,2,2,1,1,2,2,1,0,1,1,0],[0,0,0,0,0,1,1,0,1,2,2,1,1,2,2,1],
+    [0,0,2,2,1,1,0,2,1,1,0,2,0,0,2,2],[0,1,1,0,0,1,1,0,2,0,0,2,2,2,2,2],[0,0,1,1,0,1,2,2,0,1,2,2,0,0,1,1],[0,0,0,0,2,0,0,0,2,2,1,1,2,2,2,1],
+    [0,0,0,0,0,0,0,2,1,1,2,2,1,2,2,2],[0,2,2,2,0,0,2,2,0,0,1,2,0,0,1,1],[0,0,1,1,0,0,1,2,0,0,2,2,0,2,2,2],[0,1,2,0,0,1,2,0,0,1,2,0,0,1,2,0],
+    [0,0,0,0,1,1,1,1,2,2,2,2,0,0,0,0],[0,1,2,0,1,2,0,1,2,0,1,2,0,1,2,0],[0,1,2,0,2,0,1,2,1,2,0,1,0,1,2,0],[0,0,1,1,2,2,0,0,1,1,2,2,0,0,1,1],
+    [0,0,1,1,1,1,2,2,2,2,0,0,0,0,1,1],[0,1,0,1,0,1,0,1,2,2,2,2,2,2,2,2],[0,0,0,0,0,0,0,0,2,1,2,1,2,1,2,1],[0,0,2,2,1,1,2,2,0,0,2,2,1,1,2,2],
+    [0,0,2,2,0,0,1,1,0,0,2,2,0,0,1,1],[0,2,2,0,1,2,2,1,0,2,2,0,1,2,2,1],[0,1,0,1,2,2,2,2,2,2,2,2,0,1,0,1],[0,0,0,0,2,1,2,1,2,1,2,1,2,1,2,1],
+    [0,1,0,1,0,1,0,1,0,1,0,1,2,2,2,2],[0,2,2,2,0,1,1,1,0,2,2,2,0,1,1,1],[0,0,0,2,1,1,1,2,0,0,0,2,1,1,1,2],[0,0,0,0,2,1,1,2,2,1,1,2,2,1,1,2],
+    [0,2,2,2,0,1,1,1,0,1,1,1,0,2,2,2],[0,0,0,2,1,1,1,2,1,1,1,2,0,0,0,2],[0,1,1,0,0,1,1,0,0,1,1,0,2,2,2,2],[0,0,0,0,0,0,0,0,2,1,1,2,2,1,1,2],
+    [0,1,1,0,0,1,1,0,2,2,2,2,2,2,2,2],[0,0,2,2,0,0,1,1,0,0,1,1,0,0,2,2],[0,0,2,2,1,1,2,2,1,1,2,2,0,0,2,2],[0,0,0,0,0,0,0,0,0,0,0,0,2,1,1,2],
+    [0,0,0,2,0,0,0,1,0,0,0,2,0,0,0,1],[0,2,2,2,1,2,2,2,0,2,2,2,1,2,2,2],[0,1,0,1,2,2,2,2,2,2,2,2,2,2,2,2],[0,1,1,1,2,0,1,1,2,2,0,1,2,2,2,0],
+];
+
+//texel whose index has its highest bit omitted (always 0), for subset 1 of two subset partitions, and subsets 1 and 2 of three subset partitions
+const ANCHORS_2:[u8;64]=[
+    15,15,15,15,15,15,15,15,15,15,15,15,15,15,15,15,
+    15,2,8,2,2,8,8,15,2,8,2,2,8,8,2,2,
+    15,15,6,8,2,8,15,15,2,8,2,2,2,15,15,6,
+    6,2,6,8,15,15,2,2,15,15,15,15,15,2,2,15,
+];
+const ANCHORS_3_SECOND:[u8;64]=[
+    3,3,15,15,8,3,15,15,8,8,6,6,6,5,3,3,
+    3,3,8,15,3,3,6,10,5,8,8,6,8,5,15,15,
+    8,15,3,5,6,10,8,15,15,3,15,5,15,15,15,15,
+    3,15,5,5,5,8,5,10,5,10,8,13,15,12,3,3,
+];
+const ANCHORS_3_THIRD:[u8;64]=[
+    15,8,8,3,15,15,3,8,15,15,15,15,15,15,15,8,
+    15,8,15,3,15,8,15,8,3,15,6,10,15,15,10,8,
+    15,3,15,10,10,8,9,10,6,15,8,15,3,6,6,8,
+    15,3,15,15,15,15,15,15,15,15,15,15,3,15,15,8,
+];
+
+//layout of one bc7 mode
+struct Bc7Mode{
+    subsets:usize,
+    partition_bits:u32,
+    rotation_bits:u32,
+    index_selection_bits:u32,
+    color_bits:u32,
+    alpha_bits:u32,
+    endpoint_p_bits:bool,//one p-bit per endpoint
+    shared_p_bits:bool,//one p-bit per subset
+    index_bits:u32,
+    secondary_index_bits:u32,
+}
+const BC7_MODES:[Bc7Mode;8]=[
+    Bc7Mode{subsets:3,partition_bits:4,rotation_bits:0,index_selection_bits:0,color_bits:4,alpha_bits:0,endpoint_p_bits:true,shared_p_bits:false,index_bits:3,secondary_index_bits:0},
+    Bc7Mode{subsets:2,partition_bits:6,rotation_bits:0,index_selection_bits:0,color_bits:6,alpha_bits:0,endpoint_p_bits:false,shared_p_bits:true,index_bits:3,secondary_index_bits:0},
+    Bc7Mode{subsets:3,partition_bits:6,rotation_bits:0,index_selection_bits:0,color_bits:5,alpha_bits:0,endpoint_p_bits:false,shared_p_bits:false,index_bits:2,secondary_index_bits:0},
+    Bc7Mode{subsets:2,partition_bits:6,rotation_bits:0,index_selection_bits:0,color_bits:7,alpha_bits:0,endpoint_p_bits:true,shared_p_bits:false,index_bits:2,secondary_index_bits:0},
+    Bc7Mode{subsets:1,partition_bits:0,rotation_bits:2,index_selection_bits:1,color_bits:5,alpha_bits:6,endpoint_p_bits:false,shared_p_bits:false,index_bits:2,secondary_index_bits:3},
+    Bc7Mode{subsets:1,partition_bits:0,rotation_bits:2,index_selection_bits:0,color_bits:7,alpha_bits:8,endpoint_p_bits:false,shared_p_bits:false,index_bits:2,secondary_index_bits:2},
+    Bc7Mode{subsets:1,partition_bits:0,rotation_bits:0,index_selection_bits:0,color_bits:7,alpha_bits:7,endpoint_p_bits:true,shared_p_bits:false,index_bits:4,secondary_index_bits:0},
+    Bc7Mode{subsets:2,partition_bits:6,rotation_bits:0,index_selection_bits:0,color_bits:5,alpha_bits:5,endpoint_p_bits:true,shared_p_bits:false,index_bits:2,secondary_index_bits:0},
+];
+
+//endpoint fields of bc6h modes, w and x are the endpoints of the first subset, y and z those of the second
+const RW:usize=0;
+const GW:usize=1;
+const BW:usize=2;
+const RX:usize=3;
+const GX:usize=4;
+const BX:usize=5;
+const RY:usize=6;
+const GY:usize=7;
+const BY:usize=8;
+const RZ:usize=9;
+const GZ:usize=10;
+const BZ:usize=11;
+
+//layout of one bc6h mode
+struct Bc6hMode{
+    mode:u32,
+    mode_bits:u32,
+    subsets:usize,
+    transformed:bool,//endpoints other than w are stored as deltas to w
+    endpoint_bits:u32,
+    delta_bits:[u32;3],
+    //endpoint bits in the order they are stored: field, then the first and last bit of the field that is read
+    layout:&'static [(usize,u32,u32)],
+}
+const BC6H_MODES:[Bc6hMode;14]=[
+    Bc6hMode{mode:0b00,mode_bits:2,subsets:2,transformed:true,endpoint_bits:10,delta_bits:[5,5,5],layout:&[(GY,4,4),(BY,4,4),(BZ,4,4),(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,4),(GZ,4,4),(GY,0,3),(GX,0,4),(BZ,0,0),(GZ,0,3),(BX,0,4),(BZ,1,1),(BY,0,3),(RY,0,4),(BZ,2,2),(RZ,0,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b01,mode_bits:2,subsets:2,transformed:true,endpoint_bits:7,delta_bits:[6,6,6],layout:&[(GY,5,5),(GZ,4,4),(GZ,5,5),(RW,0,6),(BZ,0,0),(BZ,1,1),(BY,4,4),(GW,0,6),(BY,5,5),(BZ,2,2),(GY,4,4),(BW,0,6),(BZ,3,3),(BZ,5,5),(BZ,4,4),(RX,0,5),(GY,0,3),(GX,0,5),(GZ,0,3),(BX,0,5),(BY,0,3),(RY,0,5),(RZ,0,5)]},
+    Bc6hMode{mode:0b00010,mode_bits:5,subsets:2,transformed:true,endpoint_bits:11,delta_bits:[5,4,4],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,4),(RW,10,10),(GY,0,3),(GX,0,3),(GW,10,10),(BZ,0,0),(GZ,0,3),(BX,0,3),(BW,10,10),(BZ,1,1),(BY,0,3),(RY,0,4),(BZ,2,2),(RZ,0,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b00110,mode_bits:5,subsets:2,transformed:true,endpoint_bits:11,delta_bits:[4,5,4],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,3),(RW,10,10),(GZ,4,4),(GY,0,3),(GX,0,4),(GW,10,10),(GZ,0,3),(BX,0,3),(BW,10,10),(BZ,1,1),(BY,0,3),(RY,0,3),(BZ,0,0),(BZ,2,2),(RZ,0,3),(GY,4,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b01010,mode_bits:5,subsets:2,transformed:true,endpoint_bits:11,delta_bits:[4,4,5],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,3),(RW,10,10),(BY,4,4),(GY,0,3),(GX,0,3),(GW,10,10),(BZ,0,0),(GZ,0,3),(BX,0,4),(BW,10,10),(BY,0,3),(RY,0,3),(BZ,1,1),(BZ,2,2),(RZ,0,3),(BZ,4,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b01110,mode_bits:5,subsets:2,transformed:true,endpoint_bits:9,delta_bits:[5,5,5],layout:&[(RW,0,8),(BY,4,4),(GW,0,8),(GY,4,4),(BW,0,8),(BZ,4,4),(RX,0,4),(GZ,4,4),(GY,0,3),(GX,0,4),(BZ,0,0),(GZ,0,3),(BX,0,4),(BZ,1,1),(BY,0,3),(RY,0,4),(BZ,2,2),(RZ,0,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b10010,mode_bits:5,subsets:2,transformed:true,endpoint_bits:8,delta_bits:[6,5,5],layout:&[(RW,0,7),(GZ,4,4),(BY,4,4),(GW,0,7),(BZ,2,2),(GY,4,4),(BW,0,7),(BZ,3,3),(BZ,4,4),(RX,0,5),(GY,0,3),(GX,0,4),(BZ,0,0),(GZ,0,3),(BX,0,4),(BZ,1,1),(BY,0,3),(RY,0,5),(RZ,0,5)]},
+    Bc6hMode{mode:0b10110,mode_bits:5,subsets:2,transformed:true,endpoint_bits:8,delta_bits:[5,6,5],layout:&[(RW,0,7),(BZ,0,0),(BY,4,4),(GW,0,7),(GY,5,5),(GY,4,4),(BW,0,7),(GZ,5,5),(BZ,4,4),(RX,0,4),(GZ,4,4),(GY,0,3),(GX,0,5),(GZ,0,3),(BX,0,4),(BZ,1,1),(BY,0,3),(RY,0,4),(BZ,2,2),(RZ,0,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b11010,mode_bits:5,subsets:2,transformed:true,endpoint_bits:8,delta_bits:[5,5,6],layout:&[(RW,0,7),(BZ,1,1),(BY,4,4),(GW,0,7),(BY,5,5),(GY,4,4),(BW,0,7),(BZ,5,5),(BZ,4,4),(RX,0,4),(GZ,4,4),(GY,0,3),(GX,0,4),(BZ,0,0),(GZ,0,3),(BX,0,5),(BY,0,3),(RY,0,4),(BZ,2,2),(RZ,0,4),(BZ,3,3)]},
+    Bc6hMode{mode:0b11110,mode_bits:5,subsets:2,transformed:false,endpoint_bits:6,delta_bits:[6,6,6],layout:&[(RW,0,5),(GZ,4,4),(BZ,0,0),(BZ,1,1),(BY,4,4),(GW,0,5),(GY,5,5),(BY,5,5),(BZ,2,2),(GY,4,4),(BW,0,5),(GZ,5,5),(BZ,3,3),(BZ,5,5),(BZ,4,4),(RX,0,5),(GY,0,3),(GX,0,5),(GZ,0,3),(BX,0,5),(BY,0,3),(RY,0,5),(RZ,0,5)]},
+    Bc6hMode{mode:0b00011,mode_bits:5,subsets:1,transformed:false,endpoint_bits:10,delta_bits:[10,10,10],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,9),(GX,0,9),(BX,0,9)]},
+    Bc6hMode{mode:0b00111,mode_bits:5,subsets:1,transformed:true,endpoint_bits:11,delta_bits:[9,9,9],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,8),(RW,10,10),(GX,0,8),(GW,10,10),(BX,0,8),(BW,10,10)]},
+    Bc6hMode{mode:0b01011,mode_bits:5,subsets:1,transformed:true,endpoint_bits:12,delta_bits:[8,8,8],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,7),(RW,11,10),(GX,0,7),(GW,11,10),(BX,0,7),(BW,11,10)]},
+    Bc6hMode{mode:0b01111,mode_bits:5,subsets:1,transformed:true,endpoint_bits:16,delta_bits:[4,4,4],layout:&[(RW,0,9),(GW,0,9),(BW,0,9),(RX,0,3),(RW,15,10),(GX,0,3),(GW,15,10),(BX,0,3),(BW,15,10)]},
+];
+
+//reads bits of a block, starting with the least significant bit of the first byte
+struct BitReader{
+    bits:u128,
+    position:u32,
+}
+impl BitReader{
+    fn new(block:&[u8])->Self{
+        let mut bytes=[0u8;16];
+        bytes[..block.len()].copy_from_slice(block);
+        Self{
+            bits:u128::from_le_bytes(bytes),
+            position:0,
+        }
+    }
+    fn read(&mut self,count:u32)->u32{
+        let value=((self.bits>>self.position) & ((1u128<<count)-1)) as u32;
+        self.position+=count;
+        value
+    }
+}
+
+//expand a value with the given number of bits to 8 bits, by replicating the high bits into the low bits
+fn expand(value:u32,bits:u32)->u8{
+    let value=value<<(8-bits);
+    (value | (value>>bits)) as u8
+}
+
+fn rgb565(color:u16)->[u32;3]{
+    [
+        expand((color>>11) as u32 & 0x1f,5) as u32,
+        expand((color>>5) as u32 & 0x3f,6) as u32,
+        expand(color as u32 & 0x1f,5) as u32,
+    ]
+}
+
+//color part of bc1-bc3.
+//bc1 blocks whose first endpoint is not larger than the second one have 3 colors, and index 3 is black (transparent for bc1 with alpha).
+//bc2 and bc3 blocks always have 4 colors.
+fn decode_color(block:&[u8],three_color_mode:bool,transparent_black:bool)->Block{
+    let color0=u16::from_le_bytes([block[0],block[1]]);
+    let color1=u16::from_le_bytes([block[2],block[3]]);
+    let (c0,c1)=(rgb565(color0),rgb565(color1));
+    let mix=|w0:u32,w1:u32| [0,1,2].iter().map(|&i| ((c0[i]*w0+c1[i]*w1)/(w0+w1)) as u8).collect::<Vec<u8>>();
+    let opaque=|c:&[u8]| [c[0],c[1],c[2],255];
+    let palette=if color0>color1 || !three_color_mode{
+        [opaque(&mix(1,0)),opaque(&mix(0,1)),opaque(&mix(2,1)),opaque(&mix(1,2))]
+    }else{
+        [opaque(&mix(1,0)),opaque(&mix(0,1)),opaque(&mix(1,1)),[0,0,0,if transparent_black{0}else{255}]]
+    };
+    let indices=u32::from_le_bytes([block[4],block[5],block[6],block[7]]);
+    let mut texels=[[0u8;4];16];
+    for (i,texel) in texels.iter_mut().enumerate(){
+        *texel=palette[((indices>>(i*2)) & 3) as usize];
+    }
+    texels
+}
+
+//single channel block of bc3 alpha, bc4 and bc5, as 8 bit unorm or snorm values
+fn decode_channel(block:&[u8],signed:bool)->[u8;16]{
+    let (e0,e1)=if signed{
+        ((block[0] as i8).max(-127) as i32,(block[1] as i8).max(-127) as i32)
+    }else{
+        (block[0] as i32,block[1] as i32)
+    };
+    let (min,max)=if signed{(-127,127)}else{(0,255)};
+    let mut palette=[e0,e1,0,0,0,0,0,0];
+    if e0>e1{
+        for (i,value) in palette.iter_mut().enumerate().skip(2){
+            *value=((8-i as i32)*e0+(i as i32-1)*e1)/7;
+        }
+    }else{
+        for (i,value) in palette.iter_mut().enumerate().take(6).skip(2){
+            *value=((6-i as i32)*e0+(i as i32-1)*e1)/5;
+        }
+        palette[6]=min;
+        palette[7]=max;
+    }
+    let mut indices=[0u8;8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices=u64::from_le_bytes(indices);
+    let mut values=[0u8;16];
+    for (i,value) in values.iter_mut().enumerate(){
+        *value=palette[((indices>>(i*3)) & 7) as usize] as u8;
+    }
+    values
+}
+
+fn decode_bc2(block:&[u8])->Block{
+    let mut texels=decode_color(&block[8..],false,false);
+    for (i,texel) in texels.iter_mut().enumerate(){
+        texel[3]=expand(((block[i/2]>>((i%2)*4)) & 0xf) as u32,4);
+    }
+    texels
+}
+
+fn decode_bc3(block:&[u8])->Block{
+    let mut texels=decode_color(&block[8..],false,false);
+    let alpha=decode_channel(&block[..8],false);
+    for (texel,&alpha) in texels.iter_mut().zip(alpha.iter()){
+        texel[3]=alpha;
+    }
+    texels
+}
+
+//bc4 and bc5 expand into red (and green), with blue 0 and alpha opaque
+fn decode_bc4(block:&[u8],signed:bool)->Block{
+    let one=if signed{127}else{255};
+    let red=decode_channel(block,signed);
+    let mut texels=[[0,0,0,one];16];
+    for (texel,&red) in texels.iter_mut().zip(red.iter()){
+        texel[0]=red;
+    }
+    texels
+}
+fn decode_bc5(block:&[u8],signed:bool)->Block{
+    let mut texels=decode_bc4(&block[..8],signed);
+    let green=decode_channel(&block[8..],signed);
+    for (texel,&green) in texels.iter_mut().zip(green.iter()){
+        texel[1]=green;
+    }
+    texels
+}
+
+fn decode_bc7(block:&[u8])->Block{
+    //the mode is given by the position of the first set bit, blocks without any are invalid and decode to transparent black
+    if block[0]==0{
+        return [[0;4];16];
+    }
+    let mode_index=block[0].trailing_zeros();
+    let mode=&BC7_MODES[mode_index as usize];
+    let mut reader=BitReader::new(block);
+    reader.read(mode_index+1);
+
+    let partition=reader.read(mode.partition_bits) as usize;
+    let rotation=reader.read(mode.rotation_bits);
+    let index_selection=reader.read(mode.index_selection_bits);
+
+    //endpoints of all subsets, channel by channel
+    let endpoint_count=mode.subsets*2;
+    let mut endpoints=[[0u32;4];6];
+    for channel in 0..4{
+        let bits=if channel<3{mode.color_bits}else{mode.alpha_bits};
+        for endpoint in endpoints.iter_mut().take(endpoint_count){
+            endpoint[channel]=reader.read(bits);
+        }
+    }
+
+    //p-bits are appended as the lowest bit of every channel
+    let mut p_bits=[0u32;6];
+    if mode.endpoint_p_bits{
+        for p_bit in p_bits.iter_mut().take(endpoint_count){
+            *p_bit=reader.read(1);
+        }
+    }else if mode.shared_p_bits{
+        for subset in 0..mode.subsets{
+            let p_bit=reader.read(1);
+            p_bits[subset*2]=p_bit;
+            p_bits[subset*2+1]=p_bit;
+        }
+    }
+    let has_p_bits=mode.endpoint_p_bits || mode.shared_p_bits;
+    let mut colors=[[0u32;4];6];
+    for ((color,endpoint),p_bit) in colors.iter_mut().zip(endpoints.iter()).zip(p_bits.iter()).take(endpoint_count){
+        for channel in 0..4{
+            let bits=if channel<3{mode.color_bits}else{mode.alpha_bits};
+            color[channel]=if bits==0{
+                255
+            }else if has_p_bits{
+                expand((endpoint[channel]<<1) | p_bit,bits+1) as u32
+            }else{
+                expand(endpoint[channel],bits) as u32
+            };
+        }
+    }
+
+    let subset_of=|texel:usize| match mode.subsets{
+        1=>0,
+        2=>((PARTITIONS_2[partition]>>texel) & 1) as usize,
+        _=>PARTITIONS_3[partition][texel] as usize,
+    };
+    let is_anchor=|texel:usize| texel==0 || match mode.subsets{
+        1=>false,
+        2=>texel==ANCHORS_2[partition] as usize,
+        _=>texel==ANCHORS_3_SECOND[partition] as usize || texel==ANCHORS_3_THIRD[partition] as usize,
+    };
+
+    let mut indices=[0u32;16];
+    for (texel,index) in indices.iter_mut().enumerate(){
+        *index=reader.read(mode.index_bits-is_anchor(texel) as u32);
+    }
+    //the secondary indices only have an anchor at the first texel
+    let mut secondary_indices=[0u32;16];
+    if mode.secondary_index_bits>0{
+        for (texel,index) in secondary_indices.iter_mut().enumerate(){
+            *index=reader.read(mode.secondary_index_bits-(texel==0) as u32);
+        }
+    }
+
+    let weight=|bits:u32,index:u32| match bits{
+        2=>WEIGHTS_2[index as usize],
+        3=>WEIGHTS_3[index as usize],
+        _=>WEIGHTS_4[index as usize],
+    };
+    let mut texels=[[0u8;4];16];
+    for (texel_index,texel) in texels.iter_mut().enumerate(){
+        let subset=subset_of(texel_index);
+        let (e0,e1)=(colors[subset*2],colors[subset*2+1]);
+        //with secondary indices, one set is used for color and the other for alpha
+        let (color_weight,alpha_weight)=if mode.secondary_index_bits==0{
+            let w=weight(mode.index_bits,indices[texel_index]);
+            (w,w)
+        }else{
+            let primary=weight(mode.index_bits,indices[texel_index]);
+            let secondary=weight(mode.secondary_index_bits,secondary_indices[texel_index]);
+            if index_selection==0{(primary,secondary)}else{(secondary,primary)}
+        };
+        for channel in 0..4{
+            let w=if channel<3{color_weight}else{alpha_weight};
+            texel[channel]=(((64-w)*e0[channel]+w*e1[channel]+32)>>6) as u8;
+        }
+        //rotation swaps alpha with one of the color channels
+        if rotation>0{
+            texel.swap(3,rotation as usize-1);
+        }
+    }
+    texels
+}
+
+fn sign_extend(value:i32,bits:u32)->i32{
+    let shift=32-bits;
+    (value<<shift)>>shift
+}
+
+//scale an endpoint to 16 bits (15 bits and sign for signed blocks), so that the largest value becomes the largest finite half float after interpolation
+fn unquantize_bc6h(value:i32,bits:u32,signed:bool)->i32{
+    if !signed{
+        if bits>=15 || value==0{
+            value
+        }else if value==(1<<bits)-1{
+            0xffff
+        }else{
+            ((value<<16)+0x8000)>>bits
+        }
+    }else if bits>=16{
+        value
+    }else{
+        let magnitude=value.abs();
+        let unquantized=if magnitude==0{
+            0
+        }else if magnitude>=(1<<(bits-1))-1{
+            0x7fff
+        }else{
+            ((magnitude<<15)+0x4000)>>(bits-1)
+        };
+        if value<0{-unquantized}else{unquantized}
+    }
+}
+
+//bits of a half float from an interpolated value
+fn finish_bc6h(value:i32,signed:bool)->u16{
+    if !signed{
+        ((value*31)>>6) as u16
+    }else if value<0{
+        0x8000 | (((-value)*31)>>5) as u16
+    }else{
+        ((value*31)>>5) as u16
+    }
+}
+
+fn decode_bc6h(block:&[u8],signed:bool)->[[u16;4];16]{
+    const ONE:u16=0x3c00;
+    let mut reader=BitReader::new(block);
+    let mut mode_value=reader.read(2);
+    if mode_value>=2{
+        mode_value|=reader.read(3)<<2;
+    }
+    //reserved modes decode to black
+    let mode=match BC6H_MODES.iter().find(|mode| mode.mode==mode_value){
+        Some(mode)=>mode,
+        None=>return [[0,0,0,ONE];16],
+    };
+
+    let mut fields=[0i32;12];
+    for &(field,first,last) in mode.layout.iter(){
+        if first<=last{
+            fields[field]|=(reader.read(last-first+1) as i32)<<first;
+        }else{
+            for bit in (last..=first).rev(){
+                fields[field]|=(reader.read(1) as i32)<<bit;
+            }
+        }
+    }
+    let partition=if mode.subsets==2{reader.read(5) as usize}else{0};
+
+    let endpoint_count=mode.subsets*2;
+    let mut endpoints=[[0i32;3];4];
+    for (endpoint_index,endpoint) in endpoints.iter_mut().enumerate().take(endpoint_count){
+        for (channel,value) in endpoint.iter_mut().enumerate(){
+            let field=fields[endpoint_index*3+channel];
+            *value=if endpoint_index==0{
+                if signed{sign_extend(field,mode.endpoint_bits)}else{field}
+            }else if mode.transformed{
+                //deltas are always signed, and wrap around within the endpoint precision
+                let sum=(fields[channel]+sign_extend(field,mode.delta_bits[channel])) & ((1<<mode.endpoint_bits)-1);
+                if signed{sign_extend(sum,mode.endpoint_bits)}else{sum}
+            }else if signed{
+                sign_extend(field,mode.endpoint_bits)
+            }else{
+                field
+            };
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count){
+        for value in endpoint.iter_mut(){
+            *value=unquantize_bc6h(*value,mode.endpoint_bits,signed);
+        }
+    }
+
+    //the first texel of each subset has one index bit less, its highest bit is 0
+    let (index_bits,anchor)=if mode.subsets==2{(3,ANCHORS_2[partition] as usize)}else{(4,0)};
+    let mut texels=[[0,0,0,ONE];16];
+    for (texel_index,texel) in texels.iter_mut().enumerate(){
+        let bits=if texel_index==0 || (mode.subsets==2 && texel_index==anchor){index_bits-1}else{index_bits};
+        let index=reader.read(bits) as usize;
+        let weight=if index_bits==3{WEIGHTS_3[index]}else{WEIGHTS_4[index]} as i32;
+        let subset=if mode.subsets==2{((PARTITIONS_2[partition]>>texel_index) & 1) as usize}else{0};
+        let (e0,e1)=(endpoints[subset*2],endpoints[subset*2+1]);
+        for channel in 0..3{
+            texel[channel]=finish_bc6h(((64-weight)*e0[channel]+weight*e1[channel]+32)>>6,signed);
+        }
+    }
+    texels
+}
+
+//decode all blocks of an image into tightly packed texels of TEXEL_SIZE bytes
+fn decode_blocks<const TEXEL_SIZE:usize>(format:vk::Format,width:u32,height:u32,data:&[u8],block_size:usize,decode:impl Fn(&[u8])->[[u8;TEXEL_SIZE];16])->Vec<u8>{
+    let blocks_x=width.div_ceil(4) as usize;
+    let blocks_y=height.div_ceil(4) as usize;
+    if data.len()<blocks_x*blocks_y*block_size{
+        panic!("{}x{} image in format {:?} requires {} bytes, but got {}",width,height,format,blocks_x*blocks_y*block_size,data.len());
+    }
+    let (width,height)=(width as usize,height as usize);
+    let mut pixels=vec![0u8;width*height*TEXEL_SIZE];
+    for block_y in 0..blocks_y{
+        for block_x in 0..blocks_x{
+            let offset=(block_y*blocks_x+block_x)*block_size;
+            let texels=decode(&data[offset..offset+block_size]);
+            //blocks on the right and bottom edge may extend beyond the image
+            for (i,texel) in texels.iter().enumerate(){
+                let (x,y)=(block_x*4+i%4,block_y*4+i/4);
+                if x<width && y<height{
+                    let pixel_offset=(y*width+x)*TEXEL_SIZE;
+                    pixels[pixel_offset..pixel_offset+TEXEL_SIZE].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+    pixels
+}
+
+//decompress one level of a block compressed image into tightly packed r8g8b8a8 (or r16g16b16a16 half float for bc6h) texels, and the format of the result.
+//returns None for formats that cannot be decompressed on the cpu.
+pub fn decompress(format:vk::Format,width:u32,height:u32,data:&[u8])->Option<(vk::Format,Vec<u8>)>{
+    if format==vk::Format::BC6H_UFLOAT_BLOCK || format==vk::Format::BC6H_SFLOAT_BLOCK{
+        let signed=format==vk::Format::BC6H_SFLOAT_BLOCK;
+        let pixels=decode_blocks(format,width,height,data,16,|block|{
+            decode_bc6h(block,signed).map(|texel|{
+                let mut bytes=[0u8;8];
+                for (channel,value) in texel.iter().enumerate(){
+                    bytes[channel*2..channel*2+2].copy_from_slice(&value.to_le_bytes());
+                }
+                bytes
+            })
+        });
+        return Some((vk::Format::R16G16B16A16_SFLOAT,pixels));
+    }
+
+    let (decode,block_size,decompressed_format):(BlockDecoder,usize,vk::Format)=match format{
+        vk::Format::BC1_RGB_UNORM_BLOCK=>(Box::new(|block| decode_color(block,true,false)),8,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC1_RGB_SRGB_BLOCK=>(Box::new(|block| decode_color(block,true,false)),8,vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC1_RGBA_UNORM_BLOCK=>(Box::new(|block| decode_color(block,true,true)),8,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC1_RGBA_SRGB_BLOCK=>(Box::new(|block| decode_color(block,true,true)),8,vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC2_UNORM_BLOCK=>(Box::new(decode_bc2),16,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC2_SRGB_BLOCK=>(Box::new(decode_bc2),16,vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC3_UNORM_BLOCK=>(Box::new(decode_bc3),16,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC3_SRGB_BLOCK=>(Box::new(decode_bc3),16,vk::Format::R8G8B8A8_SRGB),
+        vk::Format::BC4_UNORM_BLOCK=>(Box::new(|block| decode_bc4(block,false)),8,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC4_SNORM_BLOCK=>(Box::new(|block| decode_bc4(block,true)),8,vk::Format::R8G8B8A8_SNORM),
+        vk::Format::BC5_UNORM_BLOCK=>(Box::new(|block| decode_bc5(block,false)),16,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC5_SNORM_BLOCK=>(Box::new(|block| decode_bc5(block,true)),16,vk::Format::R8G8B8A8_SNORM),
+        vk::Format::BC7_UNORM_BLOCK=>(Box::new(decode_bc7),16,vk::Format::R8G8B8A8_UNORM),
+        vk::Format::BC7_SRGB_BLOCK=>(Box::new(decode_bc7),16,vk::Format::R8G8B8A8_SRGB),
+        _=>return None,
+    };
+
+    Some((decompressed_format,decode_blocks(format,width,height,data,block_size,decode)))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //texel i of a 4x4 block uses index i%4
+    const INDICES:[u8;4]=[0xe4,0xe4,0xe4,0xe4];
+
+    fn bc1_block(color0:u16,color1:u16)->Vec<u8>{
+        [color0.to_le_bytes(),color1.to_le_bytes()].concat().into_iter().chain(INDICES.iter().copied()).collect()
+    }
+
+    fn texels(pixels:&[u8])->Vec<[u8;4]>{
+        pixels.chunks_exact(4).take(4).map(|texel| [texel[0],texel[1],texel[2],texel[3]]).collect()
+    }
+
+    #[test]
+    fn bc1_four_colors(){
+        //white before black: two interpolated colors at 1/3 and 2/3
+        let (format,pixels)=decompress(vk::Format::BC1_RGBA_UNORM_BLOCK,4,4,&bc1_block(0xffff,0x0000)).unwrap();
+        assert_eq!(format,vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(texels(&pixels),vec![[255,255,255,255],[0,0,0,255],[170,170,170,255],[85,85,85,255]]);
+    }
+
+    #[test]
+    fn bc1_three_colors(){
+        //black before white: one interpolated color at 1/2, and black at index 3
+        let (_,pixels)=decompress(vk::Format::BC1_RGB_UNORM_BLOCK,4,4,&bc1_block(0x0000,0xffff)).unwrap();
+        assert_eq!(texels(&pixels),vec![[0,0,0,255],[255,255,255,255],[127,127,127,255],[0,0,0,255]]);
+        //with alpha, index 3 is transparent
+        let (_,pixels)=decompress(vk::Format::BC1_RGBA_SRGB_BLOCK,4,4,&bc1_block(0x0000,0xffff)).unwrap();
+        assert_eq!(texels(&pixels),vec![[0,0,0,255],[255,255,255,255],[127,127,127,255],[0,0,0,0]]);
+        //equal endpoints are also 3 color blocks
+        let (_,pixels)=decompress(vk::Format::BC1_RGB_UNORM_BLOCK,4,4,&bc1_block(0xf800,0xf800)).unwrap();
+        assert_eq!(texels(&pixels),vec![[255,0,0,255],[255,0,0,255],[255,0,0,255],[0,0,0,255]]);
+    }
+
+    #[test]
+    fn bc3_colors_ignore_endpoint_order(){
+        //opaque alpha block, then a color block with black before white, which still has 4 colors
+        let mut block=vec![255,255,0,0,0,0,0,0];
+        block.extend(bc1_block(0x0000,0xffff));
+        let (_,pixels)=decompress(vk::Format::BC3_UNORM_BLOCK,4,4,&block).unwrap();
+        assert_eq!(texels(&pixels),vec![[0,0,0,255],[255,255,255,255],[85,85,85,255],[170,170,170,255]]);
+    }
+
+    #[test]
+    fn bc1_partial_blocks(){
+        //a 5x3 image covers 2x1 blocks, texels outside of the image are dropped
+        let block=bc1_block(0xffff,0x0000);
+        let (_,pixels)=decompress(vk::Format::BC1_RGB_UNORM_BLOCK,5,3,&[block.clone(),block].concat()).unwrap();
+        assert_eq!(pixels.len(),5*3*4);
+        assert_eq!(&pixels[4*4..5*4],&[255,255,255,255]);
+    }
+
+    #[test]
+    fn bc6h_single_subset(){
+        //mode 11 with 10 bit endpoints: w is the largest value, x is 0
+        let mut bits:u128=0b00011;
+        for channel in 0..3{
+            bits|=1023<<(5+channel*10);
+        }
+        //index 15 of texel 1 selects x
+        bits|=0b1111<<(65+3);
+        let (format,pixels)=decompress(vk::Format::BC6H_UFLOAT_BLOCK,4,4,&bits.to_le_bytes()).unwrap();
+        assert_eq!(format,vk::Format::R16G16B16A16_SFLOAT);
+        let half=|texel:usize,channel:usize| u16::from_le_bytes([pixels[texel*8+channel*2],pixels[texel*8+channel*2+1]]);
+        //largest finite half float, opaque
+        assert_eq!([half(0,0),half(0,1),half(0,2),half(0,3)],[0x7bff,0x7bff,0x7bff,0x3c00]);
+        assert_eq!([half(1,0),half(1,1),half(1,2),half(1,3)],[0,0,0,0x3c00]);
+    }
+
+    #[test]
+    fn bc6h_reserved_mode(){
+        let (_,pixels)=decompress(vk::Format::BC6H_SFLOAT_BLOCK,4,4,&[0b10011;16]).unwrap();
+        assert!(pixels.chunks_exact(8).all(|texel| texel==[0,0,0,0,0,0,0x00,0x3c]));
+    }
+}
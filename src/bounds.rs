@@ -0,0 +1,234 @@
+//axis aligned bounding box
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct BoundingBox{
+    pub min:glm::Vec3,
+    pub max:glm::Vec3,
+}
+impl BoundingBox{
+    //box around all points, or an empty box at the origin if there are none
+    pub fn from_points<I:Iterator<Item=glm::Vec3>>(points:I)->Self{
+        let mut bounding_box:Option<Self>=None;
+        for point in points{
+            bounding_box=Some(match bounding_box{
+                Some(b)=>Self{
+                    min:glm::min2(&b.min,&point),
+                    max:glm::max2(&b.max,&point),
+                },
+                None=>Self{
+                    min:point,
+                    max:point,
+                },
+            });
+        }
+        bounding_box.unwrap_or(Self{
+            min:glm::vec3(0.0,0.0,0.0),
+            max:glm::vec3(0.0,0.0,0.0),
+        })
+    }
+
+    pub fn center(&self)->glm::Vec3{
+        (self.min+self.max)*0.5
+    }
+
+    pub fn corners(&self)->[glm::Vec3;8]{
+        [
+            glm::vec3(self.min.x,self.min.y,self.min.z),
+            glm::vec3(self.max.x,self.min.y,self.min.z),
+            glm::vec3(self.min.x,self.max.y,self.min.z),
+            glm::vec3(self.max.x,self.max.y,self.min.z),
+            glm::vec3(self.min.x,self.min.y,self.max.z),
+            glm::vec3(self.max.x,self.min.y,self.max.z),
+            glm::vec3(self.min.x,self.max.y,self.max.z),
+            glm::vec3(self.max.x,self.max.y,self.max.z),
+        ]
+    }
+
+    //axis aligned box around this box after transformation
+    pub fn transformed(&self,transform:&glm::Mat4)->Self{
+        Self::from_points(self.corners().iter().map(|corner|{
+            glm::vec4_to_vec3(&(transform*glm::vec4(corner.x,corner.y,corner.z,1.0)))
+        }))
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct BoundingSphere{
+    pub center:glm::Vec3,
+    pub radius:f32,
+}
+impl BoundingSphere{
+    //sphere around the center of the bounding box of the points (not minimal, but cheap)
+    pub fn from_points<I:Iterator<Item=glm::Vec3>>(points:I,bounding_box:&BoundingBox)->Self{
+        let center=bounding_box.center();
+        let radius=points.map(|point| glm::distance(&center,&point)).fold(0.0,f32::max);
+        Self{
+            center,
+            radius,
+        }
+    }
+
+    //sphere around this sphere after transformation, scaled by the largest axis scale
+    pub fn transformed(&self,transform:&glm::Mat4)->Self{
+        let center=glm::vec4_to_vec3(&(transform*glm::vec4(self.center.x,self.center.y,self.center.z,1.0)));
+        let max_scale=(0..3).map(|column| glm::length(&glm::vec4_to_vec3(&transform.column(column).into_owned()))).fold(0.0,f32::max);
+        Self{
+            center,
+            radius:self.radius*max_scale,
+        }
+    }
+}
+
+//view frustum as 6 inward facing planes (xyz is the normal, w the distance), in world space
+#[derive(Debug,Clone,Copy)]
+pub struct Frustum{
+    pub planes:[glm::Vec4;6],
+}
+impl Frustum{
+    //extract planes from a combined projection*view matrix (with clip space depth in [-w;w], as produced by Camera::projection)
+    pub fn from_matrix(projection_view:&glm::Mat4)->Self{
+        let row=|i:usize| projection_view.row(i).transpose().into_owned();
+        let mut planes=[
+            row(3)+row(0),//left
+            row(3)-row(0),//right
+            row(3)+row(1),//bottom
+            row(3)-row(1),//top
+            row(3)+row(2),//near
+            row(3)-row(2),//far
+        ];
+        for plane in planes.iter_mut(){
+            let length=glm::length(&glm::vec4_to_vec3(plane));
+            if length>0.0{
+                *plane/=length;
+            }
+        }
+        Self{
+            planes,
+        }
+    }
+
+    fn distance(plane:&glm::Vec4,point:&glm::Vec3)->f32{
+        plane.x*point.x+plane.y*point.y+plane.z*point.z+plane.w
+    }
+
+    pub fn intersects_sphere(&self,sphere:&BoundingSphere)->bool{
+        self.planes.iter().all(|plane| Self::distance(plane,&sphere.center)>=-sphere.radius)
+    }
+
+    //conservative test: may return true for some boxes just outside of the frustum corners
+    pub fn intersects_box(&self,bounding_box:&BoundingBox)->bool{
+        self.planes.iter().all(|plane|{
+            //corner furthest along the plane normal
+            let positive_vertex=glm::vec3(
+                if plane.x>=0.0{bounding_box.max.x}else{bounding_box.min.x},
+                if plane.y>=0.0{bounding_box.max.y}else{bounding_box.min.y},
+                if plane.z>=0.0{bounding_box.max.z}else{bounding_box.min.z},
+            );
+            Self::distance(plane,&positive_vertex)>=0.0
+        })
+    }
+
+    //test mesh bounds after transformation into world space, sphere first because it is cheaper
+    pub fn intersects(&self,bounding_sphere:&BoundingSphere,bounding_box:&BoundingBox,transform:&glm::Mat4)->bool{
+        self.intersects_sphere(&bounding_sphere.transformed(transform)) && self.intersects_box(&bounding_box.transformed(transform))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::camera::Camera;
+
+    //camera at z=5 looking at the origin, with a 90 degree field of view and a square viewport
+    //so the side planes go through the eye at 45 degrees, near is at z=4 and far at z=-95
+    fn frustum()->Frustum{
+        let mut camera=Camera::new(glm::vec3(0.0,0.0,5.0),glm::vec3(0.0,0.0,0.0));
+        camera.fov_y=std::f32::consts::FRAC_PI_2;
+        camera.near=1.0;
+        camera.far=100.0;
+        let extent=ash::vk::Extent2D{width:600,height:600};
+        Frustum::from_matrix(&(camera.projection(extent)*camera.view()))
+    }
+
+    fn sphere(x:f32,y:f32,z:f32,radius:f32)->BoundingSphere{
+        BoundingSphere{
+            center:glm::vec3(x,y,z),
+            radius,
+        }
+    }
+
+    fn unit_box(x:f32,y:f32,z:f32)->BoundingBox{
+        BoundingBox{
+            min:glm::vec3(x-0.5,y-0.5,z-0.5),
+            max:glm::vec3(x+0.5,y+0.5,z+0.5),
+        }
+    }
+
+    #[test]
+    fn planes(){
+        let s=std::f32::consts::FRAC_1_SQRT_2;
+        let expected=[
+            glm::vec4(s,0.0,-s,5.0*s),//left
+            glm::vec4(-s,0.0,-s,5.0*s),//right
+            glm::vec4(0.0,s,-s,5.0*s),//bottom
+            glm::vec4(0.0,-s,-s,5.0*s),//top
+            glm::vec4(0.0,0.0,-1.0,4.0),//near
+            glm::vec4(0.0,0.0,1.0,95.0),//far
+        ];
+        for (plane,expected) in frustum().planes.iter().zip(expected.iter()){
+            assert!(glm::distance(plane,expected)<1e-3,"{:?} is not {:?}",plane,expected);
+        }
+    }
+
+    #[test]
+    fn sphere_culling(){
+        let frustum=frustum();
+        assert!(frustum.intersects_sphere(&sphere(0.0,0.0,0.0,1.0)));
+        assert!(frustum.intersects_sphere(&sphere(0.0,0.0,-90.0,1.0)));
+        //outside on each side
+        assert!(!frustum.intersects_sphere(&sphere(-10.0,0.0,0.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(10.0,0.0,0.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0,-10.0,0.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0,10.0,0.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0,0.0,6.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0,0.0,-200.0,1.0)));
+        //center just outside the right plane, but the sphere reaches into the frustum
+        assert!(frustum.intersects_sphere(&sphere(5.5,0.0,0.0,1.0)));
+        assert!(!frustum.intersects_sphere(&sphere(5.5,0.0,0.0,0.3)));
+        //straddling the near plane
+        assert!(frustum.intersects_sphere(&sphere(0.0,0.0,4.5,1.0)));
+    }
+
+    #[test]
+    fn box_culling(){
+        let frustum=frustum();
+        assert!(frustum.intersects_box(&unit_box(0.0,0.0,0.0)));
+        assert!(frustum.intersects_box(&unit_box(0.0,0.0,4.0)));
+        assert!(!frustum.intersects_box(&unit_box(10.0,0.0,0.0)));
+        assert!(!frustum.intersects_box(&unit_box(0.0,-10.0,0.0)));
+        assert!(!frustum.intersects_box(&unit_box(0.0,0.0,5.0)));
+        assert!(!frustum.intersects_box(&unit_box(0.0,0.0,-96.0)));
+        //box that contains the whole frustum
+        assert!(frustum.intersects_box(&BoundingBox{
+            min:glm::vec3(-1000.0,-1000.0,-1000.0),
+            max:glm::vec3(1000.0,1000.0,1000.0),
+        }));
+    }
+
+    #[test]
+    fn transformed_bounds(){
+        let frustum=frustum();
+        let bounding_box=unit_box(0.0,0.0,0.0);
+        let bounding_sphere=BoundingSphere::from_points(bounding_box.corners().iter().copied(),&bounding_box);
+        assert!((bounding_sphere.radius-0.75f32.sqrt()).abs()<1e-6);
+
+        assert!(frustum.intersects(&bounding_sphere,&bounding_box,&glm::identity()));
+        assert!(!frustum.intersects(&bounding_sphere,&bounding_box,&glm::translation(&glm::vec3(20.0,0.0,0.0))));
+        //scaled up, the box reaches back into the frustum
+        let transform=glm::translation(&glm::vec3(20.0,0.0,0.0))*glm::scaling(&glm::vec3(30.0,1.0,1.0));
+        assert!(frustum.intersects(&bounding_sphere,&bounding_box,&transform));
+        assert_eq!(bounding_box.transformed(&transform),BoundingBox{
+            min:glm::vec3(5.0,-0.5,-0.5),
+            max:glm::vec3(35.0,0.5,0.5),
+        });
+    }
+}
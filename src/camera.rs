@@ -0,0 +1,52 @@
+use crate::bounds::BoundingSphere;
+
+//perspective camera looking from eye at target
+#[derive(Debug,Clone,Copy)]
+pub struct Camera{
+    pub eye:glm::Vec3,
+    pub target:glm::Vec3,
+    pub up:glm::Vec3,
+    //vertical field of view, in radians
+    pub fov_y:f32,
+    pub near:f32,
+    pub far:f32,
+}
+impl Camera{
+    pub fn new(eye:glm::Vec3,target:glm::Vec3)->Self{
+        Self{
+            eye,
+            target,
+            up:glm::vec3(0.0,1.0,0.0),
+            fov_y:glm::radians(&glm::vec1(80.0)).x,
+            near:0.001,
+            far:1000.0,
+        }
+    }
+
+    pub fn view(&self)->glm::Mat4{
+        glm::look_at(&self.eye,&self.target,&self.up)
+    }
+
+    pub fn projection(&self,extent:ash::vk::Extent2D)->glm::Mat4{
+        glm::perspective_fov(self.fov_y,extent.width as f32,extent.height as f32,self.near,self.far)
+    }
+
+    //distance from camera to a point, used to sort transparent objects
+    pub fn distance_to(&self,point:&glm::Vec3)->f32{
+        glm::distance(&self.eye,point)
+    }
+
+    //diameter of the sphere on screen, relative to the screen height (1 fills the screen vertically)
+    pub fn projected_size(&self,sphere:&BoundingSphere)->f32{
+        let distance=self.distance_to(&sphere.center);
+        if distance<=sphere.radius{
+            return f32::INFINITY;
+        }
+        sphere.radius/(distance*(self.fov_y*0.5).tan())
+    }
+}
+impl Default for Camera{
+    fn default()->Self{
+        Self::new(glm::vec3(2.0,2.0,2.0),glm::vec3(0.0,0.0,0.0))
+    }
+}
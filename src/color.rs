@@ -0,0 +1,81 @@
+use ash::vk;
+
+//how the contents of a texture are interpreted when sampled
+//
+//color textures (e.g. albedo) are stored sRGB encoded and are converted to linear values by the sampler,
+//data textures (e.g. normal maps, roughness) are sampled as stored
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub enum TextureUsage{
+    Color,
+    Data,
+}
+impl TextureUsage{
+    //format of an r8g8b8a8 texture with this usage
+    pub fn rgba8_format(&self)->vk::Format{
+        match self{
+            TextureUsage::Color=>vk::Format::R8G8B8A8_SRGB,
+            TextureUsage::Data=>vk::Format::R8G8B8A8_UNORM,
+        }
+    }
+}
+
+//whether values are sRGB encoded in memory, and converted to/from linear when read/written by the gpu
+pub fn is_srgb(format:vk::Format)->bool{
+    matches!(format,
+        vk::Format::R8_SRGB
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R8G8B8_SRGB
+        | vk::Format::B8G8R8_SRGB
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A8B8G8R8_SRGB_PACK32
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+    )
+}
+
+//sRGB transfer function, both sides in [0,1]
+pub fn srgb_to_linear(c:f32)->f32{
+    if c<=0.04045{
+        c/12.92
+    }else{
+        ((c+0.055)/1.055).powf(2.4)
+    }
+}
+pub fn linear_to_srgb(c:f32)->f32{
+    if c<=0.0031308{
+        c*12.92
+    }else{
+        1.055*c.powf(1.0/2.4)-0.055
+    }
+}
+
+//pick the surface format of a swapchain
+//
+//shaders write linear values, so sRGB formats are preferred (the hardware encodes on write), and 8 bit unorm
+//formats are only used if no sRGB format is available. offscreen targets use the same format, because they
+//are rendered with the same render pass.
+pub fn choose_surface_format(surface_formats:&[vk::SurfaceFormatKHR])->vk::SurfaceFormatKHR{
+    //if the only supported format is 'undefined', there is no preferred format for the surface
+    if surface_formats.len()==1 && surface_formats[0].format==vk::Format::UNDEFINED{
+        return vk::SurfaceFormatKHR{
+            format:vk::Format::R8G8B8A8_SRGB,
+            color_space:vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        };
+    }
+    for preferred in &[
+        vk::Format::B8G8R8A8_SRGB,
+        vk::Format::R8G8B8A8_SRGB,
+        vk::Format::B8G8R8A8_UNORM,
+        vk::Format::R8G8B8A8_UNORM,
+    ]{
+        if let Some(format)=surface_formats.iter().find(|format| format.format==*preferred && format.color_space==vk::ColorSpaceKHR::SRGB_NONLINEAR){
+            return *format;
+        }
+    }
+    //use first available format otherwise
+    surface_formats[0]
+}
@@ -0,0 +1,85 @@
+use ash::vk;
+
+use crate::{Decoder,Image};
+use crate::bcn;
+
+//texture read from a container file (ktx2, dds), with all mip levels in the stored format, starting with the full size level
+pub struct TextureData{
+    pub format:vk::Format,
+    pub width:u32,
+    pub height:u32,
+    pub levels:Vec<Vec<u8>>,
+}
+
+//texel block width, height and size in bytes of formats that can be read from container files
+//uncompressed formats have 1x1 blocks
+pub fn format_block(format:vk::Format)->Option<(u32,u32,usize)>{
+    Some(match format{
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB=>(1,1,1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB=>(1,1,2),
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB=>(1,1,4),
+        vk::Format::R16_UNORM | vk::Format::R16_SFLOAT=>(1,1,2),
+        vk::Format::R16G16_UNORM | vk::Format::R16G16_SFLOAT | vk::Format::R32_SFLOAT=>(1,1,4),
+        vk::Format::R16G16B16A16_UNORM | vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_SFLOAT=>(1,1,8),
+        vk::Format::R32G32B32A32_SFLOAT=>(1,1,16),
+
+        vk::Format::BC1_RGB_UNORM_BLOCK | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK | vk::Format::BC4_SNORM_BLOCK=>(4,4,8),
+        vk::Format::BC2_UNORM_BLOCK | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK | vk::Format::BC7_SRGB_BLOCK=>(4,4,16),
+
+        vk::Format::ETC2_R8G8B8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::EAC_R11_UNORM_BLOCK | vk::Format::EAC_R11_SNORM_BLOCK=>(4,4,8),
+        vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::EAC_R11G11_UNORM_BLOCK | vk::Format::EAC_R11G11_SNORM_BLOCK=>(4,4,16),
+
+        //all astc formats use 16 byte blocks of varying size
+        vk::Format::ASTC_4X4_UNORM_BLOCK | vk::Format::ASTC_4X4_SRGB_BLOCK=>(4,4,16),
+        vk::Format::ASTC_5X4_UNORM_BLOCK | vk::Format::ASTC_5X4_SRGB_BLOCK=>(5,4,16),
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK=>(5,5,16),
+        vk::Format::ASTC_6X5_UNORM_BLOCK | vk::Format::ASTC_6X5_SRGB_BLOCK=>(6,5,16),
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK=>(6,6,16),
+        vk::Format::ASTC_8X5_UNORM_BLOCK | vk::Format::ASTC_8X5_SRGB_BLOCK=>(8,5,16),
+        vk::Format::ASTC_8X6_UNORM_BLOCK | vk::Format::ASTC_8X6_SRGB_BLOCK=>(8,6,16),
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK=>(8,8,16),
+        vk::Format::ASTC_10X5_UNORM_BLOCK | vk::Format::ASTC_10X5_SRGB_BLOCK=>(10,5,16),
+        vk::Format::ASTC_10X6_UNORM_BLOCK | vk::Format::ASTC_10X6_SRGB_BLOCK=>(10,6,16),
+        vk::Format::ASTC_10X8_UNORM_BLOCK | vk::Format::ASTC_10X8_SRGB_BLOCK=>(10,8,16),
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK=>(10,10,16),
+        vk::Format::ASTC_12X10_UNORM_BLOCK | vk::Format::ASTC_12X10_SRGB_BLOCK=>(12,10,16),
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK=>(12,12,16),
+        _=>return None,
+    })
+}
+
+//size in bytes of a tightly packed image of the given size
+pub fn level_size(format:vk::Format,width:u32,height:u32)->usize{
+    let (block_width,block_height,block_size)=format_block(format).unwrap_or_else(|| panic!("unsupported texture format {:?}",format));
+    width.div_ceil(block_width) as usize*height.div_ceil(block_height) as usize*block_size
+}
+
+impl Decoder{
+    //upload texture data with all of its levels, decompressing them on the cpu if the device cannot sample the format
+    pub fn create_texture_from_data(&mut self,name:String,data:&TextureData,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let levels:Vec<&[u8]>=data.levels.iter().map(|level| &level[..]).collect();
+        if self.format_supports(data.format,vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR){
+            return self.create_texture_with_mips(name,data.format,data.width,data.height,&levels,command_buffer);
+        }
+
+        let mut decompressed_format=vk::Format::UNDEFINED;
+        let decompressed:Vec<Vec<u8>>=levels.iter().enumerate().map(|(level,bytes)|{
+            let (width,height)=crate::mipmap::mip_extent(data.width,data.height,level as u32);
+            let (format,pixels)=bcn::decompress(data.format,width,height,bytes).unwrap_or_else(|| panic!("texture {} has format {:?}, which is not supported by the device and cannot be decompressed",name,data.format));
+            decompressed_format=format;
+            pixels
+        }).collect();
+        let levels:Vec<&[u8]>=decompressed.iter().map(|level| &level[..]).collect();
+        self.create_texture_with_mips(name,decompressed_format,data.width,data.height,&levels,command_buffer)
+    }
+}
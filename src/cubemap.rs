@@ -0,0 +1,207 @@
+use ash::vk;
+
+use crate::{Decoder,Image};
+use crate::color::TextureUsage;
+use crate::float_image::{self,FloatImage};
+use crate::exr;
+
+//faces in vulkan layer order: +x,-x,+y,-y,+z,-z
+pub const FACE_COUNT:usize=6;
+
+//how the faces of a cubemap are arranged in a single image, detected from its aspect ratio
+#[derive(PartialEq,Eq,Debug,Clone,Copy)]
+pub enum CubemapLayout{
+    //4x3 faces: +y above, then -x,+z,+x,-z, then -y below
+    HorizontalCross,
+    //3x4 faces: +y, then -x,+z,+x, then -y, then -z (rotated by 180 degrees)
+    VerticalCross,
+    //2:1 panorama, longitude from left to right and latitude from top to bottom, centered on the +z face
+    Equirectangular,
+}
+impl CubemapLayout{
+    pub fn detect(width:u32,height:u32)->Option<Self>{
+        if width*3==height*4{
+            Some(CubemapLayout::HorizontalCross)
+        }else if width*4==height*3{
+            Some(CubemapLayout::VerticalCross)
+        }else if width==height*2{
+            Some(CubemapLayout::Equirectangular)
+        }else{
+            None
+        }
+    }
+}
+
+//decoded image and the formats it can be stored in, by preference (see float_image::image_formats)
+fn load_image(filename:&str,usage:TextureUsage)->(FloatImage,&'static [vk::Format]){
+    let extension=std::path::Path::new(filename).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    match extension.as_deref(){
+        Some("hdr")=>(FloatImage::load_radiance(filename),&float_image::HALF_FLOAT_FORMATS),
+        Some("exr")=>{
+            let bytes=std::fs::read(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+            let (image,full_precision)=exr::parse(filename,&bytes);
+            (image,if full_precision{&float_image::FULL_FLOAT_FORMATS}else{&float_image::HALF_FLOAT_FORMATS})
+        },
+        _=>{
+            let native_image=image::open(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+            let formats:&'static [vk::Format]=match float_image::image_formats(&native_image,usage){
+                Some(formats)=>formats,
+                None=>match usage{
+                    TextureUsage::Color=>&[vk::Format::R8G8B8A8_SRGB],
+                    TextureUsage::Data=>&[vk::Format::R8G8B8A8_UNORM],
+                },
+            };
+            (FloatImage::from_image(&native_image,usage),formats)
+        },
+    }
+}
+
+//square region of the image with the given top left corner
+fn crop(image:&FloatImage,x:u32,y:u32,size:u32)->FloatImage{
+    let channels=image.channels;
+    let mut values=Vec::with_capacity((size*size) as usize*channels);
+    for row in y..y+size{
+        let start=(row*image.width+x) as usize*channels;
+        values.extend_from_slice(&image.values[start..start+size as usize*channels]);
+    }
+    FloatImage{
+        width:size,
+        height:size,
+        channels,
+        values,
+    }
+}
+
+fn rotate_180(image:&FloatImage)->FloatImage{
+    FloatImage{
+        width:image.width,
+        height:image.height,
+        channels:image.channels,
+        values:image.values.chunks_exact(image.channels).rev().flatten().copied().collect(),
+    }
+}
+
+//direction through a texel of a face, s and t are in [-1,1] from left to right and top to bottom
+//(see the cube map face selection table of the vulkan specification)
+fn face_direction(face:usize,s:f32,t:f32)->[f32;3]{
+    match face{
+        0=>[1.0,-t,-s],
+        1=>[-1.0,-t,s],
+        2=>[s,1.0,t],
+        3=>[s,-1.0,-t],
+        4=>[s,-t,1.0],
+        _=>[-s,-t,-1.0],
+    }
+}
+
+//bilinear sample at u,v in [0,1], wrapping horizontally and clamping vertically
+fn sample(image:&FloatImage,u:f32,v:f32,texel:&mut [f32]){
+    let x=u*image.width as f32-0.5;
+    let y=(v*image.height as f32-0.5).clamp(0.0,(image.height-1) as f32);
+    let (x0,y0)=(x.floor(),y.floor());
+    let (fx,fy)=(x-x0,y-y0);
+    let column=|x:i64| x.rem_euclid(image.width as i64) as usize;
+    let (column0,column1)=(column(x0 as i64),column(x0 as i64+1));
+    let row0=y0 as usize;
+    let row1=(row0+1).min(image.height as usize-1);
+    let channels=image.channels;
+    let at=|row:usize,column:usize,channel:usize| image.values[(row*image.width as usize+column)*channels+channel];
+    for (channel,value) in texel.iter_mut().enumerate(){
+        let top=at(row0,column0,channel)*(1.0-fx)+at(row0,column1,channel)*fx;
+        let bottom=at(row1,column0,channel)*(1.0-fx)+at(row1,column1,channel)*fx;
+        *value=top*(1.0-fy)+bottom*fy;
+    }
+}
+
+//faces of the given size, resampled from an equirectangular panorama
+fn equirectangular_faces(image:&FloatImage,size:u32)->Vec<FloatImage>{
+    let channels=image.channels;
+    (0..FACE_COUNT).map(|face|{
+        let mut values=vec![0.0;(size*size) as usize*channels];
+        for (index,texel) in values.chunks_exact_mut(channels).enumerate(){
+            let (x,y)=(index as u32%size,index as u32/size);
+            let s=(x as f32+0.5)/size as f32*2.0-1.0;
+            let t=(y as f32+0.5)/size as f32*2.0-1.0;
+            let [dx,dy,dz]=face_direction(face,s,t);
+            let length=(dx*dx+dy*dy+dz*dz).sqrt();
+            let longitude=dx.atan2(dz);
+            let latitude=(dy/length).clamp(-1.0,1.0).acos();
+            sample(image,0.5+longitude/(2.0*std::f32::consts::PI),latitude/std::f32::consts::PI,texel);
+        }
+        FloatImage{
+            width:size,
+            height:size,
+            channels,
+            values,
+        }
+    }).collect()
+}
+
+//split a cross or panorama into its faces
+pub fn faces_from_image(name:&str,image:&FloatImage)->Vec<FloatImage>{
+    let layout=CubemapLayout::detect(image.width,image.height)
+        .unwrap_or_else(|| panic!("{} is {}x{}, which is neither a cross (4:3 or 3:4) nor a panorama (2:1)",name,image.width,image.height));
+    match layout{
+        CubemapLayout::HorizontalCross=>{
+            let size=image.width/4;
+            [(2,1),(0,1),(1,0),(1,2),(1,1),(3,1)].iter().map(|&(column,row)| crop(image,column*size,row*size,size)).collect()
+        },
+        CubemapLayout::VerticalCross=>{
+            let size=image.width/3;
+            let mut faces:Vec<FloatImage>=[(2,1),(0,1),(1,0),(1,2),(1,1)].iter().map(|&(column,row)| crop(image,column*size,row*size,size)).collect();
+            faces.push(rotate_180(&crop(image,size,3*size,size)));
+            faces
+        },
+        CubemapLayout::Equirectangular=>equirectangular_faces(image,image.height/2),
+    }
+}
+
+impl Decoder{
+    //load a cubemap from a single image with a cross layout or an equirectangular panorama, cached per file and usage
+    pub fn get_cubemap(&mut self,filename:&str,usage:TextureUsage,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let name=match usage{
+            TextureUsage::Color=>format!("{}#cube",filename),
+            TextureUsage::Data=>format!("{}#cube#data",filename),
+        };
+        if let Some(texture)=self.textures.get(&name){
+            return texture.clone();
+        }
+
+        let (image,formats)=load_image(filename,usage);
+        let faces=faces_from_image(filename,&image);
+        self.create_cubemap(name,&faces,formats,command_buffer)
+    }
+
+    //load a cubemap from one image per face, in order +x,-x,+y,-y,+z,-z
+    pub fn get_cubemap_from_faces(&mut self,filenames:[&str;FACE_COUNT],usage:TextureUsage,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let name=match usage{
+            TextureUsage::Color=>format!("{}#cube",filenames.join(",")),
+            TextureUsage::Data=>format!("{}#cube#data",filenames.join(",")),
+        };
+        if let Some(texture)=self.textures.get(&name){
+            return texture.clone();
+        }
+
+        //all faces are stored in the format of the first one
+        let mut formats:&'static [vk::Format]=&[];
+        let faces:Vec<FloatImage>=filenames.iter().enumerate().map(|(face,filename)|{
+            let (image,face_formats)=load_image(filename,usage);
+            if face==0{
+                formats=face_formats;
+            }
+            image
+        }).collect();
+        self.create_cubemap(name,&faces,formats,command_buffer)
+    }
+
+    //upload 6 square faces of the same size into a cubemap with a full mip chain
+    pub fn create_cubemap(&mut self,name:String,faces:&[FloatImage],formats:&[vk::Format],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        if faces.len()!=FACE_COUNT{
+            panic!("cubemap {} has {} faces instead of {}",name,faces.len(),FACE_COUNT);
+        }
+        if faces.iter().any(|face| face.width!=face.height){
+            panic!("cubemap {} has faces that are not square",name);
+        }
+        self.create_float_texture_layers(name,faces,vk::ImageViewType::CUBE,formats,command_buffer)
+    }
+}
@@ -0,0 +1,134 @@
+use ash::vk;
+
+use crate::color::TextureUsage;
+use crate::compressed_texture::{TextureData,level_size};
+
+const HEADER_FLAGS_MIPMAP_COUNT:u32=0x20000;
+const PIXEL_FORMAT_FLAGS_FOURCC:u32=0x4;
+const PIXEL_FORMAT_FLAGS_RGB:u32=0x40;
+const CAPS2_CUBEMAP:u32=0x200;
+const DX10_RESOURCE_DIMENSION_TEXTURE2D:u32=3;
+const DX10_MISC_TEXTURECUBE:u32=0x4;
+
+fn read_u32(bytes:&[u8],offset:usize)->u32{
+    u32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+
+//vulkan format of a dxgi format from the dx10 header extension
+fn dxgi_format(dxgi_format:u32)->Option<vk::Format>{
+    Some(match dxgi_format{
+        2=>vk::Format::R32G32B32A32_SFLOAT,
+        10=>vk::Format::R16G16B16A16_SFLOAT,
+        11=>vk::Format::R16G16B16A16_UNORM,
+        16=>vk::Format::R32G32_SFLOAT,
+        28=>vk::Format::R8G8B8A8_UNORM,
+        29=>vk::Format::R8G8B8A8_SRGB,
+        31=>vk::Format::R8G8B8A8_SNORM,
+        34=>vk::Format::R16G16_SFLOAT,
+        35=>vk::Format::R16G16_UNORM,
+        41=>vk::Format::R32_SFLOAT,
+        49=>vk::Format::R8G8_UNORM,
+        54=>vk::Format::R16_SFLOAT,
+        56=>vk::Format::R16_UNORM,
+        61=>vk::Format::R8_UNORM,
+        71=>vk::Format::BC1_RGBA_UNORM_BLOCK,
+        72=>vk::Format::BC1_RGBA_SRGB_BLOCK,
+        74=>vk::Format::BC2_UNORM_BLOCK,
+        75=>vk::Format::BC2_SRGB_BLOCK,
+        77=>vk::Format::BC3_UNORM_BLOCK,
+        78=>vk::Format::BC3_SRGB_BLOCK,
+        80=>vk::Format::BC4_UNORM_BLOCK,
+        81=>vk::Format::BC4_SNORM_BLOCK,
+        83=>vk::Format::BC5_UNORM_BLOCK,
+        84=>vk::Format::BC5_SNORM_BLOCK,
+        87=>vk::Format::B8G8R8A8_UNORM,
+        91=>vk::Format::B8G8R8A8_SRGB,
+        95=>vk::Format::BC6H_UFLOAT_BLOCK,
+        96=>vk::Format::BC6H_SFLOAT_BLOCK,
+        98=>vk::Format::BC7_UNORM_BLOCK,
+        99=>vk::Format::BC7_SRGB_BLOCK,
+        _=>return None,
+    })
+}
+
+//format of a legacy pixel format, which does not state whether the data is sRGB encoded, so the usage decides
+fn legacy_format(name:&str,bytes:&[u8],usage:TextureUsage)->vk::Format{
+    let flags=read_u32(bytes,80);
+    let color=usage==TextureUsage::Color;
+    if flags & PIXEL_FORMAT_FLAGS_FOURCC!=0{
+        return match &bytes[84..88]{
+            b"DXT1"=>if color{vk::Format::BC1_RGBA_SRGB_BLOCK}else{vk::Format::BC1_RGBA_UNORM_BLOCK},
+            b"DXT2" | b"DXT3"=>if color{vk::Format::BC2_SRGB_BLOCK}else{vk::Format::BC2_UNORM_BLOCK},
+            b"DXT4" | b"DXT5"=>if color{vk::Format::BC3_SRGB_BLOCK}else{vk::Format::BC3_UNORM_BLOCK},
+            b"ATI1" | b"BC4U"=>vk::Format::BC4_UNORM_BLOCK,
+            b"BC4S"=>vk::Format::BC4_SNORM_BLOCK,
+            b"ATI2" | b"BC5U"=>vk::Format::BC5_UNORM_BLOCK,
+            b"BC5S"=>vk::Format::BC5_SNORM_BLOCK,
+            fourcc=>panic!("{} has unsupported format {}",name,String::from_utf8_lossy(fourcc)),
+        };
+    }
+    //uncompressed 32 bit formats are identified by their channel masks
+    let bit_count=read_u32(bytes,88);
+    let masks=[read_u32(bytes,92),read_u32(bytes,96),read_u32(bytes,100),read_u32(bytes,104)];
+    if flags & PIXEL_FORMAT_FLAGS_RGB!=0 && bit_count==32{
+        if masks==[0xff,0xff00,0xff0000,0xff000000]{
+            return if color{vk::Format::R8G8B8A8_SRGB}else{vk::Format::R8G8B8A8_UNORM};
+        }
+        if masks==[0xff0000,0xff00,0xff,0xff000000]{
+            return if color{vk::Format::B8G8R8A8_SRGB}else{vk::Format::B8G8R8A8_UNORM};
+        }
+    }
+    panic!("{} has unsupported {} bit pixel format with masks {:x?}",name,bit_count,masks);
+}
+
+//dds: magic, 124 byte header (with the pixel format at offset 76), optional dx10 header extension if the fourcc is DX10, then all levels
+//
+//only 2d textures are supported (no arrays, cubemaps or volumes)
+pub fn parse(name:&str,bytes:&[u8],usage:TextureUsage)->TextureData{
+    if bytes.len()<128 || &bytes[..4]!=b"DDS " || read_u32(bytes,4)!=124{
+        panic!("{} is not a dds file",name);
+    }
+    let flags=read_u32(bytes,8);
+    let height=read_u32(bytes,12);
+    let width=read_u32(bytes,16);
+    let level_count=if flags & HEADER_FLAGS_MIPMAP_COUNT!=0{read_u32(bytes,28).max(1)}else{1};
+    let caps2=read_u32(bytes,112);
+    if caps2 & CAPS2_CUBEMAP!=0{
+        panic!("{} is a cubemap, which is not supported",name);
+    }
+
+    let is_dx10=read_u32(bytes,80) & PIXEL_FORMAT_FLAGS_FOURCC!=0 && &bytes[84..88]==b"DX10";
+    let (format,mut offset)=if is_dx10{
+        if bytes.len()<148{
+            panic!("{} is missing the dx10 header",name);
+        }
+        let dxgi=read_u32(bytes,128);
+        let resource_dimension=read_u32(bytes,132);
+        let misc_flags=read_u32(bytes,136);
+        let array_size=read_u32(bytes,140);
+        if resource_dimension!=DX10_RESOURCE_DIMENSION_TEXTURE2D || misc_flags & DX10_MISC_TEXTURECUBE!=0 || array_size>1{
+            panic!("{} is not a 2d texture",name);
+        }
+        (dxgi_format(dxgi).unwrap_or_else(|| panic!("{} has unsupported dxgi format {}",name,dxgi)),148)
+    }else{
+        (legacy_format(name,bytes,usage),128)
+    };
+
+    let levels=(0..level_count).map(|level|{
+        let (level_width,level_height)=crate::mipmap::mip_extent(width,height,level);
+        let length=level_size(format,level_width,level_height);
+        if offset+length>bytes.len(){
+            panic!("{} ends within level {}",name,level);
+        }
+        let data=bytes[offset..offset+length].to_vec();
+        offset+=length;
+        data
+    }).collect();
+
+    TextureData{
+        format,
+        width,
+        height,
+        levels,
+    }
+}
@@ -67,16 +67,48 @@ use ash::{
     extensions,
 };
 
-#[derive(Debug,Clone)]
+use crate::bounds::{BoundingBox,BoundingSphere};
+use crate::vertex_layout::{VertexLayout,VertexLayoutDescription};
+use crate::model::{Model,Submesh,ModelMaterial};
+use crate::sampler::SamplerDescription;
+use crate::mesh_processing;
+use crate::lod::{self,MeshLod};
+use crate::dynamic_mesh::DynamicMesh;
+use crate::mipmap;
+use crate::color::TextureUsage;
+use crate::{ktx2,dds,exr};
+use crate::float_image::{self,FloatImage};
+
+#[repr(C)]
+#[derive(Debug,Clone,VertexLayout)]
 pub struct Vertex{
     //space coordinates
+    #[vertex(POSITION,format=R32G32B32A32_SFLOAT)]
     pub x:f32,
     pub y:f32,
     pub z:f32,
     pub w:f32,
     //teture coordinates
+    #[vertex(TEXCOORD,format=R32G32_SFLOAT)]
     pub u:f32,
     pub v:f32,
+    //normal
+    #[vertex(NORMAL,format=R32G32B32_SFLOAT)]
+    pub nx:f32,
+    pub ny:f32,
+    pub nz:f32,
+    //tangent, w is the bitangent sign (bitangent=w*cross(normal,tangent))
+    #[vertex(TANGENT,format=R32G32B32A32_SFLOAT)]
+    pub tx:f32,
+    pub ty:f32,
+    pub tz:f32,
+    pub tw:f32,
+    //color, multiplied with the texture color by lit pipelines
+    #[vertex(COLOR,format=R32G32B32A32_SFLOAT)]
+    pub r:f32,
+    pub g:f32,
+    pub b:f32,
+    pub a:f32,
 }
 impl Vertex{
     pub fn new(
@@ -94,30 +126,97 @@ impl Vertex{
             w,
             u,
             v,
+            nx:0.0,
+            ny:0.0,
+            nz:0.0,
+            tx:1.0,
+            ty:0.0,
+            tz:0.0,
+            tw:1.0,
+            r:1.0,
+            g:1.0,
+            b:1.0,
+            a:1.0,
+        }
+    }
+
+    pub fn with_normal(self,nx:f32,ny:f32,nz:f32)->Self{
+        Self{
+            nx,
+            ny,
+            nz,
+            ..self
+        }
+    }
+
+    pub fn with_tangent(self,tx:f32,ty:f32,tz:f32,tw:f32)->Self{
+        Self{
+            tx,
+            ty,
+            tz,
+            tw,
+            ..self
+        }
+    }
+
+    pub fn with_color(self,r:f32,g:f32,b:f32,a:f32)->Self{
+        Self{
+            r,
+            g,
+            b,
+            a,
+            ..self
         }
     }
 }
 
+#[repr(C)]
 #[derive(Debug,Clone,Copy)]
 pub struct VertexIndices{
-    a:u16,
-    b:u16,
-    c:u16,
+    a:u32,
+    b:u32,
+    c:u32,
 }
 impl VertexIndices{
-    pub fn new(a:u16,b:u16,c:u16)->Self{
+    pub fn new(a:u32,b:u32,c:u32)->Self{
         Self{
             a,
             b,
             c,
         }
     }
+
+    pub fn corners(&self)->[u32;3]{
+        [self.a,self.b,self.c]
+    }
+
+    //group a flat triangle list into triangles
+    pub fn from_slice(vertex_indices:&[u32])->Vec<Self>{
+        if !vertex_indices.len().is_multiple_of(3){
+            panic!("index count {} is not a multiple of 3",vertex_indices.len());
+        }
+        vertex_indices.chunks_exact(3).map(|triangle| Self::new(triangle[0],triangle[1],triangle[2])).collect()
+    }
 }
 
 #[derive(Debug,Clone)]
 pub struct Mesh{
     pub vertices:IntegratedBuffer,
     pub vertex_indices:IntegratedBuffer,
+    pub index_type:vk::IndexType,//UINT16 if all vertices can be indexed with it, else UINT32
+    //levels of detail, starting with the full mesh, all stored in vertex_indices and using the same vertices
+    pub lods:Vec<MeshLod>,
+    //layout of the vertices, pipelines used to draw the mesh must match it
+    pub vertex_layout:VertexLayoutDescription,
+    //bounds of vertex positions in model space
+    pub bounding_box:BoundingBox,
+    pub bounding_sphere:BoundingSphere,
+}
+impl Mesh{
+    //most detailed level that is used for the projected size of the mesh (see Camera::projected_size)
+    pub fn lod(&self,screen_size:f32)->&MeshLod{
+        self.lods.iter().find(|lod| screen_size>=lod.screen_size).unwrap_or_else(|| self.lods.last().unwrap())
+    }
 }
 
 #[derive(Debug,Clone,Copy)]
@@ -133,218 +232,271 @@ pub struct Image{
     pub width:u32,
     pub height:u32,
     pub format:vk::Format,
+    pub mip_levels:u32,
+    pub view_type:vk::ImageViewType,//TYPE_2D, or CUBE for cubemaps
     pub memory:vk::DeviceMemory,
     pub image:vk::Image,
     pub image_view:vk::ImageView,
 }
+//format, size and channel mapping of a texture that is uploaded
+#[derive(Debug,Clone,Copy)]
+pub(crate) struct TextureDescription{
+    pub format:vk::Format,
+    pub extent:vk::Extent2D,
+    pub mip_levels:u32,
+    //6 layers with view type CUBE for cubemaps (faces in order +x,-x,+y,-y,+z,-z), 1 layer with TYPE_2D otherwise
+    pub array_layers:u32,
+    pub view_type:vk::ImageViewType,
+    //how the channels are presented to shaders, e.g. to sample single channel images as gray
+    pub components:vk::ComponentMapping,
+}
+//area weighted average of the normals of all triangles using a position
+pub fn smooth_normals(positions:&[glm::Vec3],triangles:&[[usize;3]])->Vec<glm::Vec3>{
+    let mut normals=vec![glm::vec3(0.0f32,0.0,0.0);positions.len()];
+    for triangle in triangles.iter(){
+        let [p0,p1,p2]=triangle.map(|i| positions[i]);
+        //length of the cross product is twice the triangle area
+        let face_normal=glm::cross(&(p1-p0),&(p2-p0));
+        for corner in triangle.iter(){
+            normals[*corner]+=face_normal;
+        }
+    }
+    normals.iter().map(|normal| if glm::length(normal)>0.0{glm::normalize(normal)}else{glm::vec3(0.0,0.0,1.0)}).collect()
+}
+
+//allocate and persistently map a host visible buffer
+pub(crate) fn create_mapped_buffer(device:&Device,device_memory_properties:&vk::PhysicalDeviceMemoryProperties,allocation_callbacks:Option<&vk::AllocationCallbacks>,size:u64,usage:vk::BufferUsageFlags)->(vk::Buffer,vk::DeviceMemory,*mut libc::c_void){
+    let buffer_create_info=vk::BufferCreateInfo{
+        size,
+        usage,
+        sharing_mode:vk::SharingMode::EXCLUSIVE,
+        ..Default::default()
+    };
+    let buffer=unsafe{
+        device.create_buffer(&buffer_create_info,allocation_callbacks)
+    }.unwrap();
+
+    let mut memory=vk::DeviceMemory::null();
+
+    let buffer_memory_requirements=unsafe{
+        device.get_buffer_memory_requirements(buffer)
+    };
+
+    //coherent memory, so that writes do not need to be flushed before submission
+    for memory_type_index in 0..device_memory_properties.memory_type_count{
+        if (buffer_memory_requirements.memory_type_bits & (1<<memory_type_index))>0
+        && device_memory_properties.memory_types[memory_type_index as usize].property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT){
+            //allocate
+            let memory_allocate_info=vk::MemoryAllocateInfo{
+                allocation_size:buffer_memory_requirements.size,
+                memory_type_index,
+                ..Default::default()
+            };
+            memory=unsafe{
+                device.allocate_memory(&memory_allocate_info,allocation_callbacks)
+            }.unwrap();
+            //bind
+            unsafe{
+                device.bind_buffer_memory(buffer,memory,0)
+            }.unwrap();
+
+            break;
+        }
+    }
+    if memory==vk::DeviceMemory::null(){
+        panic!("mapped buffer has no memory")
+    }
+
+    let pointer=unsafe{
+        device.map_memory(memory,0,size,vk::MemoryMapFlags::empty())
+    }.unwrap();
+
+    (buffer,memory,pointer)
+}
+
 pub struct Decoder{
     pub allocation_callbacks:Option<vk::AllocationCallbacks>,
 
     pub device:Device,
+    //used to query format support
+    pub instance:Instance,
+    pub physical_device:vk::PhysicalDevice,
 
     pub device_memory_properties:vk::PhysicalDeviceMemoryProperties,
 
     pub staging_buffer:IntegratedBuffer,
     pub staging_buffer_in_use_size:u64,
+    //staging buffers for uploads that did not fit into the remaining space of the staging buffer, destroyed by reset_staging
+    pub dedicated_staging_buffers:Vec<IntegratedBuffer>,
 
-    pub meshes:std::collections::HashMap<&'static str,std::sync::Arc<Mesh>>,
+    pub models:std::collections::HashMap<&'static str,std::sync::Arc<Model>>,
+    //meshes created from memory or generated, by name
+    pub meshes:std::collections::HashMap<String,std::sync::Arc<Mesh>>,
+    pub dynamic_meshes:std::collections::HashMap<String,DynamicMesh>,
 
-    pub textures:std::collections::HashMap<&'static str,std::sync::Arc<Image>>,
+    pub textures:std::collections::HashMap<String,std::sync::Arc<Image>>,
 }
 impl Decoder{
     pub fn get_allocation_callbacks(&self)->Option<&vk::AllocationCallbacks>{
         self.allocation_callbacks.as_ref()
     }
 
-    pub fn get_mesh(&mut self,name:&'static str,command_buffer:vk::CommandBuffer)->std::sync::Arc<Mesh>{
-        if let Some(mesh)=self.meshes.get(name){
-            return mesh.clone();
-        }
+    //write size bytes into staging memory for a copy recorded into the current frame, returns the buffer and offset to copy from
+    //uploads that do not fit into the remaining space of the staging buffer get a staging buffer of their own
+    fn write_staging(&mut self,size:u64,write:impl FnOnce(*mut u8))->(vk::Buffer,u64){
+        //offsets are aligned for any texel (or compressed block) size
+        let offset=self.staging_buffer_in_use_size.div_ceil(16)*16;
+        if offset+size<=self.staging_buffer.buffer_size{
+            self.staging_buffer_in_use_size=offset+size;
 
-        let file_content=std::fs::read_to_string(name).unwrap();
-        let set=obj::obj::parse(file_content.as_str()).unwrap();
-        let quad=set.objects[0].clone();
-
-        assert!(quad.vertices.len()==quad.tex_vertices.len());
-
-        let vertices:Vec<Vertex>=quad.vertices.iter().zip(quad.tex_vertices.iter()).map(|(v,vt)| Vertex::new(v.x as f32,v.y as f32,v.z as f32,1.0,vt.u as f32,vt.v as f32)).collect();
-        let vertex_indices:Vec<VertexIndices>=quad.geometry[0].shapes.iter().map(|s| match s.primitive{
-            obj::obj::Primitive::Triangle(i0,i1,i2)=>VertexIndices::new(i0.0 as u16,i1.0 as u16,i2.0 as u16),
-            _=>panic!("non-triangle shape")
-        }).collect();
-
-        let (vertices_size,vertices_buffer,vertices_memory)={
-            let size=(vertices.len() * std::mem::size_of::<Vertex>()) as u64;
-            let buffer_create_info=vk::BufferCreateInfo{
-                size:size,
-                usage:vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                sharing_mode:vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-            let buffer=unsafe{
-                self.device.create_buffer(&buffer_create_info,self.get_allocation_callbacks())
+            let memory_pointer=unsafe{
+                self.device.map_memory(self.staging_buffer.memory,offset,size,vk::MemoryMapFlags::empty())
             }.unwrap();
+            write(memory_pointer as *mut u8);
 
-            let mut memory=vk::DeviceMemory::null();
-
-            let buffer_memory_requirements=unsafe{
-                self.device.get_buffer_memory_requirements(buffer)
+            //flush staging and unmap after
+            let flush_range=vk::MappedMemoryRange{
+                memory:self.staging_buffer.memory,
+                offset,
+                size:vk::WHOLE_SIZE,
+                ..Default::default()
             };
+            unsafe{
+                self.device.flush_mapped_memory_ranges(&[flush_range]).unwrap();
+                self.device.unmap_memory(self.staging_buffer.memory);
+            }
 
-            for memory_type_index in 0..self.device_memory_properties.memory_type_count{
-                if (buffer_memory_requirements.memory_type_bits & (1<<memory_type_index))>0 
-                && self.device_memory_properties.memory_types[memory_type_index as usize].property_flags.intersects(vk::MemoryPropertyFlags::DEVICE_LOCAL){
-                    //allocate
-                    let memory_allocate_info=vk::MemoryAllocateInfo{
-                        allocation_size:buffer_memory_requirements.size,
-                        memory_type_index,
-                        ..Default::default()
-                    };
-                    memory=unsafe{
-                        self.device.allocate_memory(&memory_allocate_info,self.get_allocation_callbacks())
-                    }.unwrap();
-
-                    //bind
-                    let buffer_memory_offset=0;
-                    unsafe{
-                        self.device.bind_buffer_memory(buffer,memory,buffer_memory_offset)
-                    }.unwrap();
-
-                    let offset=self.staging_buffer_in_use_size;
-                    self.staging_buffer_in_use_size+=buffer_memory_requirements.size;
-
-                    //map staging (!)
-                    let memory_pointer=unsafe{
-                        self.device.map_memory(self.staging_buffer.memory,offset,size,vk::MemoryMapFlags::empty())
-                    }.unwrap();
-
-                    //memcpy
-                    unsafe{
-                        libc::memcpy(memory_pointer,vertices.as_ptr() as *const libc::c_void,size as usize);
-                    }
-
-                    //flush
-                    let flush_range=vk::MappedMemoryRange{
-                        memory:self.staging_buffer.memory,
-                        offset,
-                        //size,
-                        size:vk::WHOLE_SIZE,
-                        ..Default::default()
-                    };
-                    unsafe{
-                        self.device.flush_mapped_memory_ranges(&[flush_range])
-                    }.unwrap();
-
-                    //unmap
-                    unsafe{
-                        self.device.unmap_memory(self.staging_buffer.memory);
-                    }
+            return (self.staging_buffer.buffer,offset);
+        }
 
-                    unsafe{
-                        self.device.cmd_copy_buffer(command_buffer,self.staging_buffer.buffer,buffer,&[
-                            vk::BufferCopy{
-                                src_offset:offset,
-                                dst_offset:0,
-                                size:size,
-                            }
-                        ]);
-                    };
+        //dedicated memory is coherent, and is not mapped again after writing
+        let (buffer,memory,memory_pointer)=create_mapped_buffer(&self.device,&self.device_memory_properties,self.get_allocation_callbacks(),size,vk::BufferUsageFlags::TRANSFER_SRC);
+        write(memory_pointer as *mut u8);
+        unsafe{
+            self.device.unmap_memory(memory);
+        }
+        self.dedicated_staging_buffers.push(IntegratedBuffer{
+            buffer_size:size,
+            item_count:size,
+            buffer,
+            memory,
+        });
+        (buffer,0)
+    }
 
-                    break;
-                }
-            }
-            if memory==vk::DeviceMemory::null(){
-                panic!("staging buffer has no memory")
+    //make all staging memory available again, once the frame that recorded the copies from it has finished
+    pub fn reset_staging(&mut self){
+        self.staging_buffer_in_use_size=0;
+        for staging_buffer in self.dedicated_staging_buffers.drain(..){
+            unsafe{
+                self.device.destroy_buffer(staging_buffer.buffer,self.allocation_callbacks.as_ref());
+                self.device.free_memory(staging_buffer.memory,self.allocation_callbacks.as_ref());
             }
+        }
+    }
 
-            (size,buffer,memory)
+    //create device local buffer, and record copy of data from the staging buffer into it
+    //caller must record a barrier before the buffer is used
+    fn upload_buffer<T>(&mut self,data:&[T],usage:vk::BufferUsageFlags,command_buffer:vk::CommandBuffer)->(u64,vk::Buffer,vk::DeviceMemory){
+        let size=std::mem::size_of_val(data) as u64;
+        let buffer_create_info=vk::BufferCreateInfo{
+            size:size,
+            usage:usage | vk::BufferUsageFlags::TRANSFER_DST,
+            sharing_mode:vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
         };
+        let buffer=unsafe{
+            self.device.create_buffer(&buffer_create_info,self.get_allocation_callbacks())
+        }.unwrap();
 
-        let (vertex_indices_size,vertex_indices_buffer,vertex_indices_memory)={
-            let size=(vertex_indices.len() * std::mem::size_of::<VertexIndices>()) as u64;
-            let buffer_create_info=vk::BufferCreateInfo{
-                size:size,
-                usage:vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
-                sharing_mode:vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-            let buffer=unsafe{
-                self.device.create_buffer(&buffer_create_info,self.get_allocation_callbacks())
-            }.unwrap();
-
-            let mut memory=vk::DeviceMemory::null();
-
-            let buffer_memory_requirements=unsafe{
-                self.device.get_buffer_memory_requirements(buffer)
-            };
-
-            for memory_type_index in 0..self.device_memory_properties.memory_type_count{
-                if (buffer_memory_requirements.memory_type_bits & (1<<memory_type_index))>0 
-                && self.device_memory_properties.memory_types[memory_type_index as usize].property_flags.intersects(vk::MemoryPropertyFlags::DEVICE_LOCAL){
-                    //allocate
-                    let memory_allocate_info=vk::MemoryAllocateInfo{
-                        allocation_size:buffer_memory_requirements.size,
-                        memory_type_index,
-                        ..Default::default()
-                    };
-                    memory=unsafe{
-                        self.device.allocate_memory(&memory_allocate_info,self.get_allocation_callbacks())
-                    }.unwrap();
-
-                    //bind
-                    let buffer_memory_offset=0;
-                    unsafe{
-                        self.device.bind_buffer_memory(buffer,memory,buffer_memory_offset)
-                    }.unwrap();
-
-                    let offset=self.staging_buffer_in_use_size;
-                    self.staging_buffer_in_use_size+=buffer_memory_requirements.size;
+        let mut memory=vk::DeviceMemory::null();
 
-                    //map staging (!)
-                    let memory_pointer=unsafe{
-                        self.device.map_memory(self.staging_buffer.memory,offset,size,vk::MemoryMapFlags::empty())
-                    }.unwrap();
+        let buffer_memory_requirements=unsafe{
+            self.device.get_buffer_memory_requirements(buffer)
+        };
 
-                    //memcpy
-                    unsafe{
-                        libc::memcpy(memory_pointer,vertex_indices.as_ptr() as *const libc::c_void,size as usize);
-                    }
+        for memory_type_index in 0..self.device_memory_properties.memory_type_count{
+            if (buffer_memory_requirements.memory_type_bits & (1<<memory_type_index))>0 
+            && self.device_memory_properties.memory_types[memory_type_index as usize].property_flags.intersects(vk::MemoryPropertyFlags::DEVICE_LOCAL){
+                //allocate
+                let memory_allocate_info=vk::MemoryAllocateInfo{
+                    allocation_size:buffer_memory_requirements.size,
+                    memory_type_index,
+                    ..Default::default()
+                };
+                memory=unsafe{
+                    self.device.allocate_memory(&memory_allocate_info,self.get_allocation_callbacks())
+                }.unwrap();
 
-                    //flush
-                    let flush_range=vk::MappedMemoryRange{
-                        memory:self.staging_buffer.memory,
-                        offset,
-                        //size,
-                        size:vk::WHOLE_SIZE,
-                        ..Default::default()
-                    };
-                    unsafe{
-                        self.device.flush_mapped_memory_ranges(&[flush_range])
-                    }.unwrap();
+                //bind
+                let buffer_memory_offset=0;
+                unsafe{
+                    self.device.bind_buffer_memory(buffer,memory,buffer_memory_offset)
+                }.unwrap();
 
-                    //unmap
-                    unsafe{
-                        self.device.unmap_memory(self.staging_buffer.memory);
-                    }
+                let (staging_buffer,offset)=self.write_staging(size,|memory_pointer| unsafe{
+                    libc::memcpy(memory_pointer as *mut libc::c_void,data.as_ptr() as *const libc::c_void,size as usize);
+                });
 
-                    unsafe{
-                        self.device.cmd_copy_buffer(command_buffer,self.staging_buffer.buffer,buffer,&[
-                            vk::BufferCopy{
-                                src_offset:offset,
-                                dst_offset:0,
-                                size:size,
-                            }
-                        ]);
-                    };
+                unsafe{
+                    self.device.cmd_copy_buffer(command_buffer,staging_buffer,buffer,&[
+                        vk::BufferCopy{
+                            src_offset:offset,
+                            dst_offset:0,
+                            size:size,
+                        }
+                    ]);
+                };
 
-                    break;
-                }
-            }
-            if memory==vk::DeviceMemory::null(){
-                panic!("staging buffer has no memory")
+                break;
             }
+        }
+        if memory==vk::DeviceMemory::null(){
+            panic!("staging buffer has no memory")
+        }
+
+        (size,buffer,memory)
+    }
 
-            (size,buffer,memory)
+    //upload vertices and indices into a new mesh, with simplified levels of detail for big meshes if generate_lods is set
+    //the caller owns the returned buffers, use add_mesh to have the decoder keep and destroy the mesh
+    pub fn create_mesh<V:VertexLayout>(&mut self,vertices:&[V],vertex_indices:&[VertexIndices],generate_lods:bool,command_buffer:vk::CommandBuffer)->Mesh{
+        let positions:Vec<glm::Vec3>=vertices.iter().map(|v| v.position()).collect();
+        let bounding_box=BoundingBox::from_points(positions.iter().copied());
+        let bounding_sphere=BoundingSphere::from_points(positions.iter().copied(),&bounding_box);
+
+        //all levels are stored in a single index buffer, one after the other
+        let lod_indices=if generate_lods{
+            lod::generate_lods(&positions,vertex_indices)
+        }else{
+            vec![vertex_indices.to_vec()]
+        };
+        let mut lods=Vec::with_capacity(lod_indices.len());
+        let mut first_index=0;
+        for (level,indices) in lod_indices.iter().enumerate(){
+            lods.push(MeshLod{
+                first_index,
+                index_count:(indices.len()*3) as u32,
+                screen_size:lod::lod_screen_size(level,lod_indices.len()),
+            });
+            first_index+=(indices.len()*3) as u32;
+        }
+        let vertex_indices:Vec<VertexIndices>=lod_indices.concat();
+        let vertex_indices=&vertex_indices[..];
+
+        let (vertices_size,vertices_buffer,vertices_memory)=self.upload_buffer(vertices,vk::BufferUsageFlags::VERTEX_BUFFER,command_buffer);
+        //16 bit indices take half the memory, but can only address 65536 vertices
+        let index_type=if vertices.len()<=u16::MAX as usize+1{
+            vk::IndexType::UINT16
+        }else{
+            vk::IndexType::UINT32
+        };
+        let (vertex_indices_size,vertex_indices_buffer,vertex_indices_memory)=match index_type{
+            vk::IndexType::UINT16=>{
+                let vertex_indices:Vec<[u16;3]>=vertex_indices.iter().map(|i| [i.a as u16,i.b as u16,i.c as u16]).collect();
+                self.upload_buffer(&vertex_indices,vk::BufferUsageFlags::INDEX_BUFFER,command_buffer)
+            },
+            _=>self.upload_buffer(vertex_indices,vk::BufferUsageFlags::INDEX_BUFFER,command_buffer),
         };
 
         let buffer_memory_barriers = vec![
@@ -374,7 +526,7 @@ impl Decoder{
             self.device.cmd_pipeline_barrier( command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::VERTEX_INPUT, vk::DependencyFlags::empty(), &[], &buffer_memory_barriers[..], &[]);
         }
 
-        let mesh=std::sync::Arc::new(Mesh{
+        Mesh{
             vertices:IntegratedBuffer{
                 buffer_size:vertices_size,
                 item_count:vertices.len() as u64,
@@ -386,11 +538,191 @@ impl Decoder{
                 item_count:(vertex_indices.len()*3) as u64,
                 buffer:vertex_indices_buffer,
                 memory:vertex_indices_memory,
-            }
+            },
+            index_type,
+            lods,
+            vertex_layout:V::layout(),
+            bounding_box,
+            bounding_sphere,
+        }
+    }
+
+    //load model file, format is chosen by file extension (obj, gltf, glb, stl or ply)
+    pub fn get_model(&mut self,name:&'static str,command_buffer:vk::CommandBuffer)->std::sync::Arc<Model>{
+        if let Some(model)=self.models.get(name){
+            return model.clone();
+        }
+
+        let extension=std::path::Path::new(name).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        let model=std::sync::Arc::new(match extension.as_deref(){
+            Some("obj")=>self.load_obj(name,command_buffer),
+            Some("gltf") | Some("glb")=>self.load_gltf(name,command_buffer),
+            Some("stl")=>self.load_stl(name,command_buffer),
+            Some("ply")=>self.load_ply(name,command_buffer),
+            _=>panic!("unsupported model format {}",name),
         });
 
-        self.meshes.insert(name,mesh.clone());
+        self.models.insert(name,model.clone());
+
+        model
+    }
+
+    //load all objects of an obj file, with one submesh per object, group and material
+    fn load_obj(&mut self,name:&str,command_buffer:vk::CommandBuffer)->Model{
+        let file_content=std::fs::read_to_string(name).unwrap();
+        let set=obj::obj::parse(file_content.as_str()).unwrap();
+
+        //paths in the obj file are relative to its directory
+        let directory=std::path::Path::new(name).parent().unwrap_or_else(|| std::path::Path::new(""));
+        let materials=match &set.material_library{
+            Some(material_library)=>self.load_materials(&directory.join(material_library),command_buffer),
+            None=>Vec::new(),
+        };
+
+        let mut submeshes=Vec::new();
+        for object in set.objects.iter(){
+            //area weighted normals per position, for corners without a normal index
+            let position_normals=if object.geometry.iter().flat_map(|geometry| geometry.shapes.iter()).any(|shape| match shape.primitive{
+                obj::obj::Primitive::Triangle(i0,i1,i2)=>i0.2.is_none() || i1.2.is_none() || i2.2.is_none(),
+                _=>false,
+            }){
+                let positions:Vec<glm::Vec3>=object.vertices.iter().map(|v| glm::vec3(v.x as f32,v.y as f32,v.z as f32)).collect();
+                let triangles:Vec<[usize;3]>=object.geometry.iter().flat_map(|geometry| geometry.shapes.iter()).filter_map(|shape| match shape.primitive{
+                    obj::obj::Primitive::Triangle(i0,i1,i2)=>Some([i0.0,i1.0,i2.0]),
+                    _=>None,
+                }).collect();
+                smooth_normals(&positions,&triangles)
+            }else{
+                Vec::new()
+            };
+
+            for geometry in object.geometry.iter(){
+                //shapes with the same groups form one submesh, in order of first appearance
+                let mut group_shapes:Vec<(&Vec<obj::obj::GroupName>,Vec<[obj::obj::VTNIndex;3]>)>=Vec::new();
+                for shape in geometry.shapes.iter(){
+                    //points and lines are not drawn
+                    let triangle=match shape.primitive{
+                        obj::obj::Primitive::Triangle(i0,i1,i2)=>[i0,i1,i2],
+                        _=>continue,
+                    };
+                    match group_shapes.iter_mut().find(|(groups,_)| **groups==shape.groups){
+                        Some((_,triangles))=>triangles.push(triangle),
+                        None=>group_shapes.push((&shape.groups,vec![triangle])),
+                    }
+                }
+
+                for (groups,triangles) in group_shapes.iter(){
+                    //one vertex per unique (position, texture coordinate, normal) combination
+                    let mut vertex_ids:std::collections::HashMap<obj::obj::VTNIndex,u32>=std::collections::HashMap::new();
+                    let mut vertices:Vec<Vertex>=Vec::new();
+                    let mut vertex_indices:Vec<VertexIndices>=Vec::with_capacity(triangles.len());
+                    for triangle in triangles.iter(){
+                        let [a,b,c]=triangle.map(|corner|{
+                            *vertex_ids.entry(corner).or_insert_with(||{
+                                let (position_index,texture_index,normal_index)=corner;
+                                let position=object.vertices[position_index];
+                                //files without texture coordinates are sampled at the texture origin
+                                let (u,v)=match texture_index{
+                                    Some(texture_index)=>(object.tex_vertices[texture_index].u as f32,object.tex_vertices[texture_index].v as f32),
+                                    None=>(0.0,0.0),
+                                };
+                                let normal=match normal_index{
+                                    Some(normal_index)=>glm::vec3(object.normals[normal_index].x as f32,object.normals[normal_index].y as f32,object.normals[normal_index].z as f32),
+                                    None=>position_normals[position_index],
+                                };
+                                vertices.push(Vertex::new(position.x as f32,position.y as f32,position.z as f32,1.0,u,v).with_normal(normal.x,normal.y,normal.z));
+                                (vertices.len()-1) as u32
+                            })
+                        });
+                        vertex_indices.push(VertexIndices::new(a,b,c));
+                    }
+                    mesh_processing::optimize_vertex_cache(&mut vertex_indices,vertices.len());
+
+                    let mut submesh_name=object.name.clone();
+                    for group in groups.iter(){
+                        submesh_name.push('/');
+                        submesh_name.push_str(group);
+                    }
+                    submeshes.push(Submesh{
+                        name:submesh_name,
+                        material_name:geometry.material_name.clone(),
+                        material:geometry.material_name.as_ref().and_then(|material_name| materials.iter().find(|material| material.name==*material_name).cloned()),
+                        mesh:std::sync::Arc::new(self.create_mesh(&vertices,&vertex_indices,true,command_buffer)),
+                    });
+                }
+            }
+        }
+        if submeshes.is_empty(){
+            panic!("{} contains no triangles",name);
+        }
+
+        Model::new(submeshes)
+    }
+
+    //load all materials of an mtl library, with textures relative to the library directory
+    //a library that is missing or cannot be parsed only prints a warning, and the model is loaded without materials
+    fn load_materials(&mut self,path:&std::path::Path,command_buffer:vk::CommandBuffer)->Vec<std::sync::Arc<ModelMaterial>>{
+        let file_content=match std::fs::read_to_string(path){
+            Ok(file_content)=>file_content,
+            Err(e)=>{
+                eprintln!("warning: failed to read material library {}: {}",path.display(),e);
+                return Vec::new();
+            },
+        };
+        let set=match obj::mtl::parse(file_content.as_str()){
+            Ok(set)=>set,
+            Err(e)=>{
+                eprintln!("warning: failed to parse material library {}: {:?}",path.display(),e);
+                return Vec::new();
+            },
+        };
+        let directory=path.parent().unwrap_or_else(|| std::path::Path::new(""));
+
+        set.materials.iter().map(|material|{
+            let diffuse_color=glm::vec3(material.color_diffuse.r as f32,material.color_diffuse.g as f32,material.color_diffuse.b as f32);
+            let alpha=material.alpha as f32;
+            let diffuse_texture=match &material.diffuse_map{
+                Some(diffuse_map)=>self.get_texture(directory.join(diffuse_map).to_str().unwrap(),TextureUsage::Color,command_buffer),
+                None=>{
+                    let to_u8=|c:f32| (c.clamp(0.0,1.0)*255.0).round() as u8;
+                    self.get_color_texture([to_u8(diffuse_color.x),to_u8(diffuse_color.y),to_u8(diffuse_color.z),to_u8(alpha)],command_buffer)
+                },
+            };
+            std::sync::Arc::new(ModelMaterial{
+                name:material.name.clone(),
+                diffuse_color,
+                alpha,
+                transparent:alpha<1.0,
+                diffuse_texture,
+                sampler:SamplerDescription::default(),
+                normal_map:material.bump_map.as_ref().map(|bump_map| self.get_texture(directory.join(bump_map).to_str().unwrap(),TextureUsage::Data,command_buffer)),
+                specular_map:material.specular_map.as_ref().map(|specular_map| self.get_texture(directory.join(specular_map).to_str().unwrap(),TextureUsage::Data,command_buffer)),
+            })
+        }).collect()
+    }
 
+    //mesh of a model file that consists of a single submesh, or a mesh added with add_mesh
+    //panics if the file has more than one submesh (each has its own mesh and material), use get_model for those
+    pub fn get_mesh(&mut self,name:&'static str,command_buffer:vk::CommandBuffer)->std::sync::Arc<Mesh>{
+        //meshes added from memory take precedence over files
+        if let Some(mesh)=self.meshes.get(name){
+            return mesh.clone();
+        }
+        let model=self.get_model(name,command_buffer);
+        if model.submeshes.len()>1{
+            panic!("{} has {} submeshes, load it with get_model instead of get_mesh",name,model.submeshes.len());
+        }
+        model.submeshes[0].mesh.clone()
+    }
+
+    //upload a mesh from memory, which is then also returned by get_mesh with the same name (see create_mesh)
+    pub fn add_mesh<V:VertexLayout>(&mut self,name:&str,vertices:&[V],vertex_indices:&[VertexIndices],generate_lods:bool,command_buffer:vk::CommandBuffer)->std::sync::Arc<Mesh>{
+        //meshes may be in use by the gpu, so they cannot be replaced
+        if self.meshes.contains_key(name){
+            panic!("mesh {} already exists",name);
+        }
+        let mesh=std::sync::Arc::new(self.create_mesh(vertices,vertex_indices,generate_lods,command_buffer));
+        self.meshes.insert(String::from(name),mesh.clone());
         mesh
     }
 
@@ -445,32 +777,154 @@ impl Decoder{
     }
     */
 
-    pub fn get_texture(&mut self,filename:&'static str,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+    //load a texture from disk, cached per file and usage
+    //ktx2 and dds files state their format, the usage only decides between sRGB and unorm where a dds file does not
+    pub fn get_texture(&mut self,filename:&str,usage:TextureUsage,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        //data textures get a different name, so the same file can be loaded with both usages
+        let name=match usage{
+            TextureUsage::Color=>String::from(filename),
+            TextureUsage::Data=>format!("{}#data",filename),
+        };
         //return cached texture if present
-        if let Some(texture)=self.textures.get(filename){
+        if let Some(texture)=self.textures.get(&name){
             return texture.clone();
         }
 
-        //read file from disk and decode into b8g8r8a8 format
-        let native_image=image::open(filename).unwrap().into_rgba8();
+        //container formats are uploaded with their stored levels (possibly block compressed), other images are decoded
+        let extension=std::path::Path::new(filename).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+        match extension.as_deref(){
+            Some("ktx2")=>{
+                let bytes=std::fs::read(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+                let data=ktx2::parse(filename,&bytes);
+                self.create_texture_from_data(name,&data,command_buffer)
+            },
+            Some("dds")=>{
+                let bytes=std::fs::read(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+                let data=dds::parse(filename,&bytes,usage);
+                self.create_texture_from_data(name,&data,command_buffer)
+            },
+            //hdr images are linear, independent of the usage
+            Some("hdr")=>{
+                let image=FloatImage::load_radiance(filename);
+                self.create_float_texture(name,&image,&float_image::HALF_FLOAT_FORMATS,command_buffer)
+            },
+            Some("exr")=>{
+                let bytes=std::fs::read(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+                let (image,full_precision)=exr::parse(filename,&bytes);
+                let formats=if full_precision{float_image::FULL_FLOAT_FORMATS}else{float_image::HALF_FLOAT_FORMATS};
+                self.create_float_texture(name,&image,&formats,command_buffer)
+            },
+            _=>{
+                let native_image=image::open(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+                self.create_texture_from_image(name,&native_image,usage,command_buffer)
+            },
+        }
+    }
+
+    //upload a decoded image into a new texture, which is cached under the given name
+    //16 bit and single channel images keep their precision and channel count where the device supports it, others are converted to r8g8b8a8
+    pub fn create_texture_from_image(&mut self,name:String,native_image:&image::DynamicImage,usage:TextureUsage,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        if let Some(formats)=float_image::image_formats(native_image,usage){
+            return self.create_float_texture(name,&FloatImage::from_image(native_image,usage),formats,command_buffer);
+        }
+
+        let native_image=native_image.to_rgba8();
         let width=native_image.width();
         let height=native_image.height();
 
+        self.create_texture(name,width,height,&native_image.into_raw(),usage,command_buffer)
+    }
+
+    //1x1 color texture of a single sRGB encoded color, e.g. for materials without a texture
+    pub fn get_color_texture(&mut self,color:[u8;4],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let name=format!("color:{},{},{},{}",color[0],color[1],color[2],color[3]);
+        if let Some(texture)=self.textures.get(&name){
+            return texture.clone();
+        }
+        self.create_texture(name,1,1,&color,TextureUsage::Color,command_buffer)
+    }
+
+    //upload tightly packed r8g8b8a8 pixels into a new texture with a full mip chain, which is cached under the given name
+    //color textures are sRGB encoded, data textures are sampled as stored
+    pub fn create_texture(&mut self,name:String,width:u32,height:u32,pixels:&[u8],usage:TextureUsage,command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        if pixels.len()!=(width*height*4) as usize{
+            panic!("texture {} is {}x{}, which requires {} bytes, but got {}",name,width,height,width*height*4,pixels.len());
+        }
+        let description=TextureDescription{
+            format:usage.rgba8_format(),
+            extent:vk::Extent2D{width,height},
+            mip_levels:mipmap::mip_level_count(width,height),
+            array_layers:1,
+            view_type:vk::ImageViewType::TYPE_2D,
+            components:vk::ComponentMapping::default(),
+        };
+        //blits need linear filtering support for the format, otherwise the mip chain is computed on the cpu
+        if description.mip_levels>1 && !self.format_supports_blits(description.format){
+            let generated=mipmap::generate_mips_rgba8(width,height,pixels,usage==TextureUsage::Color,description.mip_levels);
+            let levels:Vec<&[u8]>=std::iter::once(pixels).chain(generated.iter().map(|level| &level[..])).collect();
+            return self.upload_texture(name,&description,&levels,command_buffer);
+        }
+        self.upload_texture(name,&description,&[pixels],command_buffer)
+    }
+
+    //upload a texture with pre-built mip levels (e.g. from a container format), starting with the full size level.
+    //each level is tightly packed in the given format, no further levels are generated.
+    pub fn create_texture_with_mips(&mut self,name:String,format:vk::Format,width:u32,height:u32,levels:&[&[u8]],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        if levels.is_empty() || levels.len() as u32>mipmap::mip_level_count(width,height){
+            panic!("texture {} is {}x{}, which cannot have {} mip levels",name,width,height,levels.len());
+        }
+        let description=TextureDescription{
+            format,
+            extent:vk::Extent2D{width,height},
+            mip_levels:levels.len() as u32,
+            array_layers:1,
+            view_type:vk::ImageViewType::TYPE_2D,
+            components:vk::ComponentMapping::default(),
+        };
+        self.upload_texture(name,&description,levels,command_buffer)
+    }
+
+    //whether images with optimal tiling support all of the features in the format
+    pub fn format_supports(&self,format:vk::Format,features:vk::FormatFeatureFlags)->bool{
+        let format_properties=unsafe{
+            self.instance.get_physical_device_format_properties(self.physical_device,format)
+        };
+        format_properties.optimal_tiling_features.contains(features)
+    }
+
+    //whether mip levels of the format can be generated with linear blits
+    pub fn format_supports_blits(&self,format:vk::Format)->bool{
+        self.format_supports(format,vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+    }
+
+    //create a texture from the given levels, and generate the remaining levels up to mip_levels with blits
+    //each level holds all array layers one after another
+    pub(crate) fn upload_texture(&mut self,name:String,description:&TextureDescription,levels:&[&[u8]],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let TextureDescription{format,extent:vk::Extent2D{width,height},mip_levels,array_layers,view_type,components}=*description;
+        //textures may be in use by the gpu, so they cannot be replaced
+        if self.textures.contains_key(&name){
+            panic!("texture {} already exists",name);
+        }
+        let generate_mips=(levels.len() as u32)<mip_levels;
+
         //create image vulkan handle
         let image={
             let image_create_info=vk::ImageCreateInfo{
+                //cubemaps are 2d images with 6 layers that can be viewed as a cube
+                flags:if view_type==vk::ImageViewType::CUBE{vk::ImageCreateFlags::CUBE_COMPATIBLE}else{vk::ImageCreateFlags::empty()},
                 image_type:vk::ImageType::TYPE_2D,
-                format:vk::Format::R8G8B8A8_UNORM,
+                format,
                 extent:vk::Extent3D{
                     width,
                     height,
                     depth:1,
                 },
-                mip_levels:1,
-                array_layers:1,
+                mip_levels,
+                array_layers,
                 samples:vk::SampleCountFlags::TYPE_1,
                 tiling:vk::ImageTiling::OPTIMAL,
-                usage:vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED, //is copied to, and then sampled from
+                //is copied to, and then sampled from (and blitted from into the next level when mips are generated)
+                usage:vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED | if generate_mips{vk::ImageUsageFlags::TRANSFER_SRC}else{vk::ImageUsageFlags::empty()},
                 sharing_mode:vk::SharingMode::EXCLUSIVE,//is only accessed from queues from the same family at the same time (ownership transfer in between)
                 initial_layout:vk::ImageLayout::UNDEFINED,//when ownership is acquired to copy data to this image, the layout is transitioned to a valid value
                 ..Default::default()
@@ -480,6 +934,14 @@ impl Decoder{
             }
         };
 
+        //levels are placed one after another in the staging buffer, aligned for any texel (or compressed block) size
+        let mut level_offsets=Vec::with_capacity(levels.len());
+        let mut staging_size=0u64;
+        for level in levels.iter(){
+            level_offsets.push(staging_size);
+            staging_size+=level.len().div_ceil(16) as u64*16;
+        }
+
         //allocate image memory and upload data into staging buffer
         //then schedule commands to copy image data from staging into image memory
         let mut memory=vk::DeviceMemory::null();
@@ -487,10 +949,6 @@ impl Decoder{
             let image_memory_reqirements=unsafe{
                 self.device.get_image_memory_requirements(image)
             };
-            if self.staging_buffer.buffer_size<image_memory_reqirements.size{
-                panic!("staging buffer not big enough");
-            }
-
             for i in 0..self.device_memory_properties.memory_type_count{
                 if (image_memory_reqirements.memory_type_bits & (1<<i))>0 &&
                     self.device_memory_properties.memory_types[i as usize].property_flags
@@ -511,39 +969,23 @@ impl Decoder{
                         self.device.bind_image_memory(image, memory, 0)
                     }.unwrap();
 
-                    let offset=self.staging_buffer_in_use_size;//offset mesh data because staging buffer is used mesh and texture upload, with no synchronization against each other (could do that, somehow?)
-                    self.staging_buffer_in_use_size+=image_memory_reqirements.size;
-
-                    //map staging memory
-                    let memory_pointer=unsafe{
-                        self.device.map_memory(self.staging_buffer.memory, offset, image_memory_reqirements.size, vk::MemoryMapFlags::empty())
-                    }.unwrap();
-
-                    //copy image data to staging
-                    unsafe{
-                        libc::memcpy(memory_pointer,native_image.into_raw().as_mut_ptr() as *mut libc::c_void,image_memory_reqirements.size as usize);
-                    }
-
-                    //flush staging and unmap after
-                    let flush_range=vk::MappedMemoryRange{
-                        memory:self.staging_buffer.memory,
-                        offset,
-                        size:image_memory_reqirements.size,
-                        ..Default::default()
-                    };
-                    unsafe{
-                        self.device.flush_mapped_memory_ranges(&[flush_range]).unwrap();
-                        self.device.unmap_memory(self.staging_buffer.memory);
-                    }
+                    //copy image data to staging, textures that do not fit into the staging buffer get a staging buffer of their own
+                    let (staging_buffer,offset)=self.write_staging(staging_size,|memory_pointer|{
+                        for (level,level_offset) in levels.iter().zip(level_offsets.iter()){
+                            unsafe{
+                                libc::memcpy(memory_pointer.add(*level_offset as usize) as *mut libc::c_void,level.as_ptr() as *const libc::c_void,level.len());
+                            }
+                        }
+                    });
 
                     //schedule image data transfer from staging to final
                     
                     let image_subresource_range=vk::ImageSubresourceRange{
                         aspect_mask:vk::ImageAspectFlags::COLOR,
                         base_mip_level:0,
-                        level_count:1,
+                        level_count:levels.len() as u32,
                         base_array_layer:0,
-                        layer_count:1,
+                        layer_count:array_layers,
                     };
                     let image_memory_barrier_none_to_transfer = vk::ImageMemoryBarrier{
                         src_access_mask:vk::AccessFlags::empty(),
@@ -561,52 +1003,51 @@ impl Decoder{
                         self.device.cmd_pipeline_barrier(command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::TRANSFER, vk::DependencyFlags::empty(), &[], &[], &[image_memory_barrier_none_to_transfer]);
                     }
 
-                    let buffer_image_copy_info=vk::BufferImageCopy{
-                        buffer_offset:offset,
-                        buffer_row_length:0,
-                        buffer_image_height:0,
-                        image_subresource:vk::ImageSubresourceLayers{
-                            aspect_mask:vk::ImageAspectFlags::COLOR,
-                            mip_level:0,
-                            base_array_layer:0,
-                            layer_count:1,
-                        },
-                        image_offset:vk::Offset3D{
-                            x:0,
-                            y:0,
-                            z:0,
-                        },
-                        image_extent:vk::Extent3D{
-                            width,
-                            height,
-                            depth:1,
-                        },
-                        ..Default::default()
-                    };
+                    let buffer_image_copy_infos:Vec<vk::BufferImageCopy>=level_offsets.iter().enumerate().map(|(level,level_offset)|{
+                        let (level_width,level_height)=mipmap::mip_extent(width,height,level as u32);
+                        vk::BufferImageCopy{
+                            buffer_offset:offset+level_offset,
+                            buffer_row_length:0,
+                            buffer_image_height:0,
+                            image_subresource:vk::ImageSubresourceLayers{
+                                aspect_mask:vk::ImageAspectFlags::COLOR,
+                                mip_level:level as u32,
+                                base_array_layer:0,
+                                layer_count:array_layers,
+                            },
+                            image_offset:vk::Offset3D{
+                                x:0,
+                                y:0,
+                                z:0,
+                            },
+                            image_extent:vk::Extent3D{
+                                width:level_width,
+                                height:level_height,
+                                depth:1,
+                            },
+                        }
+                    }).collect();
                     unsafe{
-                        self.device.cmd_copy_buffer_to_image(command_buffer, self.staging_buffer.buffer, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &[buffer_image_copy_info]);
+                        self.device.cmd_copy_buffer_to_image(command_buffer, staging_buffer, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, &buffer_image_copy_infos);
                     }
-                    
-                    let image_subresource_range=vk::ImageSubresourceRange{
-                        aspect_mask:vk::ImageAspectFlags::COLOR,
-                        base_mip_level:0,
-                        level_count:1,
-                        base_array_layer:0,
-                        layer_count:1,
-                    };
-                    let image_memory_barrier_transfer_to_shader_read = vk::ImageMemoryBarrier{
-                        src_access_mask:vk::AccessFlags::TRANSFER_WRITE,
-                        dst_access_mask:vk::AccessFlags::SHADER_READ,
-                        old_layout:vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        new_layout:vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                        src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-                        dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
-                        image,
-                        subresource_range:image_subresource_range,
-                        ..Default::default()
-                    };
-                    unsafe{
-                        self.device.cmd_pipeline_barrier( command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &[image_memory_barrier_transfer_to_shader_read]);
+
+                    if generate_mips{
+                        mipmap::record_mip_blits(&self.device,command_buffer,image,width,height,mip_levels,array_layers);
+                    }else{
+                        let image_memory_barrier_transfer_to_shader_read = vk::ImageMemoryBarrier{
+                            src_access_mask:vk::AccessFlags::TRANSFER_WRITE,
+                            dst_access_mask:vk::AccessFlags::SHADER_READ,
+                            old_layout:vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            new_layout:vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+                            image,
+                            subresource_range:image_subresource_range,
+                            ..Default::default()
+                        };
+                        unsafe{
+                            self.device.cmd_pipeline_barrier( command_buffer, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::FRAGMENT_SHADER, vk::DependencyFlags::empty(), &[], &[], &[image_memory_barrier_transfer_to_shader_read]);
+                        }
                     }
 
                     break;
@@ -622,20 +1063,15 @@ impl Decoder{
             let subresource_range=vk::ImageSubresourceRange{
                 aspect_mask:vk::ImageAspectFlags::COLOR,
                 base_mip_level:0,
-                level_count:1,
+                level_count:mip_levels,
                 base_array_layer:0,
-                layer_count:1,
+                layer_count:array_layers,
             };
             let image_view_create_info=vk::ImageViewCreateInfo{
                 image,
-                view_type:vk::ImageViewType::TYPE_2D,
-                format:vk::Format::R8G8B8A8_UNORM,
-                components:vk::ComponentMapping{
-                    r:vk::ComponentSwizzle::IDENTITY,
-                    g:vk::ComponentSwizzle::IDENTITY,
-                    b:vk::ComponentSwizzle::IDENTITY,
-                    a:vk::ComponentSwizzle::IDENTITY,
-                },
+                view_type,
+                format,
+                components,
                 subresource_range,
                 ..Default::default()
             };
@@ -647,13 +1083,15 @@ impl Decoder{
         let image=std::sync::Arc::new(Image{
             width,
             height,
-            format:vk::Format::R8G8B8A8_UNORM,
+            format,
+            mip_levels,
+            view_type,
             memory,
             image,
             image_view,
         });
 
-        self.textures.insert(filename,image.clone());
+        self.textures.insert(name,image.clone());
 
         image
     }
@@ -667,7 +1105,7 @@ impl Drop for Decoder{
                 self.device.free_memory(texture.memory,self.get_allocation_callbacks());
             }
         }
-        for (_name,mesh) in self.meshes.iter(){
+        for mesh in self.models.values().flat_map(|model| model.submeshes.iter()).map(|submesh| &submesh.mesh).chain(self.meshes.values()){
             unsafe{
                 self.device.free_memory(mesh.vertices.memory,self.get_allocation_callbacks());
                 self.device.destroy_buffer(mesh.vertices.buffer, self.get_allocation_callbacks());
@@ -677,6 +1115,11 @@ impl Drop for Decoder{
             }
         }
 
+        for dynamic_mesh in self.dynamic_meshes.values(){
+            dynamic_mesh.destroy(&self.device,self.get_allocation_callbacks());
+        }
+
+        self.reset_staging();
         unsafe{
             self.device.free_memory(self.staging_buffer.memory,self.get_allocation_callbacks());
             self.device.destroy_buffer(self.staging_buffer.buffer, self.get_allocation_callbacks());
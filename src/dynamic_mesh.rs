@@ -0,0 +1,149 @@
+use ash::{
+    vk,
+    version::{
+        DeviceV1_0,
+    },
+    Device,
+};
+
+use crate::{Decoder,Mesh,IntegratedBuffer};
+use crate::decoder::{VertexIndices,create_mapped_buffer};
+use crate::vertex_layout::{VertexLayout,VertexLayoutDescription};
+use crate::bounds::{BoundingBox,BoundingSphere};
+use crate::lod::MeshLod;
+
+//number of buffer sets of a dynamic mesh.
+//the manager waits for the previous frame before the next one is submitted, so the set written for a frame is never in use by the gpu.
+pub const FRAMES_IN_FLIGHT:usize=2;
+
+//persistently mapped vertex and index buffers, used for one frame
+#[derive(Debug)]
+struct DynamicMeshFrame{
+    vertices:IntegratedBuffer,
+    vertices_pointer:*mut libc::c_void,
+    vertex_indices:IntegratedBuffer,
+    vertex_indices_pointer:*mut libc::c_void,
+}
+
+//mesh whose contents can be replaced every frame (e.g. debug geometry or animated meshes)
+//written directly into host visible memory, so drawing it is slower than drawing a mesh in device local memory
+#[derive(Debug)]
+pub struct DynamicMesh{
+    vertex_layout:VertexLayoutDescription,
+    frames:Vec<DynamicMeshFrame>,
+    frame:usize,//index of the frame that was written last
+}
+impl DynamicMesh{
+    fn destroy_frame(device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>,frame:&DynamicMeshFrame){
+        unsafe{
+            for buffer in [frame.vertices,frame.vertex_indices].iter(){
+                device.unmap_memory(buffer.memory);
+                device.destroy_buffer(buffer.buffer,allocation_callbacks);
+                device.free_memory(buffer.memory,allocation_callbacks);
+            }
+        }
+    }
+
+    pub(crate) fn destroy(&self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>){
+        for frame in self.frames.iter(){
+            Self::destroy_frame(device,allocation_callbacks,frame);
+        }
+    }
+}
+
+impl Decoder{
+    fn create_dynamic_mesh_frame(&self,vertex_capacity:usize,triangle_capacity:usize,vertex_size:usize)->DynamicMeshFrame{
+        //buffers cannot be empty
+        let vertices_size=(vertex_capacity.max(1)*vertex_size) as u64;
+        let vertex_indices_size=(triangle_capacity.max(1)*std::mem::size_of::<VertexIndices>()) as u64;
+        let (vertices_buffer,vertices_memory,vertices_pointer)=create_mapped_buffer(&self.device,&self.device_memory_properties,self.get_allocation_callbacks(),vertices_size,vk::BufferUsageFlags::VERTEX_BUFFER);
+        let (vertex_indices_buffer,vertex_indices_memory,vertex_indices_pointer)=create_mapped_buffer(&self.device,&self.device_memory_properties,self.get_allocation_callbacks(),vertex_indices_size,vk::BufferUsageFlags::INDEX_BUFFER);
+        DynamicMeshFrame{
+            vertices:IntegratedBuffer{
+                buffer_size:vertices_size,
+                item_count:vertex_capacity.max(1) as u64,
+                buffer:vertices_buffer,
+                memory:vertices_memory,
+            },
+            vertices_pointer,
+            vertex_indices:IntegratedBuffer{
+                buffer_size:vertex_indices_size,
+                item_count:(triangle_capacity.max(1)*3) as u64,
+                buffer:vertex_indices_buffer,
+                memory:vertex_indices_memory,
+            },
+            vertex_indices_pointer,
+        }
+    }
+
+    //create a dynamic mesh with room for the given number of vertices and triangles per frame (it grows when updated with more)
+    pub fn create_dynamic_mesh<V:VertexLayout>(&mut self,name:&str,vertex_capacity:usize,triangle_capacity:usize){
+        if self.dynamic_meshes.contains_key(name){
+            panic!("dynamic mesh {} already exists",name);
+        }
+        let frames=(0..FRAMES_IN_FLIGHT).map(|_| self.create_dynamic_mesh_frame(vertex_capacity,triangle_capacity,std::mem::size_of::<V>())).collect();
+        self.dynamic_meshes.insert(String::from(name),DynamicMesh{
+            vertex_layout:V::layout(),
+            frames,
+            frame:0,
+        });
+    }
+
+    //write new contents into the buffers of the next frame, and return the mesh to draw this frame
+    //the returned mesh is only valid until the dynamic mesh has been updated FRAMES_IN_FLIGHT more times
+    pub fn update_dynamic_mesh<V:VertexLayout>(&mut self,name:&str,vertices:&[V],vertex_indices:&[VertexIndices])->std::sync::Arc<Mesh>{
+        let mut dynamic_mesh=self.dynamic_meshes.remove(name).unwrap_or_else(|| panic!("dynamic mesh {} does not exist",name));
+        if V::layout()!=dynamic_mesh.vertex_layout{
+            panic!("vertex layout of dynamic mesh {} does not match",name);
+        }
+
+        dynamic_mesh.frame=(dynamic_mesh.frame+1)%FRAMES_IN_FLIGHT;
+        let frame_index=dynamic_mesh.frame;
+        //the buffers of this frame are not in use, so they can be replaced when they are too small
+        {
+            let frame=&dynamic_mesh.frames[frame_index];
+            if vertices.len() as u64>frame.vertices.item_count || (vertex_indices.len()*3) as u64>frame.vertex_indices.item_count{
+                let vertex_capacity=vertices.len().max(frame.vertices.item_count as usize*2);
+                let triangle_capacity=vertex_indices.len().max(frame.vertex_indices.item_count as usize/3*2);
+                DynamicMesh::destroy_frame(&self.device,self.get_allocation_callbacks(),frame);
+                dynamic_mesh.frames[frame_index]=self.create_dynamic_mesh_frame(vertex_capacity,triangle_capacity,std::mem::size_of::<V>());
+            }
+        }
+
+        let frame=&dynamic_mesh.frames[frame_index];
+        unsafe{
+            std::ptr::copy_nonoverlapping(vertices.as_ptr(),frame.vertices_pointer as *mut V,vertices.len());
+            std::ptr::copy_nonoverlapping(vertex_indices.as_ptr(),frame.vertex_indices_pointer as *mut VertexIndices,vertex_indices.len());
+        }
+
+        let positions=vertices.iter().map(|v| v.position());
+        let bounding_box=BoundingBox::from_points(positions.clone());
+        let bounding_sphere=BoundingSphere::from_points(positions,&bounding_box);
+        let index_count=(vertex_indices.len()*3) as u32;
+        let mesh=Mesh{
+            vertices:IntegratedBuffer{
+                buffer_size:std::mem::size_of_val(vertices) as u64,
+                item_count:vertices.len() as u64,
+                ..frame.vertices
+            },
+            vertex_indices:IntegratedBuffer{
+                buffer_size:std::mem::size_of_val(vertex_indices) as u64,
+                item_count:index_count as u64,
+                ..frame.vertex_indices
+            },
+            index_type:vk::IndexType::UINT32,
+            //contents change every frame, so no simplified levels are generated
+            lods:vec![MeshLod{
+                first_index:0,
+                index_count,
+                screen_size:0.0,
+            }],
+            vertex_layout:dynamic_mesh.vertex_layout.clone(),
+            bounding_box,
+            bounding_sphere,
+        };
+
+        self.dynamic_meshes.insert(String::from(name),dynamic_mesh);
+        std::sync::Arc::new(mesh)
+    }
+}
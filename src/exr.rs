@@ -0,0 +1,309 @@
+use crate::float_image::{FloatImage,f16_to_f32};
+
+const MAGIC:u32=20000630;
+const VERSION_FLAG_TILED:u32=0x200;
+const VERSION_FLAG_DEEP:u32=0x800;
+const VERSION_FLAG_MULTIPART:u32=0x1000;
+
+const PIXEL_TYPE_UINT:u32=0;
+const PIXEL_TYPE_HALF:u32=1;
+const PIXEL_TYPE_FLOAT:u32=2;
+
+const COMPRESSION_NONE:u8=0;
+const COMPRESSION_RLE:u8=1;
+const COMPRESSION_ZIPS:u8=2;
+const COMPRESSION_ZIP:u8=3;
+
+fn read_u32(bytes:&[u8],offset:usize)->u32{
+    u32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+fn read_i32(bytes:&[u8],offset:usize)->i32{
+    read_u32(bytes,offset) as i32
+}
+
+//null terminated string starting at offset, and the offset after it
+fn read_string(bytes:&[u8],offset:usize)->(&str,usize){
+    let length=bytes[offset..].iter().position(|&b| b==0).unwrap_or_else(|| panic!("unterminated string in exr header"));
+    (std::str::from_utf8(&bytes[offset..offset+length]).unwrap(),offset+length+1)
+}
+
+struct Channel{
+    name:String,
+    pixel_type:u32,
+}
+impl Channel{
+    fn size(&self)->usize{
+        if self.pixel_type==PIXEL_TYPE_HALF{2}else{4}
+    }
+}
+
+//rle: a negative count is followed by that many literal bytes, a positive count by one byte repeated count+1 times
+fn decompress_rle(data:&[u8],size:usize)->Vec<u8>{
+    let mut out=Vec::with_capacity(size);
+    let mut i=0;
+    while i<data.len(){
+        let count=data[i] as i8 as i32;
+        if count<0{
+            out.extend_from_slice(&data[i+1..i+1+(-count) as usize]);
+            i+=1+(-count) as usize;
+        }else{
+            out.extend(std::iter::repeat_n(data[i+1],count as usize+1));
+            i+=2;
+        }
+    }
+    out
+}
+
+//undo the delta encoding and the split into even and odd bytes applied before rle and zip compression
+fn reconstruct(mut data:Vec<u8>)->Vec<u8>{
+    for i in 1..data.len(){
+        data[i]=data[i-1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    let half=data.len().div_ceil(2);
+    (0..data.len()).map(|i| if i%2==0{data[i/2]}else{data[half+i/2]}).collect()
+}
+
+//openexr scanline image: magic, version, header attributes (name, type, size, value) until an empty name, line offset table, chunks of lines.
+//each chunk starts with its first line and its size, every line holds all samples of one channel after another (channels sorted by name).
+//
+//uncompressed, rle and zip compressed files with unsubsampled half, float and uint channels are supported.
+//returns the image with r,g,b,a (or luminance y) channels of the default layer, and whether any of them is stored as 32 bit float.
+pub fn parse(name:&str,bytes:&[u8])->(FloatImage,bool){
+    if bytes.len()<8 || read_u32(bytes,0)!=MAGIC{
+        panic!("{} is not an openexr file",name);
+    }
+    let version=read_u32(bytes,4);
+    if version & (VERSION_FLAG_TILED | VERSION_FLAG_DEEP | VERSION_FLAG_MULTIPART)!=0{
+        panic!("{} is a tiled, deep or multipart openexr file, which is not supported",name);
+    }
+
+    let mut channels=Vec::new();
+    let mut compression=COMPRESSION_NONE;
+    let mut data_window=None;
+    let mut offset=8;
+    loop{
+        let (attribute,next)=read_string(bytes,offset);
+        if attribute.is_empty(){
+            offset=next;
+            break;
+        }
+        let (_attribute_type,next)=read_string(bytes,next);
+        let size=read_u32(bytes,next) as usize;
+        let value=&bytes[next+4..next+4+size];
+        match attribute{
+            "channels"=>{
+                let mut channel_offset=0;
+                while value[channel_offset]!=0{
+                    let (channel_name,next)=read_string(value,channel_offset);
+                    let pixel_type=read_u32(value,next);
+                    let (x_sampling,y_sampling)=(read_i32(value,next+8),read_i32(value,next+12));
+                    if x_sampling!=1 || y_sampling!=1{
+                        panic!("channel {} of {} is subsampled, which is not supported",channel_name,name);
+                    }
+                    channels.push(Channel{
+                        name:String::from(channel_name),
+                        pixel_type,
+                    });
+                    channel_offset=next+16;
+                }
+            },
+            "compression"=>compression=value[0],
+            "dataWindow"=>data_window=Some([read_i32(value,0),read_i32(value,4),read_i32(value,8),read_i32(value,12)]),
+            _=>{},
+        }
+        offset=next+4+size;
+    }
+
+    let [x_min,y_min,x_max,y_max]=data_window.unwrap_or_else(|| panic!("{} has no data window",name));
+    let width=(x_max-x_min+1) as usize;
+    let height=(y_max-y_min+1) as usize;
+    let lines_per_chunk=match compression{
+        COMPRESSION_NONE | COMPRESSION_RLE | COMPRESSION_ZIPS=>1,
+        COMPRESSION_ZIP=>16,
+        _=>panic!("{} uses compression {}, which is not supported",name,compression),
+    };
+
+    //channels of the default layer (without a layer prefix) that are used, by their output channel
+    let find=|channel_name:&str| channels.iter().position(|channel| channel.name==channel_name);
+    let sources:[Option<usize>;4]=match (find("R"),find("G"),find("B"),find("Y")){
+        (None,None,None,Some(y))=>[Some(y),Some(y),Some(y),find("A")],
+        (r,g,b,_)=>[r,g,b,find("A")],
+    };
+    let full_precision=sources.iter().flatten().any(|&source| channels[source].pixel_type==PIXEL_TYPE_FLOAT);
+
+    let line_size:usize=channels.iter().map(|channel| channel.size()*width).sum();
+    //missing color channels stay black, missing alpha is opaque
+    let mut values=vec![0.0f32;width*height*4];
+    for texel in values.chunks_exact_mut(4){
+        texel[3]=1.0;
+    }
+
+    let chunk_count=height.div_ceil(lines_per_chunk);
+    for chunk in 0..chunk_count{
+        let chunk_offset=read_u32(bytes,offset+chunk*8) as usize | (read_u32(bytes,offset+chunk*8+4) as usize)<<32;
+        let first_line=(read_i32(bytes,chunk_offset)-y_min) as usize;
+        let data_size=read_u32(bytes,chunk_offset+4) as usize;
+        let data=&bytes[chunk_offset+8..chunk_offset+8+data_size];
+        let line_count=lines_per_chunk.min(height-first_line);
+        let size=line_size*line_count;
+
+        //chunks that would not get smaller are stored uncompressed
+        let data=if data_size==size{
+            data.to_vec()
+        }else{
+            match compression{
+                COMPRESSION_RLE=>reconstruct(decompress_rle(data,size)),
+                COMPRESSION_ZIPS | COMPRESSION_ZIP=>reconstruct(miniz_oxide::inflate::decompress_to_vec_zlib(data).unwrap_or_else(|e| panic!("failed to decompress {}: {:?}",name,e))),
+                _=>data.to_vec(),
+            }
+        };
+        if data.len()!=size{
+            panic!("chunk {} of {} has {} bytes, but requires {}",chunk,name,data.len(),size);
+        }
+
+        for line in 0..line_count{
+            let y=first_line+line;
+            let mut channel_offset=line*line_size;
+            for (channel_index,channel) in channels.iter().enumerate(){
+                for (output,_) in sources.iter().enumerate().filter(|(_,source)| **source==Some(channel_index)){
+                    for x in 0..width{
+                        let at=channel_offset+x*channel.size();
+                        values[(y*width+x)*4+output]=match channel.pixel_type{
+                            PIXEL_TYPE_HALF=>f16_to_f32(u16::from_le_bytes([data[at],data[at+1]])),
+                            PIXEL_TYPE_FLOAT=>f32::from_le_bytes([data[at],data[at+1],data[at+2],data[at+3]]),
+                            PIXEL_TYPE_UINT=>read_u32(&data,at) as f32,
+                            _=>panic!("channel {} of {} has unknown pixel type {}",channel.name,name,channel.pixel_type),
+                        };
+                    }
+                }
+                channel_offset+=channel.size()*width;
+            }
+        }
+    }
+
+    (FloatImage{
+        width:width as u32,
+        height:height as u32,
+        channels:4,
+        values,
+    },full_precision)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::float_image::f32_to_f16;
+
+    //inverse of reconstruct: split into even and odd bytes, then delta encode
+    fn predict(data:&[u8])->Vec<u8>{
+        let mut split:Vec<u8>=data.iter().step_by(2).chain(data.iter().skip(1).step_by(2)).copied().collect();
+        for i in (1..split.len()).rev(){
+            split[i]=split[i].wrapping_sub(split[i-1]).wrapping_add(128);
+        }
+        split
+    }
+
+    //runs of at least 3 equal bytes are repeated, everything else is stored as literals
+    fn compress_rle(data:&[u8])->Vec<u8>{
+        let mut out=Vec::new();
+        let mut i=0;
+        while i<data.len(){
+            let run=data[i..].iter().take(128).take_while(|&&b| b==data[i]).count();
+            if run>=3{
+                out.extend_from_slice(&[(run-1) as u8,data[i]]);
+                i+=run;
+            }else{
+                let start=i;
+                while i<data.len() && i-start<127 && !(i+2<data.len() && data[i]==data[i+1] && data[i]==data[i+2]){
+                    i+=1;
+                }
+                out.push((-((i-start) as i32)) as u8);
+                out.extend_from_slice(&data[start..i]);
+            }
+        }
+        out
+    }
+
+    fn attribute(out:&mut Vec<u8>,name:&str,attribute_type:&str,value:&[u8]){
+        for string in [name,attribute_type]{
+            out.extend_from_slice(string.as_bytes());
+            out.push(0);
+        }
+        out.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        out.extend_from_slice(value);
+    }
+
+    //scanline file with half float b,g,r channels, texels are rgb
+    fn write(width:usize,height:usize,texels:&[[f32;3]],compression:u8)->Vec<u8>{
+        let mut channels=Vec::new();
+        for name in ["B","G","R"]{
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&PIXEL_TYPE_HALF.to_le_bytes());
+            channels.extend_from_slice(&[0,0,0,0]);
+            channels.extend_from_slice(&1i32.to_le_bytes());
+            channels.extend_from_slice(&1i32.to_le_bytes());
+        }
+        channels.push(0);
+        let data_window:Vec<u8>=[0,10,width as i32-1,10+height as i32-1].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        let mut out=Vec::new();
+        out.extend_from_slice(&MAGIC.to_le_bytes());
+        out.extend_from_slice(&2u32.to_le_bytes());
+        attribute(&mut out,"channels","chlist",&channels);
+        attribute(&mut out,"compression","compression",&[compression]);
+        attribute(&mut out,"dataWindow","box2i",&data_window);
+        attribute(&mut out,"displayWindow","box2i",&data_window);
+        out.push(0);
+
+        let table=out.len();
+        out.resize(table+height*8,0);
+        for y in 0..height{
+            let offset=out.len() as u64;
+            out[table+y*8..table+y*8+8].copy_from_slice(&offset.to_le_bytes());
+            let mut line=Vec::new();
+            for channel in (0..3).rev(){
+                for x in 0..width{
+                    line.extend_from_slice(&f32_to_f16(texels[y*width+x][channel]).to_le_bytes());
+                }
+            }
+            let data=if compression==COMPRESSION_RLE{
+                let compressed=compress_rle(&predict(&line));
+                assert_ne!(compressed.len(),line.len());
+                compressed
+            }else{
+                line
+            };
+            out.extend_from_slice(&(10+y as i32).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&data);
+        }
+        out
+    }
+
+    fn texels()->Vec<[f32;3]>{
+        (0..24).map(|i| if i<12{[1.0,0.5,0.25]}else{[i as f32,-2.0,65504.0]}).collect()
+    }
+
+    #[test]
+    fn rle_round_trip(){
+        let data:Vec<u8>=(0..300).map(|i| if i<200{7}else{(i*37) as u8}).collect();
+        assert_eq!(reconstruct(decompress_rle(&compress_rle(&predict(&data)),data.len())),data);
+    }
+
+    #[test]
+    fn uncompressed(){
+        let (image,full_precision)=parse("test.exr",&write(8,3,&texels(),COMPRESSION_NONE));
+        assert!(!full_precision);
+        assert_eq!((image.width,image.height,image.channels),(8,3,4));
+        assert_eq!(&image.values[0..4],&[1.0,0.5,0.25,1.0]);
+        assert_eq!(&image.values[13*4..14*4],&[13.0,-2.0,65504.0,1.0]);
+    }
+
+    #[test]
+    fn rle(){
+        let (image,_)=parse("test.exr",&write(8,3,&texels(),COMPRESSION_RLE));
+        let expected:Vec<f32>=texels().iter().flat_map(|texel| [texel[0],texel[1],texel[2],1.0]).collect();
+        assert_eq!(image.values,expected);
+    }
+}
@@ -0,0 +1,289 @@
+use ash::vk;
+use image::GenericImageView;
+
+use crate::{Decoder,Image};
+use crate::decoder::TextureDescription;
+use crate::color::{TextureUsage,srgb_to_linear,linear_to_srgb};
+use crate::mipmap;
+
+//formats for hdr images by preference, the first one filtering is supported for is used
+pub const HALF_FLOAT_FORMATS:[vk::Format;2]=[vk::Format::R16G16B16A16_SFLOAT,vk::Format::R32G32B32A32_SFLOAT];
+pub const FULL_FLOAT_FORMATS:[vk::Format;2]=[vk::Format::R32G32B32A32_SFLOAT,vk::Format::R16G16B16A16_SFLOAT];
+
+//image with 1 (gray), 2 (gray, alpha) or 4 (rgba) interleaved channels, used for textures that are not stored as 8 bit rgba.
+//values are linear, i.e. the color channels of sRGB encoded images have been converted.
+pub struct FloatImage{
+    pub width:u32,
+    pub height:u32,
+    pub channels:usize,
+    pub values:Vec<f32>,
+}
+impl FloatImage{
+    //convert a decoded image, the color channels of color textures are converted from sRGB to linear
+    pub fn from_image(native_image:&image::DynamicImage,usage:TextureUsage)->Self{
+        let unorm8=|values:&[u8]| values.iter().map(|&v| v as f32/255.0).collect::<Vec<f32>>();
+        let unorm16=|values:&[u16]| values.iter().map(|&v| v as f32/65535.0).collect::<Vec<f32>>();
+        let (channels,mut values)=if let Some(image)=native_image.as_luma8(){
+            (1,unorm8(image.as_raw()))
+        }else if let Some(image)=native_image.as_luma_alpha8(){
+            (2,unorm8(image.as_raw()))
+        }else if let Some(image)=native_image.as_luma16(){
+            (1,unorm16(image.as_raw()))
+        }else if let Some(image)=native_image.as_luma_alpha16(){
+            (2,unorm16(image.as_raw()))
+        }else{
+            (4,unorm16(native_image.to_rgba16().as_raw()))
+        };
+
+        if usage==TextureUsage::Color{
+            let color_channels=if channels==4{3}else{1};
+            for (i,value) in values.iter_mut().enumerate(){
+                if i%channels<color_channels{
+                    *value=srgb_to_linear(*value);
+                }
+            }
+        }
+
+        Self{
+            width:native_image.width(),
+            height:native_image.height(),
+            channels,
+            values,
+        }
+    }
+
+    //radiance rgbe (.hdr) file
+    pub fn load_radiance(filename:&str)->Self{
+        let file=std::fs::File::open(filename).unwrap_or_else(|e| panic!("failed to open {}: {}",filename,e));
+        let decoder=image::codecs::hdr::HdrDecoder::new(std::io::BufReader::new(file)).unwrap_or_else(|e| panic!("failed to decode {}: {}",filename,e));
+        let metadata=decoder.metadata();
+        let pixels=decoder.read_image_hdr().unwrap_or_else(|e| panic!("failed to decode {}: {}",filename,e));
+        Self{
+            width:metadata.width,
+            height:metadata.height,
+            channels:4,
+            values:pixels.iter().flat_map(|pixel| vec![pixel[0],pixel[1],pixel[2],1.0]).collect(),
+        }
+    }
+
+    fn downsample(&self)->Self{
+        let (width,height,values)=mipmap::downsample(self.width,self.height,self.channels,&self.values);
+        Self{
+            width,
+            height,
+            channels:self.channels,
+            values,
+        }
+    }
+}
+
+//formats to store a decoded image in by preference, or None if it is stored as 8 bit rgba.
+//the last format of each list can always be sampled with linear filtering.
+pub fn image_formats(native_image:&image::DynamicImage,usage:TextureUsage)->Option<&'static [vk::Format]>{
+    match (native_image,usage){
+        (image::DynamicImage::ImageLuma8(_),TextureUsage::Color)=>Some(&[vk::Format::R8_SRGB,vk::Format::R8G8B8A8_SRGB]),
+        (image::DynamicImage::ImageLuma8(_),TextureUsage::Data)=>Some(&[vk::Format::R8_UNORM,vk::Format::R8G8B8A8_UNORM]),
+        //there is no format with an sRGB encoded first and a linear second channel
+        (image::DynamicImage::ImageLumaA8(_),TextureUsage::Color)=>None,
+        (image::DynamicImage::ImageLumaA8(_),TextureUsage::Data)=>Some(&[vk::Format::R8G8_UNORM,vk::Format::R8G8B8A8_UNORM]),
+        //no 16 bit sRGB formats exist, so color textures are stored linear
+        (image::DynamicImage::ImageLuma16(_),_)=>Some(&[vk::Format::R16_UNORM,vk::Format::R16_SFLOAT,vk::Format::R16G16B16A16_UNORM,vk::Format::R16G16B16A16_SFLOAT]),
+        (image::DynamicImage::ImageLumaA16(_),_)=>Some(&[vk::Format::R16G16_UNORM,vk::Format::R16G16_SFLOAT,vk::Format::R16G16B16A16_UNORM,vk::Format::R16G16B16A16_SFLOAT]),
+        (image::DynamicImage::ImageRgb16(_),_) | (image::DynamicImage::ImageRgba16(_),_)=>Some(&[vk::Format::R16G16B16A16_UNORM,vk::Format::R16G16B16A16_SFLOAT]),
+        _=>None,
+    }
+}
+
+//round to the nearest half float, values beyond the half float range become infinite
+pub fn f32_to_f16(value:f32)->u16{
+    let sign=((value.to_bits()>>16) & 0x8000) as u16;
+    let magnitude=value.abs();
+    if magnitude.is_nan(){
+        return sign | 0x7e00;
+    }
+    if magnitude>=65520.0{
+        return sign | 0x7c00;
+    }
+    //subnormal, in steps of 2^-24 (rounding to nearest even like normal values, the largest ones round up to the smallest normal value)
+    if magnitude<6.103_515_6e-5{
+        return sign | (magnitude*16_777_216.0).round_ties_even() as u16;
+    }
+    let bits=magnitude.to_bits();
+    let exponent=(bits>>23) as i32-127+15;
+    let mantissa=bits & 0x7f_ffff;
+    let mut half=((exponent as u32)<<10) | (mantissa>>13);
+    //round to nearest even, a carry into the exponent is correct
+    let rest=mantissa & 0x1fff;
+    if rest>0x1000 || (rest==0x1000 && half & 1==1){
+        half+=1;
+    }
+    sign | half as u16
+}
+
+pub fn f16_to_f32(half:u16)->f32{
+    let sign=if half & 0x8000!=0{-1.0}else{1.0};
+    let exponent=((half>>10) & 0x1f) as i32;
+    let mantissa=(half & 0x3ff) as f32;
+    sign*match exponent{
+        0=>mantissa*2f32.powi(-24),
+        31=>if mantissa==0.0{f32::INFINITY}else{f32::NAN},
+        _=>(1.0+mantissa/1024.0)*2f32.powi(exponent-15),
+    }
+}
+
+fn channel_count(format:vk::Format)->usize{
+    match format{
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB | vk::Format::R16_UNORM | vk::Format::R16_SFLOAT | vk::Format::R32_SFLOAT=>1,
+        vk::Format::R8G8_UNORM | vk::Format::R16G16_UNORM | vk::Format::R16G16_SFLOAT | vk::Format::R32G32_SFLOAT=>2,
+        _=>4,
+    }
+}
+
+//tightly packed texels of the image in the given format.
+//gray images are expanded to gray rgb when stored in a four channel format.
+fn encode(format:vk::Format,image:&FloatImage)->Vec<u8>{
+    let channels=channel_count(format);
+    let texels=image.values.chunks_exact(image.channels).map(|texel| match (image.channels,channels){
+        (1,4)=>[texel[0],texel[0],texel[0],1.0],
+        (2,4)=>[texel[0],texel[0],texel[0],texel[1]],
+        _=>{
+            let mut values=[0.0;4];
+            values[..channels].copy_from_slice(&texel[..channels]);
+            values
+        },
+    });
+    let srgb=crate::color::is_srgb(format);
+    let color_channels=if channels==4{3}else{1};
+
+    let mut bytes=Vec::with_capacity(image.values.len()/image.channels*channels*4);
+    for texel in texels{
+        for (channel,&value) in texel.iter().take(channels).enumerate(){
+            match format{
+                vk::Format::R8_UNORM | vk::Format::R8G8_UNORM | vk::Format::R8G8B8A8_UNORM
+                | vk::Format::R8_SRGB | vk::Format::R8G8B8A8_SRGB=>{
+                    let value=if srgb && channel<color_channels{linear_to_srgb(value.clamp(0.0,1.0))}else{value};
+                    bytes.push((value.clamp(0.0,1.0)*255.0).round() as u8);
+                },
+                vk::Format::R16_UNORM | vk::Format::R16G16_UNORM | vk::Format::R16G16B16A16_UNORM=>{
+                    bytes.extend_from_slice(&((value.clamp(0.0,1.0)*65535.0).round() as u16).to_le_bytes());
+                },
+                vk::Format::R16_SFLOAT | vk::Format::R16G16_SFLOAT | vk::Format::R16G16B16A16_SFLOAT=>{
+                    bytes.extend_from_slice(&f32_to_f16(value.clamp(-65504.0,65504.0)).to_le_bytes());
+                },
+                vk::Format::R32_SFLOAT | vk::Format::R32G32_SFLOAT | vk::Format::R32G32B32A32_SFLOAT=>{
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                },
+                _=>panic!("cannot store images in format {:?}",format),
+            }
+        }
+    }
+    bytes
+}
+
+impl Decoder{
+    //upload an image with a full mip chain in the first of the formats that supports linear filtering
+    //single channel formats are sampled as gray (and two channel formats as gray with alpha)
+    pub fn create_float_texture(&mut self,name:String,image:&FloatImage,formats:&[vk::Format],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        self.create_float_texture_layers(name,std::slice::from_ref(image),vk::ImageViewType::TYPE_2D,formats,command_buffer)
+    }
+
+    //same as create_float_texture, for images with several layers of the same size and channel count (e.g. the 6 faces of a cubemap)
+    pub(crate) fn create_float_texture_layers(&mut self,name:String,layers:&[FloatImage],view_type:vk::ImageViewType,formats:&[vk::Format],command_buffer:vk::CommandBuffer)->std::sync::Arc<Image>{
+        let format=*formats.iter().find(|format| self.format_supports(**format,vk::FormatFeatureFlags::SAMPLED_IMAGE | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR))
+            .unwrap_or_else(|| panic!("none of the formats {:?} for texture {} can be sampled by the device",formats,name));
+        let components=match channel_count(format){
+            1=>vk::ComponentMapping{
+                r:vk::ComponentSwizzle::R,
+                g:vk::ComponentSwizzle::R,
+                b:vk::ComponentSwizzle::R,
+                a:vk::ComponentSwizzle::ONE,
+            },
+            2=>vk::ComponentMapping{
+                r:vk::ComponentSwizzle::R,
+                g:vk::ComponentSwizzle::R,
+                b:vk::ComponentSwizzle::R,
+                a:vk::ComponentSwizzle::G,
+            },
+            _=>vk::ComponentMapping::default(),
+        };
+        let (width,height)=(layers[0].width,layers[0].height);
+        if layers.iter().any(|layer| layer.width!=width || layer.height!=height || layer.channels!=layers[0].channels){
+            panic!("layers of texture {} differ in size or channel count",name);
+        }
+        let description=TextureDescription{
+            format,
+            extent:vk::Extent2D{
+                width,
+                height,
+            },
+            mip_levels:mipmap::mip_level_count(width,height),
+            array_layers:layers.len() as u32,
+            view_type,
+            components,
+        };
+
+        let encode_layers=|layers:&[FloatImage]| layers.iter().flat_map(|layer| encode(format,layer)).collect::<Vec<u8>>();
+        let mut levels=vec![encode_layers(layers)];
+        //mips are averaged from the linear values when the format cannot be blitted
+        if description.mip_levels>1 && !self.format_supports_blits(format){
+            let mut level:Vec<FloatImage>=layers.iter().map(|layer| layer.downsample()).collect();
+            levels.push(encode_layers(&level));
+            for _ in 2..description.mip_levels{
+                level=level.iter().map(|layer| layer.downsample()).collect();
+                levels.push(encode_layers(&level));
+            }
+        }
+        let levels:Vec<&[u8]>=levels.iter().map(|level| &level[..]).collect();
+        self.upload_texture(name,&description,&levels,command_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn f32_to_f16_exact(){
+        assert_eq!(f32_to_f16(0.0),0x0000);
+        assert_eq!(f32_to_f16(-0.0),0x8000);
+        assert_eq!(f32_to_f16(1.0),0x3c00);
+        assert_eq!(f32_to_f16(-2.0),0xc000);
+        assert_eq!(f32_to_f16(65504.0),0x7bff);
+        //smallest normal and subnormal values
+        assert_eq!(f32_to_f16(2f32.powi(-14)),0x0400);
+        assert_eq!(f32_to_f16(2f32.powi(-24)),0x0001);
+        assert_eq!(f32_to_f16(f32::INFINITY),0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY),0xfc00);
+    }
+
+    #[test]
+    fn f32_to_f16_rounding(){
+        //ties round to the even mantissa
+        assert_eq!(f32_to_f16(1.0+2f32.powi(-11)),0x3c00);
+        assert_eq!(f32_to_f16(1.0+3.0*2f32.powi(-11)),0x3c02);
+        assert_eq!(f32_to_f16(1.0+2f32.powi(-11)+2f32.powi(-20)),0x3c01);
+        //a carry out of the mantissa increments the exponent
+        assert_eq!(f32_to_f16(2.0-2f32.powi(-12)),0x4000);
+        //halfway between the largest finite value and the next step overflows
+        assert_eq!(f32_to_f16(65519.0),0x7bff);
+        assert_eq!(f32_to_f16(65520.0),0x7c00);
+        //subnormal ties
+        assert_eq!(f32_to_f16(2f32.powi(-25)),0x0000);
+        assert_eq!(f32_to_f16(3.0*2f32.powi(-25)),0x0002);
+        assert_eq!(f32_to_f16(-3.0*2f32.powi(-25)),0x8002);
+        //the largest subnormal values round up to the smallest normal one
+        assert_eq!(f32_to_f16(2f32.powi(-14)-2f32.powi(-26)),0x0400);
+    }
+
+    #[test]
+    fn f16_round_trip(){
+        for half in 0..=u16::MAX{
+            let value=f16_to_f32(half);
+            if value.is_nan(){
+                assert!(f16_to_f32(f32_to_f16(value)).is_nan(),"{:#06x}",half);
+            }else{
+                assert_eq!(f32_to_f16(value),half,"{:#06x} -> {}",half,value);
+            }
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use ash::{
+    vk,
+};
+
+use crate::{Decoder,Vertex};
+use crate::decoder::{VertexIndices,smooth_normals};
+use crate::json::JsonValue;
+use crate::model::{Model,ModelNode,Submesh,ModelMaterial};
+use crate::sampler::SamplerDescription;
+use crate::color::{TextureUsage,linear_to_srgb,srgb_to_linear};
+
+const GLB_MAGIC:u32=0x4654_6c67;//"glTF"
+const GLB_CHUNK_JSON:u32=0x4e4f_534a;
+const GLB_CHUNK_BIN:u32=0x004e_4942;
+
+//decode base64 (standard alphabet, padding optional), as used in data uris
+fn decode_base64(text:&str)->Vec<u8>{
+    let value=|c:u8|->u32{
+        match c{
+            b'A'..=b'Z'=>(c-b'A') as u32,
+            b'a'..=b'z'=>(c-b'a') as u32+26,
+            b'0'..=b'9'=>(c-b'0') as u32+52,
+            b'+' | b'-'=>62,
+            b'/' | b'_'=>63,
+            _=>panic!("invalid base64 character {}",c as char),
+        }
+    };
+    let digits:Vec<u8>=text.bytes().filter(|c| !c.is_ascii_whitespace() && *c!=b'=').collect();
+    let mut bytes=Vec::with_capacity(digits.len()*3/4);
+    for chunk in digits.chunks(4){
+        let mut group=0u32;
+        for (i,digit) in chunk.iter().enumerate(){
+            group|=value(*digit)<<(18-6*i);
+        }
+        for i in 0..chunk.len()-1{
+            bytes.push((group>>(16-8*i)) as u8);
+        }
+    }
+    bytes
+}
+
+//decode %xx escapes of a relative uri reference into the utf-8 path they encode
+fn percent_decode(uri:&str)->String{
+    let hex=|c:u8| (c as char).to_digit(16);
+    let bytes=uri.as_bytes();
+    let mut decoded=Vec::with_capacity(bytes.len());
+    let mut i=0;
+    while i<bytes.len(){
+        match (bytes[i],bytes.get(i+1).and_then(|c| hex(*c)),bytes.get(i+2).and_then(|c| hex(*c))){
+            (b'%',Some(high),Some(low))=>{
+                decoded.push((high*16+low) as u8);
+                i+=3;
+            },
+            (c,_,_)=>{
+                decoded.push(c);
+                i+=1;
+            },
+        }
+    }
+    String::from_utf8(decoded).unwrap_or_else(|_| panic!("uri {} does not decode to utf-8",uri))
+}
+
+fn u32_at(bytes:&[u8],offset:usize)->u32{
+    u32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+
+//split binary gltf into the json chunk and the optional binary chunk that holds the first buffer
+fn split_glb(path:&str,file:&[u8])->(JsonValue,Option<Vec<u8>>){
+    let mut json=None;
+    let mut binary_chunk=None;
+    let mut offset=12;
+    while offset+8<=file.len(){
+        let chunk_length=u32_at(file,offset) as usize;
+        let chunk_type=u32_at(file,offset+4);
+        let chunk=file.get(offset+8..offset+8+chunk_length).unwrap_or_else(|| panic!("{}: glb chunk at byte {} with length {} exceeds the file size {}",path,offset,chunk_length,file.len()));
+        match chunk_type{
+            GLB_CHUNK_JSON=>json=Some(JsonValue::parse(std::str::from_utf8(chunk).unwrap()).unwrap()),
+            GLB_CHUNK_BIN=>binary_chunk=Some(chunk.to_vec()),
+            _=>{},//unknown chunks must be ignored
+        }
+        offset+=8+chunk_length;
+    }
+    (json.unwrap_or_else(|| panic!("{}: glb has no json chunk",path)),binary_chunk)
+}
+
+//data of a uri relative to the gltf file, which may be a base64 data uri
+fn read_uri(directory:&std::path::Path,uri:&str)->Vec<u8>{
+    if let Some(data)=uri.strip_prefix("data:"){
+        let (_media_type,data)=data.split_once(";base64,").unwrap_or_else(|| panic!("unsupported data uri"));
+        return decode_base64(data);
+    }
+    let path=directory.join(percent_decode(uri));
+    std::fs::read(&path).unwrap_or_else(|e| panic!("failed to read {}: {}",path.display(),e))
+}
+
+//parsed gltf document with all buffers loaded
+struct Gltf{
+    path:String,//for error messages
+    json:JsonValue,
+    buffers:Vec<Vec<u8>>,
+}
+impl Gltf{
+    fn open(path:&str)->Self{
+        let directory=std::path::Path::new(path).parent().unwrap_or_else(|| std::path::Path::new(""));
+        let file=std::fs::read(path).unwrap_or_else(|e| panic!("failed to read {}: {}",path,e));
+
+        let (json,mut binary_chunk)=if file.len()>=12 && u32_at(&file,0)==GLB_MAGIC{
+            split_glb(path,&file)
+        }else{
+            (JsonValue::parse(std::str::from_utf8(&file).unwrap()).unwrap(),None)
+        };
+
+        let buffers=json.get("buffers").map(|buffers| buffers.elements()).unwrap_or(&[]).iter().map(|buffer|{
+            match buffer.get("uri").and_then(|uri| uri.as_str()){
+                Some(uri)=>read_uri(directory,uri),
+                None=>binary_chunk.take().unwrap_or_else(|| panic!("{}: buffer without uri, but no binary chunk",path)),
+            }
+        }).collect();
+
+        Self{
+            path:String::from(path),
+            json,
+            buffers,
+        }
+    }
+
+    //element of a top level array, e.g. ("accessors",3)
+    fn item(&self,kind:&str,index:usize)->&JsonValue{
+        self.json.get(kind).and_then(|items| items.elements().get(index)).unwrap_or_else(|| panic!("{}: gltf has no {} {}",self.path,kind,index))
+    }
+
+    fn items(&self,kind:&str)->&[JsonValue]{
+        self.json.get(kind).map(|items| items.elements()).unwrap_or(&[])
+    }
+
+    //bytes of a buffer view, and its stride (0 if tightly packed)
+    fn buffer_view(&self,index:usize)->(&[u8],usize){
+        let buffer_view=self.item("bufferViews",index);
+        let buffer_index=buffer_view.get("buffer").and_then(|b| b.as_usize()).unwrap_or_else(|| panic!("{}: buffer view {} has no buffer",self.path,index));
+        let buffer=self.buffers.get(buffer_index).unwrap_or_else(|| panic!("{}: buffer view {} refers to missing buffer {}",self.path,index,buffer_index));
+        let offset=buffer_view.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+        let length=buffer_view.get("byteLength").and_then(|l| l.as_usize()).unwrap_or_else(|| panic!("{}: buffer view {} has no byte length",self.path,index));
+        let stride=buffer_view.get("byteStride").and_then(|s| s.as_usize()).unwrap_or(0);
+        let bytes=buffer.get(offset..offset+length).unwrap_or_else(|| panic!("{}: buffer view {} (bytes {}..{}) exceeds buffer {} of {} bytes",self.path,index,offset,offset+length,buffer_index,buffer.len()));
+        (bytes,stride)
+    }
+
+    //bytes of the buffer view of an accessor, with the accessor offset and element stride,
+    //after checking that count elements of element_size bytes fit into the buffer view
+    fn accessor_bytes(&self,index:usize,buffer_view:usize,count:usize,element_size:usize)->(&[u8],usize,usize){
+        let accessor=self.item("accessors",index);
+        let (bytes,stride)=self.buffer_view(buffer_view);
+        let offset=accessor.get("byteOffset").and_then(|o| o.as_usize()).unwrap_or(0);
+        let stride=if stride==0{element_size}else{stride};
+        if count>0 && offset+(count-1)*stride+element_size>bytes.len(){
+            panic!("{}: accessor {} ({} elements of {} bytes, stride {}, offset {}) exceeds buffer view {} of {} bytes",self.path,index,count,element_size,stride,offset,buffer_view,bytes.len());
+        }
+        (bytes,offset,stride)
+    }
+
+    //number of elements of an accessor
+    fn accessor_count(&self,index:usize)->usize{
+        self.item("accessors",index).get("count").and_then(|c| c.as_usize()).unwrap_or_else(|| panic!("{}: accessor {} has no count",self.path,index))
+    }
+
+    //read all elements of an accessor as floats, converting (and normalizing, if set) integer components
+    //returns the values and the number of components per element
+    fn read_accessor(&self,index:usize)->(Vec<f32>,usize){
+        let accessor=self.item("accessors",index);
+        if accessor.get("sparse").is_some(){
+            panic!("{}: accessor {} is sparse, which is not supported",self.path,index);
+        }
+        let count=self.accessor_count(index);
+        let components=match accessor.get("type").and_then(|t| t.as_str()).unwrap(){
+            "SCALAR"=>1,
+            "VEC2"=>2,
+            "VEC3"=>3,
+            "VEC4"=>4,
+            "MAT4"=>16,
+            other=>panic!("{}: accessor {} has unsupported type {}",self.path,index,other),
+        };
+        let component_type=accessor.get("componentType").and_then(|c| c.as_usize()).unwrap_or_else(|| panic!("{}: accessor {} has no component type",self.path,index));
+        let normalized=accessor.get("normalized").and_then(|n| n.as_bool()).unwrap_or(false);
+        let component_size=match component_type{
+            5120 | 5121=>1,
+            5122 | 5123=>2,
+            5125 | 5126=>4,
+            other=>panic!("{}: accessor {} has unsupported component type {}",self.path,index,other),
+        };
+
+        let buffer_view=match accessor.get("bufferView").and_then(|b| b.as_usize()){
+            Some(buffer_view)=>buffer_view,
+            //accessors without buffer view are all zeros
+            None=>return (vec![0.0;count*components],components),
+        };
+        let (bytes,offset,stride)=self.accessor_bytes(index,buffer_view,count,component_size*components);
+
+        let mut values=Vec::with_capacity(count*components);
+        for element in 0..count{
+            for component in 0..components{
+                let at=offset+element*stride+component*component_size;
+                let value=match component_type{
+                    5120=>{
+                        let v=bytes[at] as i8 as f32;
+                        if normalized{(v/127.0).max(-1.0)}else{v}
+                    },
+                    5121=>{
+                        let v=bytes[at] as f32;
+                        if normalized{v/255.0}else{v}
+                    },
+                    5122=>{
+                        let v=i16::from_le_bytes([bytes[at],bytes[at+1]]) as f32;
+                        if normalized{(v/32767.0).max(-1.0)}else{v}
+                    },
+                    5123=>{
+                        let v=u16::from_le_bytes([bytes[at],bytes[at+1]]) as f32;
+                        if normalized{v/65535.0}else{v}
+                    },
+                    5125=>u32_at(bytes,at) as f32,
+                    _=>f32::from_le_bytes([bytes[at],bytes[at+1],bytes[at+2],bytes[at+3]]),
+                };
+                values.push(value);
+            }
+        }
+        (values,components)
+    }
+
+    //read index accessor without going through f32, which cannot represent all u32 values
+    fn read_indices(&self,index:usize)->Vec<u32>{
+        let accessor=self.item("accessors",index);
+        let count=self.accessor_count(index);
+        let component_type=accessor.get("componentType").and_then(|c| c.as_usize()).unwrap_or_else(|| panic!("{}: accessor {} has no component type",self.path,index));
+        let component_size=match component_type{
+            5121=>1,
+            5123=>2,
+            5125=>4,
+            other=>panic!("{}: index accessor {} has unsupported component type {}",self.path,index,other),
+        };
+        let buffer_view=accessor.get("bufferView").and_then(|b| b.as_usize()).unwrap_or_else(|| panic!("{}: index accessor {} has no buffer view",self.path,index));
+        let (bytes,offset,stride)=self.accessor_bytes(index,buffer_view,count,component_size);
+        (0..count).map(|i|{
+            let at=offset+i*stride;
+            match component_type{
+                5121=>bytes[at] as u32,
+                5123=>u16::from_le_bytes([bytes[at],bytes[at+1]]) as u32,
+                _=>u32_at(bytes,at),
+            }
+        }).collect()
+    }
+
+    //encoded bytes of an image, from a file, a data uri or a buffer view
+    fn image_bytes(&self,directory:&std::path::Path,index:usize)->Vec<u8>{
+        let image=self.item("images",index);
+        match (image.get("uri").and_then(|uri| uri.as_str()),image.get("bufferView").and_then(|b| b.as_usize())){
+            (Some(uri),_)=>read_uri(directory,uri),
+            (None,Some(buffer_view))=>self.buffer_view(buffer_view).0.to_vec(),
+            (None,None)=>panic!("{}: image {} has no data",self.path,index),
+        }
+    }
+
+    fn sampler(&self,index:Option<usize>)->SamplerDescription{
+        let default=SamplerDescription::default();
+        let sampler=match index{
+            Some(index)=>self.item("samplers",index),
+            None=>return default,
+        };
+        let filter=|value:Option<usize>,default:vk::Filter| match value{
+            Some(9728) | Some(9984) | Some(9986)=>vk::Filter::NEAREST,
+            Some(9729) | Some(9985) | Some(9987)=>vk::Filter::LINEAR,
+            _=>default,
+        };
+        let address_mode=|value:Option<usize>| match value{
+            Some(33071)=>vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            Some(33648)=>vk::SamplerAddressMode::MIRRORED_REPEAT,
+            _=>vk::SamplerAddressMode::REPEAT,
+        };
+        let min_filter=sampler.get("minFilter").and_then(|f| f.as_usize());
+        SamplerDescription{
+            mag_filter:filter(sampler.get("magFilter").and_then(|f| f.as_usize()),default.mag_filter),
+            min_filter:filter(min_filter,default.min_filter),
+            mipmap_mode:match min_filter{
+                Some(9984) | Some(9985)=>vk::SamplerMipmapMode::NEAREST,
+                _=>vk::SamplerMipmapMode::LINEAR,
+            },
+            address_mode_u:address_mode(sampler.get("wrapS").and_then(|w| w.as_usize())),
+            address_mode_v:address_mode(sampler.get("wrapT").and_then(|w| w.as_usize())),
+            ..default
+        }
+    }
+
+    fn node_transform(node:&JsonValue)->glm::Mat4{
+        let floats=|key:&str| node.get(key).map(|value| value.elements().iter().map(|v| v.as_f64().unwrap() as f32).collect::<Vec<f32>>());
+        if let Some(matrix)=floats("matrix"){
+            //column major, same as glm
+            return glm::make_mat4(&matrix);
+        }
+        let translation=floats("translation").map(|t| glm::vec3(t[0],t[1],t[2])).unwrap_or_else(|| glm::vec3(0.0,0.0,0.0));
+        let rotation=floats("rotation").map(|r| glm::quat(r[0],r[1],r[2],r[3])).unwrap_or_else(glm::quat_identity);
+        let scale=floats("scale").map(|s| glm::vec3(s[0],s[1],s[2])).unwrap_or_else(|| glm::vec3(1.0,1.0,1.0));
+        glm::translation(&translation)*glm::quat_to_mat4(&rotation)*glm::scaling(&scale)
+    }
+}
+
+//multiply an sRGB encoded image with a linear rgba factor
+fn tint_image(mut image:image::RgbaImage,factor:&[f32])->image::RgbaImage{
+    let to_u8=|c:f32| (c.clamp(0.0,1.0)*255.0).round() as u8;
+    for pixel in image.pixels_mut(){
+        for c in 0..3{
+            pixel[c]=to_u8(linear_to_srgb(srgb_to_linear(pixel[c] as f32/255.0)*factor[c]));
+        }
+        pixel[3]=to_u8(pixel[3] as f32/255.0*factor[3]);
+    }
+    image
+}
+
+impl Decoder{
+    //load gltf or glb file, with one submesh per mesh primitive (triangles only) and the node hierarchy of the default scene
+    pub(crate) fn load_gltf(&mut self,path:&str,command_buffer:vk::CommandBuffer)->Model{
+        let gltf=Gltf::open(path);
+        let directory=std::path::Path::new(path).parent().unwrap_or_else(|| std::path::Path::new(""));
+
+        //images are shared between textures, and decoded only once
+        let mut images=vec![None;gltf.items("images").len()];
+        let mut materials=Vec::new();
+        for (material_index,material) in gltf.items("materials").iter().enumerate(){
+            let pbr=material.get("pbrMetallicRoughness");
+            let base_color=pbr.and_then(|pbr| pbr.get("baseColorFactor")).map(|factor| factor.elements().iter().map(|v| v.as_f64().unwrap() as f32).collect::<Vec<f32>>()).unwrap_or_else(|| vec![1.0;4]);
+            let texture=pbr.and_then(|pbr| pbr.get("baseColorTexture")).and_then(|info| info.get("index")).and_then(|index| index.as_usize()).map(|index| gltf.item("textures",index));
+
+            //the base color factor is multiplied with the base color texture, or used as a 1x1 texture if there is none
+            let (diffuse_texture,sampler)=match texture.and_then(|texture| texture.get("source").and_then(|s| s.as_usize()).map(|source| (texture,source))){
+                Some((texture,source)) if base_color.iter().any(|c| *c!=1.0)=>{
+                    //tinted copy of the image, per material
+                    let name=format!("{}#material{}",path,material_index);
+                    let diffuse_texture=match self.textures.get(&name){
+                        Some(texture)=>texture.clone(),
+                        None=>{
+                            let native_image=image::load_from_memory(&gltf.image_bytes(directory,source)).unwrap_or_else(|e| panic!("{}: failed to decode image {}: {}",path,source,e));
+                            let native_image=image::DynamicImage::ImageRgba8(tint_image(native_image.to_rgba8(),&base_color));
+                            self.create_texture_from_image(name,&native_image,TextureUsage::Color,command_buffer)
+                        },
+                    };
+                    (diffuse_texture,gltf.sampler(texture.get("sampler").and_then(|s| s.as_usize())))
+                },
+                Some((texture,source))=>{
+                    if images[source].is_none(){
+                        let image=gltf.item("images",source);
+                        images[source]=Some(match image.get("uri").and_then(|uri| uri.as_str()){
+                            Some(uri) if !uri.starts_with("data:")=>self.get_texture(directory.join(percent_decode(uri)).to_str().unwrap(),TextureUsage::Color,command_buffer),
+                            _=>{
+                                let name=format!("{}#image{}",path,source);
+                                match self.textures.get(&name){
+                                    Some(texture)=>texture.clone(),
+                                    None=>{
+                                        let native_image=image::load_from_memory(&gltf.image_bytes(directory,source)).unwrap_or_else(|e| panic!("{}: failed to decode image {}: {}",path,source,e));
+                                        self.create_texture_from_image(name,&native_image,TextureUsage::Color,command_buffer)
+                                    },
+                                }
+                            },
+                        });
+                    }
+                    (images[source].clone().unwrap(),gltf.sampler(texture.get("sampler").and_then(|s| s.as_usize())))
+                },
+                None=>{
+                    //the factor is linear, color textures are sRGB encoded
+                    let to_u8=|c:f32| (c.clamp(0.0,1.0)*255.0).round() as u8;
+                    let to_srgb_u8=|c:f32| to_u8(linear_to_srgb(c.clamp(0.0,1.0)));
+                    (self.get_color_texture([to_srgb_u8(base_color[0]),to_srgb_u8(base_color[1]),to_srgb_u8(base_color[2]),to_u8(base_color[3])],command_buffer),SamplerDescription::default())
+                },
+            };
+
+            materials.push(std::sync::Arc::new(ModelMaterial{
+                name:material.get("name").and_then(|n| n.as_str()).map(String::from).unwrap_or_else(|| format!("material{}",material_index)),
+                diffuse_color:glm::vec3(base_color[0],base_color[1],base_color[2]),
+                alpha:base_color[3],
+                transparent:material.get("alphaMode").and_then(|m| m.as_str())==Some("BLEND"),
+                diffuse_texture,
+                sampler,
+                normal_map:None,
+                specular_map:None,
+            }));
+        }
+
+        //submeshes of each gltf mesh
+        let mut submeshes=Vec::new();
+        let mut mesh_submeshes=Vec::new();
+        for (mesh_index,mesh) in gltf.items("meshes").iter().enumerate(){
+            let mesh_name=mesh.get("name").and_then(|n| n.as_str()).map(String::from).unwrap_or_else(|| format!("mesh{}",mesh_index));
+            let mut primitive_submeshes=Vec::new();
+            for (primitive_index,primitive) in mesh.get("primitives").map(|p| p.elements()).unwrap_or(&[]).iter().enumerate(){
+                //points and lines are not drawn
+                if primitive.get("mode").and_then(|m| m.as_usize()).unwrap_or(4)!=4{
+                    continue;
+                }
+                let attributes=primitive.get("attributes").expect("primitive has no attributes");
+                let position_accessor=attributes.get("POSITION").and_then(|a| a.as_usize()).unwrap_or_else(|| panic!("{}: primitive {} of mesh {} has no positions",path,primitive_index,mesh_index));
+                let vertex_count=gltf.accessor_count(position_accessor);
+                //attributes with the number of components they must have
+                let attribute=|name:&str,components:usize| attributes.get(name).and_then(|a| a.as_usize()).map(|accessor|{
+                    let (values,accessor_components)=gltf.read_accessor(accessor);
+                    if accessor_components!=components || values.len()!=vertex_count*components{
+                        panic!("{}: accessor {} ({}) has {} elements of {} components, but the primitive has {} vertices",path,accessor,name,values.len()/accessor_components,accessor_components,vertex_count);
+                    }
+                    values
+                });
+
+                let positions=attribute("POSITION",3).unwrap();
+                let positions:Vec<glm::Vec3>=positions.chunks(3).map(|p| glm::vec3(p[0],p[1],p[2])).collect();
+                let indices=match primitive.get("indices").and_then(|i| i.as_usize()){
+                    Some(accessor)=>{
+                        let indices=gltf.read_indices(accessor);
+                        if let Some(index)=indices.iter().find(|index| **index as usize>=vertex_count){
+                            panic!("{}: index accessor {} contains index {}, but the primitive has {} vertices",path,accessor,index,vertex_count);
+                        }
+                        indices
+                    },
+                    None=>(0..vertex_count as u32).collect(),
+                };
+                let triangles:Vec<[usize;3]>=indices.chunks_exact(3).map(|t| [t[0] as usize,t[1] as usize,t[2] as usize]).collect();
+                let normals=match attribute("NORMAL",3){
+                    Some(normals)=>normals.chunks(3).map(|n| glm::vec3(n[0],n[1],n[2])).collect(),
+                    None=>smooth_normals(&positions,&triangles),
+                };
+                let texcoords=attribute("TEXCOORD_0",2).unwrap_or_else(|| vec![0.0;vertex_count*2]);
+
+                let vertices:Vec<Vertex>=(0..vertex_count).map(|i|{
+                    Vertex::new(positions[i].x,positions[i].y,positions[i].z,1.0,texcoords[i*2],texcoords[i*2+1]).with_normal(normals[i].x,normals[i].y,normals[i].z)
+                }).collect();
+                let vertex_indices:Vec<VertexIndices>=indices.chunks_exact(3).map(|t| VertexIndices::new(t[0],t[1],t[2])).collect();
+
+                let material=primitive.get("material").and_then(|m| m.as_usize()).map(|m| materials[m].clone());
+                primitive_submeshes.push(submeshes.len());
+                submeshes.push(Submesh{
+                    name:format!("{}/{}",mesh_name,primitive_index),
+                    material_name:material.as_ref().map(|material| material.name.clone()),
+                    material,
+                    mesh:std::sync::Arc::new(self.create_mesh(&vertices,&vertex_indices,true,command_buffer)),
+                });
+            }
+            mesh_submeshes.push(primitive_submeshes);
+        }
+        if submeshes.is_empty(){
+            panic!("{} contains no triangles",path);
+        }
+
+        let nodes:Vec<ModelNode>=gltf.items("nodes").iter().enumerate().map(|(node_index,node)|{
+            ModelNode{
+                name:node.get("name").and_then(|n| n.as_str()).map(String::from).unwrap_or_else(|| format!("node{}",node_index)),
+                local_transform:Gltf::node_transform(node),
+                submeshes:node.get("mesh").and_then(|m| m.as_usize()).map(|mesh| mesh_submeshes[mesh].clone()).unwrap_or_default(),
+                children:node.get("children").map(|c| c.elements()).unwrap_or(&[]).iter().map(|child| child.as_usize().unwrap()).collect(),
+            }
+        }).collect();
+
+        //root nodes of the default scene, or all nodes that are not a child of another node if there are no scenes
+        let root_nodes:Vec<usize>=match gltf.json.get("scene").and_then(|s| s.as_usize()).or_else(|| if gltf.items("scenes").is_empty(){None}else{Some(0)}){
+            Some(scene)=>gltf.item("scenes",scene).get("nodes").map(|n| n.elements()).unwrap_or(&[]).iter().map(|node| node.as_usize().unwrap()).collect(),
+            None=>(0..nodes.len()).filter(|node| !nodes.iter().any(|parent| parent.children.contains(node))).collect(),
+        };
+
+        Model::with_nodes(submeshes,nodes,root_nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn gltf(json:&str,buffer:Vec<u8>)->Gltf{
+        Gltf{
+            path:String::from("test.gltf"),
+            json:JsonValue::parse(json).unwrap(),
+            buffers:vec![buffer],
+        }
+    }
+
+    #[test]
+    fn base64(){
+        assert_eq!(decode_base64("aGVsbG8="),b"hello");
+        assert_eq!(decode_base64("aGVsbG8"),b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8h"),b"hello!");
+        assert_eq!(decode_base64("+/8="),[0xfb,0xff]);
+        assert_eq!(decode_base64("-_8"),[0xfb,0xff]);
+        assert!(decode_base64("").is_empty());
+    }
+
+    #[test]
+    fn percent_decoding(){
+        assert_eq!(percent_decode("my%20model%2Fa%C3%A9.png"),"my model/aé.png");
+        assert_eq!(percent_decode("plain.png"),"plain.png");
+        //not an escape
+        assert_eq!(percent_decode("100%.png"),"100%.png");
+        assert_eq!(percent_decode("%2"),"%2");
+    }
+
+    #[test]
+    fn glb_chunks(){
+        let json=br#"{"asset":{"version":"2.0"}}  "#;
+        let binary=[1u8,2,3,4];
+        let mut file=Vec::new();
+        let chunk=|file:&mut Vec<u8>,chunk_type:u32,data:&[u8]|{
+            file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            file.extend_from_slice(&chunk_type.to_le_bytes());
+            file.extend_from_slice(data);
+        };
+        file.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        file.extend_from_slice(&2u32.to_le_bytes());
+        file.extend_from_slice(&0u32.to_le_bytes());
+        chunk(&mut file,GLB_CHUNK_JSON,json);
+        //unknown chunks are skipped
+        chunk(&mut file,0x1234_5678,&[9;8]);
+        chunk(&mut file,GLB_CHUNK_BIN,&binary);
+        let length=file.len() as u32;
+        file[8..12].copy_from_slice(&length.to_le_bytes());
+
+        let (json,binary_chunk)=split_glb("test.glb",&file);
+        assert_eq!(json.get("asset").and_then(|a| a.get("version")).and_then(|v| v.as_str()),Some("2.0"));
+        assert_eq!(binary_chunk,Some(binary.to_vec()));
+
+        file.truncate(file.len()-1);
+        let result=std::panic::catch_unwind(|| split_glb("test.glb",&file));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn accessor_normalization(){
+        let buffer=vec![
+            0,255,51,0,//unsigned byte
+            0x80,0x7f,0x81,0,//byte
+            0xff,0xff,0,0,//unsigned short
+            0x00,0x80,0xff,0x7f,//short
+        ];
+        let gltf=gltf(r#"{
+            "bufferViews":[{"buffer":0,"byteLength":16}],
+            "accessors":[
+                {"bufferView":0,"componentType":5121,"normalized":true,"count":3,"type":"SCALAR"},
+                {"bufferView":0,"componentType":5121,"count":3,"type":"SCALAR"},
+                {"bufferView":0,"byteOffset":4,"componentType":5120,"normalized":true,"count":3,"type":"SCALAR"},
+                {"bufferView":0,"byteOffset":8,"componentType":5123,"normalized":true,"count":2,"type":"SCALAR"},
+                {"bufferView":0,"byteOffset":12,"componentType":5122,"normalized":true,"count":2,"type":"SCALAR"},
+                {"componentType":5126,"count":2,"type":"VEC3"}
+            ]
+        }"#,buffer);
+        assert_eq!(gltf.read_accessor(0),(vec![0.0,1.0,0.2],1));
+        assert_eq!(gltf.read_accessor(1),(vec![0.0,255.0,51.0],1));
+        //-128 and -127 both map to -1
+        assert_eq!(gltf.read_accessor(2),(vec![-1.0,1.0,-1.0],1));
+        assert_eq!(gltf.read_accessor(3),(vec![1.0,0.0],1));
+        assert_eq!(gltf.read_accessor(4),(vec![-1.0,1.0],1));
+        //accessors without buffer view are zeros
+        assert_eq!(gltf.read_accessor(5),(vec![0.0;6],3));
+    }
+
+    #[test]
+    fn interleaved_strides(){
+        //three elements of a vec2 float followed by an unsigned short index, 12 bytes apart, after 4 bytes of padding
+        let mut buffer=vec![0;4];
+        for i in 0..3u16{
+            buffer.extend_from_slice(&(i as f32).to_le_bytes());
+            buffer.extend_from_slice(&(i as f32*2.0).to_le_bytes());
+            buffer.extend_from_slice(&(i+7).to_le_bytes());
+            buffer.extend_from_slice(&[0,0]);
+        }
+        let gltf=gltf(r#"{
+            "bufferViews":[{"buffer":0,"byteOffset":4,"byteLength":36,"byteStride":12}],
+            "accessors":[
+                {"bufferView":0,"componentType":5126,"count":3,"type":"VEC2"},
+                {"bufferView":0,"byteOffset":8,"componentType":5123,"count":3,"type":"SCALAR"}
+            ]
+        }"#,buffer);
+        assert_eq!(gltf.read_accessor(0),(vec![0.0,0.0,1.0,2.0,2.0,4.0],2));
+        assert_eq!(gltf.read_indices(1),vec![7,8,9]);
+    }
+
+    #[test]
+    #[should_panic(expected="test.gltf: accessor 0")]
+    fn accessor_out_of_range(){
+        let gltf=gltf(r#"{
+            "bufferViews":[{"buffer":0,"byteLength":8}],
+            "accessors":[{"bufferView":0,"componentType":5126,"count":3,"type":"SCALAR"}]
+        }"#,vec![0;8]);
+        gltf.read_accessor(0);
+    }
+
+    #[test]
+    #[should_panic(expected="test.gltf: buffer view 0")]
+    fn buffer_view_out_of_range(){
+        let gltf=gltf(r#"{
+            "bufferViews":[{"buffer":0,"byteOffset":4,"byteLength":8}],
+            "accessors":[{"bufferView":0,"componentType":5125,"count":1,"type":"SCALAR"}]
+        }"#,vec![0;8]);
+        gltf.read_indices(0);
+    }
+
+    #[test]
+    fn tint(){
+        let image=image::RgbaImage::from_pixel(1,1,image::Rgba([255,255,255,255]));
+        let tinted=tint_image(image,&[1.0,0.0,0.2140,0.5]);
+        let pixel=tinted.get_pixel(0,0).0;
+        assert_eq!([pixel[0],pixel[1],pixel[3]],[255,0,128]);
+        //linear 0.214 is about sRGB 0.5
+        assert!((127..=128).contains(&pixel[2]));
+    }
+}
@@ -0,0 +1,37 @@
+use crate::{Mesh,Material};
+
+//per instance vertex data, read through the second vertex binding of the instanced pipelines
+#[repr(C)]
+#[derive(Debug,Clone,Copy)]
+pub struct InstanceData{
+    pub transform:glm::Mat4,//model matrix
+    pub tint:glm::Vec4,//multiplied with texture color
+    pub material_index:u32,//index into InstanceBatch::materials
+}
+impl InstanceData{
+    pub fn new(transform:glm::Mat4,tint:glm::Vec4,material_index:u32)->Self{
+        Self{
+            transform,
+            tint,
+            material_index,
+        }
+    }
+}
+
+//many copies of one mesh, drawn with one instanced draw call per material
+#[derive(Clone)]
+pub struct InstanceBatch{
+    pub mesh:std::sync::Arc<Mesh>,
+    pub materials:Vec<std::sync::Arc<Material>>,
+    pub instances:Vec<InstanceData>,
+}
+
+//range of the instance buffer, written during Painter::draw, that is drawn with a single material
+pub struct InstanceGroup{
+    pub mesh:std::sync::Arc<Mesh>,
+    pub material:std::sync::Arc<Material>,
+    pub first_instance:u32,
+    pub instance_count:u32,
+    //largest projected size of the instances, which selects the level of detail of the whole group
+    pub screen_size:f32,
+}
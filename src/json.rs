@@ -0,0 +1,251 @@
+//minimal json parser, used to read gltf files
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum JsonValue{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String,JsonValue)>),//in file order
+}
+impl JsonValue{
+    pub fn parse(text:&str)->Result<Self,String>{
+        let mut parser=JsonParser{
+            bytes:text.as_bytes(),
+            position:0,
+        };
+        let value=parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position!=parser.bytes.len(){
+            return Err(format!("unexpected trailing characters at {}",parser.position));
+        }
+        Ok(value)
+    }
+
+    //value of key, if this is an object that contains it
+    pub fn get(&self,key:&str)->Option<&JsonValue>{
+        match self{
+            JsonValue::Object(members)=>members.iter().find(|(name,_)| name==key).map(|(_,value)| value),
+            _=>None,
+        }
+    }
+
+    pub fn as_f64(&self)->Option<f64>{
+        match self{
+            JsonValue::Number(number)=>Some(*number),
+            _=>None,
+        }
+    }
+    pub fn as_usize(&self)->Option<usize>{
+        self.as_f64().map(|number| number as usize)
+    }
+    pub fn as_bool(&self)->Option<bool>{
+        match self{
+            JsonValue::Bool(value)=>Some(*value),
+            _=>None,
+        }
+    }
+    pub fn as_str(&self)->Option<&str>{
+        match self{
+            JsonValue::String(string)=>Some(string),
+            _=>None,
+        }
+    }
+    //elements of an array, or nothing if this is not an array
+    pub fn elements(&self)->&[JsonValue]{
+        match self{
+            JsonValue::Array(elements)=>elements,
+            _=>&[],
+        }
+    }
+    pub fn members(&self)->&[(String,JsonValue)]{
+        match self{
+            JsonValue::Object(members)=>members,
+            _=>&[],
+        }
+    }
+}
+
+struct JsonParser<'a>{
+    bytes:&'a [u8],
+    position:usize,
+}
+impl<'a> JsonParser<'a>{
+    fn skip_whitespace(&mut self){
+        while self.position<self.bytes.len() && matches!(self.bytes[self.position],b' ' | b'\t' | b'\n' | b'\r'){
+            self.position+=1;
+        }
+    }
+
+    fn peek(&self)->Option<u8>{
+        self.bytes.get(self.position).copied()
+    }
+
+    fn expect(&mut self,literal:&str)->Result<(),String>{
+        if self.bytes[self.position..].starts_with(literal.as_bytes()){
+            self.position+=literal.len();
+            Ok(())
+        }else{
+            Err(format!("expected {} at {}",literal,self.position))
+        }
+    }
+
+    fn parse_value(&mut self)->Result<JsonValue,String>{
+        self.skip_whitespace();
+        match self.peek(){
+            Some(b'n')=>self.expect("null").map(|_| JsonValue::Null),
+            Some(b't')=>self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f')=>self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'"')=>self.parse_string().map(JsonValue::String),
+            Some(b'[')=>{
+                self.position+=1;
+                let mut elements=Vec::new();
+                self.skip_whitespace();
+                if self.peek()==Some(b']'){
+                    self.position+=1;
+                    return Ok(JsonValue::Array(elements));
+                }
+                loop{
+                    elements.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.peek(){
+                        Some(b',')=>self.position+=1,
+                        Some(b']')=>{
+                            self.position+=1;
+                            return Ok(JsonValue::Array(elements));
+                        },
+                        _=>return Err(format!("expected , or ] at {}",self.position)),
+                    }
+                }
+            },
+            Some(b'{')=>{
+                self.position+=1;
+                let mut members=Vec::new();
+                self.skip_whitespace();
+                if self.peek()==Some(b'}'){
+                    self.position+=1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop{
+                    self.skip_whitespace();
+                    let key=self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    let value=self.parse_value()?;
+                    members.push((key,value));
+                    self.skip_whitespace();
+                    match self.peek(){
+                        Some(b',')=>self.position+=1,
+                        Some(b'}')=>{
+                            self.position+=1;
+                            return Ok(JsonValue::Object(members));
+                        },
+                        _=>return Err(format!("expected , or }} at {}",self.position)),
+                    }
+                }
+            },
+            Some(b'-') | Some(b'0'..=b'9')=>{
+                let start=self.position;
+                while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')=self.peek(){
+                    self.position+=1;
+                }
+                let text=std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+                text.parse::<f64>().map(JsonValue::Number).map_err(|e| format!("invalid number {} at {}: {}",text,start,e))
+            },
+            _=>Err(format!("unexpected character at {}",self.position)),
+        }
+    }
+
+    fn parse_hex4(&mut self)->Result<u32,String>{
+        let hex=self.bytes.get(self.position..self.position+4).ok_or_else(|| String::from("unexpected end of escape sequence"))?;
+        let code=u32::from_str_radix(std::str::from_utf8(hex).map_err(|e| e.to_string())?,16).map_err(|e| e.to_string())?;
+        self.position+=4;
+        Ok(code)
+    }
+
+    fn parse_string(&mut self)->Result<String,String>{
+        self.expect("\"")?;
+        let mut string=Vec::new();
+        loop{
+            match self.peek(){
+                None=>return Err(String::from("unterminated string")),
+                Some(b'"')=>{
+                    self.position+=1;
+                    return String::from_utf8(string).map_err(|e| e.to_string());
+                },
+                Some(b'\\')=>{
+                    self.position+=1;
+                    let escaped=self.peek().ok_or_else(|| String::from("unterminated string"))?;
+                    self.position+=1;
+                    let character=match escaped{
+                        b'"'=>'"',
+                        b'\\'=>'\\',
+                        b'/'=>'/',
+                        b'b'=>'\u{8}',
+                        b'f'=>'\u{c}',
+                        b'n'=>'\n',
+                        b'r'=>'\r',
+                        b't'=>'\t',
+                        b'u'=>{
+                            let mut code=self.parse_hex4()?;
+                            //utf-16 surrogate pair
+                            if (0xd800..0xdc00).contains(&code){
+                                self.expect("\\u")?;
+                                let low=self.parse_hex4()?;
+                                code=0x10000+((code-0xd800)<<10)+(low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            std::char::from_u32(code).unwrap_or('\u{fffd}')
+                        },
+                        _=>return Err(format!("invalid escape sequence at {}",self.position)),
+                    };
+                    let mut buffer=[0;4];
+                    string.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+                },
+                Some(byte)=>{
+                    string.push(byte);
+                    self.position+=1;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn string(text:&str)->String{
+        match JsonValue::parse(text){
+            Ok(JsonValue::String(string))=>string,
+            other=>panic!("{} parsed as {:?}",text,other),
+        }
+    }
+
+    #[test]
+    fn escapes(){
+        assert_eq!(string(r#""a\"b\\c\/d""#),"a\"b\\c/d");
+        assert_eq!(string(r#""\b\f\n\r\t""#),"\u{8}\u{c}\n\r\t");
+        assert_eq!(string(r#""A\u00e9\u20AC""#),"A\u{e9}\u{20ac}");
+        //unescaped utf-8 is kept as is
+        assert_eq!(string("\"é€\""),"\u{e9}\u{20ac}");
+    }
+
+    #[test]
+    fn surrogates(){
+        assert_eq!(string(r#""\ud83d\ude00""#),"\u{1f600}");
+        assert_eq!(string(r#""x\uD834\uDD1Ey""#),"x\u{1d11e}y");
+        //unpaired surrogates become replacement characters
+        assert_eq!(string(r#""\ude00""#),"\u{fffd}");
+    }
+
+    #[test]
+    fn structure(){
+        let value=JsonValue::parse(r#" {"a":[1,-2.5e2,true,null],"b":{"c":"d"}} "#).unwrap();
+        assert_eq!(value.get("a").unwrap().elements(),&[JsonValue::Number(1.0),JsonValue::Number(-250.0),JsonValue::Bool(true),JsonValue::Null]);
+        assert_eq!(value.get("b").and_then(|b| b.get("c")).and_then(|c| c.as_str()),Some("d"));
+        assert!(JsonValue::parse(r#"{"a":1} x"#).is_err());
+        assert!(JsonValue::parse(r#""\x""#).is_err());
+        assert!(JsonValue::parse(r#""abc"#).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+use ash::vk;
+
+use crate::compressed_texture::{TextureData,level_size};
+
+const IDENTIFIER:[u8;12]=[0xab,b'K',b'T',b'X',b' ',b'2',b'0',0xbb,b'\r',b'\n',0x1a,b'\n'];
+
+fn read_u32(bytes:&[u8],offset:usize)->u32{
+    u32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+fn read_u64(bytes:&[u8],offset:usize)->u64{
+    read_u32(bytes,offset) as u64 | (read_u32(bytes,offset+4) as u64)<<32
+}
+
+//ktx2: identifier, header (vkFormat, type size, width, height, depth, layer count, face count, level count, supercompression scheme),
+//index of the data format descriptor, key/value data and supercompression data, then one (offset, length, uncompressed length) entry per level
+//
+//the format is a vulkan format, so the level data can be uploaded as stored.
+//only 2d textures without supercompression are supported.
+pub fn parse(name:&str,bytes:&[u8])->TextureData{
+    if bytes.len()<80 || bytes[..12]!=IDENTIFIER{
+        panic!("{} is not a ktx2 file",name);
+    }
+    let format=vk::Format::from_raw(read_u32(bytes,12) as i32);
+    let width=read_u32(bytes,20);
+    let height=read_u32(bytes,24).max(1);
+    let depth=read_u32(bytes,28);
+    let layer_count=read_u32(bytes,32);
+    let face_count=read_u32(bytes,36);
+    //0 requests mips to be generated after loading, only the stored level is used then
+    let level_count=read_u32(bytes,40).max(1);
+    let supercompression_scheme=read_u32(bytes,44);
+
+    if format==vk::Format::UNDEFINED{
+        panic!("{} uses a universal (basis) format, which is not supported",name);
+    }
+    if supercompression_scheme!=0{
+        panic!("{} uses supercompression scheme {}, which is not supported",name,supercompression_scheme);
+    }
+    if depth>1 || layer_count>1 || face_count!=1{
+        panic!("{} is not a 2d texture (depth {}, {} layers, {} faces)",name,depth,layer_count,face_count);
+    }
+
+    let levels=(0..level_count).map(|level|{
+        let entry=80+level as usize*24;
+        let offset=read_u64(bytes,entry) as usize;
+        let length=read_u64(bytes,entry+8) as usize;
+        let (level_width,level_height)=crate::mipmap::mip_extent(width,height,level);
+        let expected=level_size(format,level_width,level_height);
+        if length!=expected || offset+length>bytes.len(){
+            panic!("level {} of {} has {} bytes at {}, but requires {}",level,name,length,offset,expected);
+        }
+        bytes[offset..offset+length].to_vec()
+    }).collect();
+
+    TextureData{
+        format,
+        width,
+        height,
+        levels,
+    }
+}
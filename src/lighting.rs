@@ -0,0 +1,106 @@
+//maximum number of point lights, must match MAX_POINT_LIGHTS in textured_polygon_3d_lit.frag
+pub const MAX_POINT_LIGHTS:usize=8;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct DirectionalLight{
+    pub direction:glm::Vec3,//direction the light travels in
+    pub color:glm::Vec3,
+    pub intensity:f32,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct PointLight{
+    pub position:glm::Vec3,
+    pub color:glm::Vec3,
+    pub intensity:f32,
+    pub range:f32,//light has no effect on anything farther away than this
+}
+
+//lights used by the lit pipelines, uploaded once per frame
+#[derive(Debug,Clone,PartialEq)]
+pub struct Lights{
+    pub ambient:glm::Vec3,
+    pub directional:Option<DirectionalLight>,
+    point_lights:Vec<PointLight>,
+}
+impl Lights{
+    pub fn new()->Self{
+        Self{
+            ambient:glm::vec3(0.1,0.1,0.1),
+            directional:Some(DirectionalLight{
+                direction:glm::vec3(-1.0,-1.0,-1.0),
+                color:glm::vec3(1.0,1.0,1.0),
+                intensity:1.0,
+            }),
+            point_lights:Vec::new(),
+        }
+    }
+
+    //returns index of the new light, or None if there are already MAX_POINT_LIGHTS point lights
+    pub fn add_point_light(&mut self,point_light:PointLight)->Option<usize>{
+        if self.point_lights.len()>=MAX_POINT_LIGHTS{
+            return None;
+        }
+        self.point_lights.push(point_light);
+        Some(self.point_lights.len()-1)
+    }
+    pub fn remove_point_light(&mut self,index:usize)->PointLight{
+        self.point_lights.remove(index)
+    }
+    pub fn point_lights(&self)->&[PointLight]{
+        &self.point_lights
+    }
+    pub fn point_lights_mut(&mut self)->&mut [PointLight]{
+        &mut self.point_lights
+    }
+
+    pub fn uniform(&self,camera_position:&glm::Vec3)->LightsUniform{
+        let mut uniform=LightsUniform{
+            ambient:glm::vec4(self.ambient.x,self.ambient.y,self.ambient.z,1.0),
+            directional_direction:glm::vec4(0.0,0.0,0.0,0.0),
+            directional_color_intensity:glm::vec4(0.0,0.0,0.0,0.0),
+            camera_position:glm::vec4(camera_position.x,camera_position.y,camera_position.z,1.0),
+            point_lights:[PointLightUniform{
+                position_range:glm::vec4(0.0,0.0,0.0,0.0),
+                color_intensity:glm::vec4(0.0,0.0,0.0,0.0),
+            };MAX_POINT_LIGHTS],
+            point_light_count:self.point_lights.len() as u32,
+            _padding:[0;3],
+        };
+        if let Some(directional)=&self.directional{
+            uniform.directional_direction=glm::vec4(directional.direction.x,directional.direction.y,directional.direction.z,1.0);
+            uniform.directional_color_intensity=glm::vec4(directional.color.x,directional.color.y,directional.color.z,directional.intensity);
+        }
+        for (uniform_light,light) in uniform.point_lights.iter_mut().zip(self.point_lights.iter()){
+            *uniform_light=PointLightUniform{
+                position_range:glm::vec4(light.position.x,light.position.y,light.position.z,light.range),
+                color_intensity:glm::vec4(light.color.x,light.color.y,light.color.z,light.intensity),
+            };
+        }
+        uniform
+    }
+}
+impl Default for Lights{
+    fn default()->Self{
+        Self::new()
+    }
+}
+
+//std140 layout of the Lights uniform block in textured_polygon_3d_lit.frag
+#[repr(C)]
+#[derive(Debug,Clone,Copy)]
+pub struct PointLightUniform{
+    pub position_range:glm::Vec4,
+    pub color_intensity:glm::Vec4,
+}
+#[repr(C)]
+#[derive(Debug,Clone,Copy)]
+pub struct LightsUniform{
+    pub ambient:glm::Vec4,
+    pub directional_direction:glm::Vec4,//w is 1 if the directional light is enabled
+    pub directional_color_intensity:glm::Vec4,
+    pub camera_position:glm::Vec4,
+    pub point_lights:[PointLightUniform;MAX_POINT_LIGHTS],
+    pub point_light_count:u32,
+    pub _padding:[u32;3],
+}
@@ -0,0 +1,308 @@
+//mesh simplification with quadric error metrics, used to generate levels of detail that share the vertices of the full mesh
+
+use crate::decoder::VertexIndices;
+use crate::mesh_processing;
+
+//number of levels generated for a mesh, including the full mesh
+pub const LOD_COUNT:usize=4;
+//each level has at most this fraction of the triangles of the previous level
+pub const LOD_TRIANGLE_RATIO:f32=0.25;
+//projected size (bounding sphere diameter over screen height) below which level 1 is used, halved for each further level
+pub const LOD_SCREEN_SIZE:f32=0.4;
+//meshes with fewer triangles are not simplified
+pub const LOD_MIN_TRIANGLES:usize=256;
+
+//range of the mesh index buffer drawn for a level of detail
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct MeshLod{
+    pub first_index:u32,
+    pub index_count:u32,
+    pub screen_size:f32,//level is used while the projected size of the mesh is at least this
+}
+
+//symmetric 4x4 matrix, stored as its upper triangle
+#[derive(Debug,Clone,Copy,Default)]
+struct Quadric([f64;10]);
+impl Quadric{
+    //squared distance to the plane through the triangle, weighted by triangle area
+    fn from_triangle(p0:&glm::Vec3,p1:&glm::Vec3,p2:&glm::Vec3)->Self{
+        let cross=glm::cross(&(p1-p0),&(p2-p0));
+        let length=glm::length(&cross);
+        if length<=0.0{
+            return Self::default();
+        }
+        let normal=cross/length;
+        let (a,b,c)=(normal.x as f64,normal.y as f64,normal.z as f64);
+        let d=-(a*p0.x as f64+b*p0.y as f64+c*p0.z as f64);
+        let area=length as f64*0.5;
+        Self([a*a,a*b,a*c,a*d,b*b,b*c,b*d,c*c,c*d,d*d].map(|value| value*area))
+    }
+
+    fn add(&mut self,other:&Self){
+        for (value,other) in self.0.iter_mut().zip(other.0.iter()){
+            *value+=other;
+        }
+    }
+
+    fn error(&self,point:&glm::Vec3)->f64{
+        let [a2,ab,ac,ad,b2,bc,bd,c2,cd,d2]=self.0;
+        let (x,y,z)=(point.x as f64,point.y as f64,point.z as f64);
+        x*x*a2+2.0*x*y*ab+2.0*x*z*ac+2.0*x*ad
+            +y*y*b2+2.0*y*z*bc+2.0*y*bd
+            +z*z*c2+2.0*z*cd
+            +d2
+    }
+}
+
+//collapse of one vertex into a neighbour, ordered by lowest cost first
+#[derive(Debug,Clone,Copy)]
+struct Collapse{
+    cost:f64,
+    from:u32,
+    to:u32,
+    version:u32,//collapses computed for an older version of the vertex are ignored
+}
+impl PartialEq for Collapse{
+    fn eq(&self,other:&Self)->bool{
+        self.cmp(other)==std::cmp::Ordering::Equal
+    }
+}
+impl Eq for Collapse{}
+impl PartialOrd for Collapse{
+    fn partial_cmp(&self,other:&Self)->Option<std::cmp::Ordering>{
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Collapse{
+    fn cmp(&self,other:&Self)->std::cmp::Ordering{
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier<'a>{
+    positions:&'a [glm::Vec3],
+    triangles:Vec<[u32;3]>,
+    removed:Vec<bool>,
+    vertex_triangles:Vec<Vec<usize>>,
+    quadrics:Vec<Quadric>,
+    //vertices on open edges (borders of the mesh and attribute seams) are never moved, so that no cracks open up
+    locked:Vec<bool>,
+    versions:Vec<u32>,
+}
+impl<'a> Simplifier<'a>{
+    fn new(positions:&'a [glm::Vec3],vertex_indices:&[VertexIndices])->Self{
+        let triangles:Vec<[u32;3]>=vertex_indices.iter().map(|triangle| triangle.corners()).collect();
+        let mut vertex_triangles=vec![Vec::new();positions.len()];
+        let mut quadrics=vec![Quadric::default();positions.len()];
+        let mut edges:std::collections::HashMap<(u32,u32),u32>=std::collections::HashMap::new();
+        for (triangle_index,triangle) in triangles.iter().enumerate(){
+            let quadric=Quadric::from_triangle(&positions[triangle[0] as usize],&positions[triangle[1] as usize],&positions[triangle[2] as usize]);
+            for corner in 0..3{
+                let (a,b)=(triangle[corner],triangle[(corner+1)%3]);
+                vertex_triangles[a as usize].push(triangle_index);
+                quadrics[a as usize].add(&quadric);
+                *edges.entry((a.min(b),a.max(b))).or_insert(0)+=1;
+            }
+        }
+        let mut locked=vec![false;positions.len()];
+        for ((a,b),count) in edges.iter(){
+            if *count!=2{
+                locked[*a as usize]=true;
+                locked[*b as usize]=true;
+            }
+        }
+        Self{
+            positions,
+            removed:vec![false;triangles.len()],
+            triangles,
+            vertex_triangles,
+            quadrics,
+            locked,
+            versions:vec![0;positions.len()],
+        }
+    }
+
+    fn neighbours(&self,vertex:u32)->Vec<u32>{
+        let mut neighbours:Vec<u32>=self.vertex_triangles[vertex as usize].iter().flat_map(|t| self.triangles[*t].iter().copied()).filter(|v| *v!=vertex).collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    //cheapest collapse of the vertex into a neighbour
+    fn best_collapse(&self,from:u32)->Option<Collapse>{
+        if self.locked[from as usize]{
+            return None;
+        }
+        let quadric=self.quadrics[from as usize];
+        self.neighbours(from).into_iter().map(|to|{
+            let mut combined=quadric;
+            combined.add(&self.quadrics[to as usize]);
+            Collapse{
+                cost:combined.error(&self.positions[to as usize]),
+                from,
+                to,
+                version:self.versions[from as usize],
+            }
+        }).min_by(|a,b| a.cost.total_cmp(&b.cost))
+    }
+
+    //triangles that would remain after the collapse must not flip or degenerate
+    fn is_valid(&self,collapse:&Collapse)->bool{
+        self.vertex_triangles[collapse.from as usize].iter().all(|t|{
+            let triangle=self.triangles[*t];
+            if triangle.contains(&collapse.to){
+                return true;
+            }
+            let [p0,p1,p2]=triangle.map(|v| self.positions[v as usize]);
+            let [q0,q1,q2]=triangle.map(|v| self.positions[if v==collapse.from{collapse.to}else{v} as usize]);
+            let before=glm::cross(&(p1-p0),&(p2-p0));
+            let after=glm::cross(&(q1-q0),&(q2-q0));
+            glm::dot(&before,&after)>0.0
+        })
+    }
+
+    fn apply(&mut self,collapse:&Collapse)->usize{
+        let (from,to)=(collapse.from,collapse.to);
+        let mut removed_triangles=0;
+        for t in std::mem::take(&mut self.vertex_triangles[from as usize]){
+            if self.triangles[t].contains(&to){
+                self.removed[t]=true;
+                removed_triangles+=1;
+                for vertex in self.triangles[t].iter(){
+                    self.vertex_triangles[*vertex as usize].retain(|other| *other!=t);
+                }
+            }else{
+                for vertex in self.triangles[t].iter_mut(){
+                    if *vertex==from{
+                        *vertex=to;
+                    }
+                }
+                self.vertex_triangles[to as usize].push(t);
+            }
+        }
+        let quadric=self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        removed_triangles
+    }
+}
+
+//remove triangles by collapsing edges until at most target_triangles remain, or no further collapse is possible.
+//vertex positions are not changed, so the result indexes the same vertices as the input.
+pub fn simplify(positions:&[glm::Vec3],vertex_indices:&[VertexIndices],target_triangles:usize)->Vec<VertexIndices>{
+    let mut simplifier=Simplifier::new(positions,vertex_indices);
+    let mut heap:std::collections::BinaryHeap<Collapse>=(0..positions.len() as u32).filter_map(|vertex| simplifier.best_collapse(vertex)).collect();
+
+    let mut triangle_count=vertex_indices.len();
+    while triangle_count>target_triangles{
+        let collapse=match heap.pop(){
+            Some(collapse)=>collapse,
+            None=>break,
+        };
+        if collapse.version!=simplifier.versions[collapse.from as usize] || simplifier.vertex_triangles[collapse.from as usize].is_empty(){
+            continue;
+        }
+        if !simplifier.is_valid(&collapse){
+            //retried when one of the neighbours changes
+            simplifier.versions[collapse.from as usize]+=1;
+            continue;
+        }
+
+        let neighbours=simplifier.neighbours(collapse.from);
+        triangle_count-=simplifier.apply(&collapse);
+        simplifier.versions[collapse.from as usize]+=1;
+        for vertex in neighbours{
+            simplifier.versions[vertex as usize]+=1;
+            if let Some(collapse)=simplifier.best_collapse(vertex){
+                heap.push(collapse);
+            }
+        }
+    }
+
+    simplifier.triangles.iter().zip(simplifier.removed.iter()).filter(|(_,removed)| !**removed).map(|(triangle,_)| VertexIndices::new(triangle[0],triangle[1],triangle[2])).collect()
+}
+
+//index lists of all levels of detail, starting with the full mesh.
+//levels that would barely reduce the triangle count are not generated.
+pub fn generate_lods(positions:&[glm::Vec3],vertex_indices:&[VertexIndices])->Vec<Vec<VertexIndices>>{
+    let mut lods=vec![vertex_indices.to_vec()];
+    if vertex_indices.len()<LOD_MIN_TRIANGLES{
+        return lods;
+    }
+    while lods.len()<LOD_COUNT{
+        let previous=lods.last().unwrap();
+        let target=(previous.len() as f32*LOD_TRIANGLE_RATIO) as usize;
+        let mut lod=simplify(positions,previous,target);
+        if lod.is_empty() || lod.len() as f32>previous.len() as f32*0.8{
+            break;
+        }
+        mesh_processing::optimize_vertex_cache(&mut lod,positions.len());
+        lods.push(lod);
+    }
+    lods
+}
+
+//screen size threshold of a level of detail, the last level is used for any size
+pub fn lod_screen_size(level:usize,level_count:usize)->f32{
+    if level+1>=level_count{
+        0.0
+    }else{
+        LOD_SCREEN_SIZE*0.5f32.powi(level as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //height field of size x size quads, slightly curved so that simplification has some error to weigh
+    fn grid(size:u32)->(Vec<glm::Vec3>,Vec<VertexIndices>){
+        let positions=(0..=size).flat_map(|y| (0..=size).map(move |x| glm::vec3(x as f32,y as f32,((x as f32*0.3).sin()+(y as f32*0.2).cos())*0.5))).collect();
+        let vertex=|x:u32,y:u32| y*(size+1)+x;
+        let vertex_indices=(0..size).flat_map(|y| (0..size).flat_map(move |x| [
+            VertexIndices::new(vertex(x,y),vertex(x+1,y),vertex(x+1,y+1)),
+            VertexIndices::new(vertex(x,y),vertex(x+1,y+1),vertex(x,y+1)),
+        ])).collect();
+        (positions,vertex_indices)
+    }
+
+    fn corners(vertex_indices:&[VertexIndices])->Vec<[u32;3]>{
+        vertex_indices.iter().map(|triangle| triangle.corners()).collect()
+    }
+
+    #[test]
+    fn levels_shrink(){
+        let (positions,vertex_indices)=grid(24);
+        let lods=generate_lods(&positions,&vertex_indices);
+        assert!(lods.len()>1 && lods.len()<=LOD_COUNT);
+        assert_eq!(corners(&lods[0]),corners(&vertex_indices));
+        for pair in lods.windows(2){
+            assert!(!pair[1].is_empty());
+            assert!(pair[1].len() as f32<=pair[0].len() as f32*0.8,"{} -> {} triangles",pair[0].len(),pair[1].len());
+        }
+        for lod in lods.iter(){
+            for triangle in lod.iter(){
+                let [a,b,c]=triangle.corners();
+                assert!((a as usize)<positions.len() && (b as usize)<positions.len() && (c as usize)<positions.len());
+                assert!(a!=b && b!=c && a!=c);
+            }
+        }
+    }
+
+    #[test]
+    fn small_meshes_are_kept(){
+        let (positions,vertex_indices)=grid(8);
+        assert!(vertex_indices.len()<LOD_MIN_TRIANGLES);
+        let lods=generate_lods(&positions,&vertex_indices);
+        assert_eq!(lods.len(),1);
+        assert_eq!(corners(&lods[0]),corners(&vertex_indices));
+    }
+
+    #[test]
+    fn screen_sizes(){
+        assert_eq!(lod_screen_size(0,1),0.0);
+        assert_eq!(lod_screen_size(0,3),LOD_SCREEN_SIZE);
+        assert_eq!(lod_screen_size(1,3),LOD_SCREEN_SIZE*0.5);
+        assert_eq!(lod_screen_size(2,3),0.0);
+    }
+}
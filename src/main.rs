@@ -15,6 +15,9 @@ extern crate image;
 extern crate ash;
 extern crate nalgebra_glm as glm;
 extern crate wavefront_obj as obj;
+extern crate dyra_derive;
+//lets code generated by #[derive(VertexLayout)] refer to this crate as ::dyra, like it does in other crates
+extern crate self as dyra;
 
 #[cfg(target_os="windows")]
 use winapi::{
@@ -98,15 +101,84 @@ pub use test_window::{TestWindow,TestWindowHandle};
 pub mod window;
 pub use window::{Window,WindowHandle};
 
+pub mod vertex_layout;
+pub use vertex_layout::{VertexLayout,VertexLayoutDescription,VertexAttribute,PositionVertex,ColorVertex,SpriteVertex};
+
 pub mod decoder;
 pub use decoder::{Decoder,Vertex,IntegratedBuffer,Mesh,Image};
 
+pub mod model;
+pub use model::{Model,Submesh,ModelMaterial};
+
+pub mod mesh_processing;
+pub use mesh_processing::{weld_vertices,generate_normals,generate_tangents,optimize_vertex_cache};
+
+pub mod color;
+pub use color::{TextureUsage};
+
+pub mod mipmap;
+
+pub mod compressed_texture;
+pub use compressed_texture::{TextureData};
+pub mod bcn;
+pub mod ktx2;
+pub mod dds;
+pub mod float_image;
+pub use float_image::{FloatImage};
+pub mod exr;
+pub mod cubemap;
+pub use cubemap::{CubemapLayout};
+pub mod atlas;
+pub use atlas::{AtlasBuilder,Atlas,AtlasRegion,Sprite};
+
+pub mod dynamic_mesh;
+pub use dynamic_mesh::{DynamicMesh};
+
+pub mod primitives;
+pub use primitives::{Primitive};
+
+pub mod lod;
+pub use lod::{MeshLod};
+
+pub mod json;
+pub mod gltf;
+pub mod stl;
+pub mod ply;
+
 pub mod painter;
 pub use painter::{Painter};
 
+pub mod render_target;
+pub use render_target::{Attachment,TransientAttachments,RenderTarget};
+
+pub mod sampler;
+pub use sampler::{SamplerDescription,SamplerCache};
+
+pub mod material;
+pub use material::{Material,BlendMode,Shading};
+
+pub mod camera;
+pub use camera::{Camera};
+
+pub mod instancing;
+pub use instancing::{InstanceData,InstanceBatch};
+
+pub mod bounds;
+pub use bounds::{BoundingBox,BoundingSphere,Frustum};
+
+pub mod scene;
+pub use scene::{Scene,Node,NodeId};
+
+pub mod render_queue;
+pub use render_queue::{RenderQueue,DrawItem,FrameStatistics};
+
+pub mod lighting;
+pub use lighting::{Lights,DirectionalLight,PointLight};
+
 pub struct Object{
     pub mesh:std::sync::Arc<Mesh>,
-    pub texture:std::sync::Arc<Image>,
+    pub material:std::sync::Arc<Material>,
+    pub transform:glm::Mat4,//model matrix
 }
 pub struct GraphicsPipeline{
     layout:vk::PipelineLayout,
@@ -258,6 +330,10 @@ struct Manager{
 
     swapchain_surface_format:vk::SurfaceFormatKHR,
 
+    offscreen_targets:Vec<RenderTarget>,
+
+    scene:Scene,
+
     painter:std::mem::ManuallyDrop<Painter>,
     decoder:std::mem::ManuallyDrop<Decoder>,
 }
@@ -266,7 +342,8 @@ impl Manager{
         self.allocation_callbacks.as_ref()
     }
 
-    pub fn new()->Self{
+    //msaa_samples is the requested number of samples per pixel (1, 2, 4 or 8), clamped to what the device supports
+    pub fn new(msaa_samples:u32)->Self{
         let window_manager_handle=WindowManagerHandle::new();
         let open_windows=Vec::new();
 
@@ -480,7 +557,20 @@ impl Manager{
             );
         }
 
+        //anisotropic filtering is used by samplers if available
+        let physical_device_features=unsafe{
+            instance.get_physical_device_features(physical_device)
+        };
+        let enabled_features=vk::PhysicalDeviceFeatures{
+            sampler_anisotropy:physical_device_features.sampler_anisotropy,
+            //compressed textures are uploaded as stored when the device supports their format, and decompressed otherwise
+            texture_compression_bc:physical_device_features.texture_compression_bc,
+            texture_compression_etc2:physical_device_features.texture_compression_etc2,
+            texture_compression_astc_ldr:physical_device_features.texture_compression_astc_ldr,
+            ..Default::default()
+        };
         let device_create_info=vk::DeviceCreateInfo{
+            p_enabled_features:&enabled_features,
             queue_create_info_count:merged_queue_create_infos.len() as u32,
             p_queue_create_infos:merged_queue_create_infos.as_ptr(),
             enabled_layer_count:device_layer_names.len() as u32,
@@ -573,98 +663,38 @@ impl Manager{
         let surface_formats=unsafe{
             surface.get_physical_device_surface_formats(physical_device, test_window.platform_surface)
         }.unwrap();
-        //use first available format, but check for two 'better' alternatives
-        let mut swapchain_surface_format=surface_formats[0];
-        //if the only supported format is 'undefined', there is no preferred format for the surface
-        //then use 'most widely used' format
-        if surface_formats.len()==1 && swapchain_surface_format.format==vk::Format::UNDEFINED{
-            swapchain_surface_format=vk::SurfaceFormatKHR{
-                format:vk::Format::R8G8B8A8_UNORM,
-                color_space:vk::ColorSpaceKHR::SRGB_NONLINEAR,
-            };
-        }else{
-            for format in surface_formats.iter(){
-                if format.format==vk::Format::R8G8B8A8_UNORM{
-                    swapchain_surface_format=*format;
-                }
-            }
-        }
+        let swapchain_surface_format=color::choose_surface_format(&surface_formats);
 
         //Painter related stuff
         let painter;
         {
-            let render_pass={
-                let render_pass_attachment_descriptions=vec![
-                    vk::AttachmentDescription{
-                        format:swapchain_surface_format.format,
-                        samples:vk::SampleCountFlags::TYPE_1,
-                        load_op:vk::AttachmentLoadOp::CLEAR,
-                        store_op:vk::AttachmentStoreOp::STORE,
-                        initial_layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                        final_layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                        ..Default::default()
-                    }
-                ];
-                let render_pass_subpass_color_attachment_references=vec![
-                    vk::AttachmentReference{
-                        attachment:0,
-                        layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-                    }
-                ];
-                let render_pass_subpass_descriptions=vec![
-                    vk::SubpassDescription{
-                        pipeline_bind_point:vk::PipelineBindPoint::GRAPHICS,
-                        color_attachment_count:render_pass_subpass_color_attachment_references.len() as u32,
-                        p_color_attachments:render_pass_subpass_color_attachment_references.as_ptr(),
-                        ..Default::default()
+            let descriptor_set_layout={
+                let descriptor_set_layout_bindings=vec![
+                    vk::DescriptorSetLayoutBinding{
+                        binding:0,
+                        descriptor_type:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,//combine image and sampler into a single descriptor
+                        descriptor_count:1,
+                        stage_flags:vk::ShaderStageFlags::FRAGMENT,
+                        p_immutable_samplers:std::ptr::null(),
                     }
                 ];
-                let render_pass_subpass_dependencies=vec![
-
-                ];
-                let render_pass_create_info=vk::RenderPassCreateInfo{
-                    attachment_count:render_pass_attachment_descriptions.len() as u32,
-                    p_attachments:render_pass_attachment_descriptions.as_ptr(),
-                    subpass_count:render_pass_subpass_descriptions.len() as u32,
-                    p_subpasses:render_pass_subpass_descriptions.as_ptr(),
-                    dependency_count:render_pass_subpass_dependencies.len() as u32,
-                    p_dependencies:render_pass_subpass_dependencies.as_ptr(),
-                    ..Default::default()
-                };
-
-                unsafe{
-                    device.create_render_pass(&render_pass_create_info,temp_allocation_callbacks)
-                }.unwrap()
-            };
-
-            let sampler={
-                let sampler_create_info=vk::SamplerCreateInfo{
-                    mag_filter:vk::Filter::LINEAR,
-                    min_filter:vk::Filter::LINEAR,
-                    mipmap_mode:vk::SamplerMipmapMode::NEAREST,
-                    address_mode_u:vk::SamplerAddressMode::CLAMP_TO_EDGE,
-                    address_mode_v:vk::SamplerAddressMode::CLAMP_TO_EDGE,
-                    address_mode_w:vk::SamplerAddressMode::CLAMP_TO_EDGE,
-                    mip_lod_bias:0.0,
-                    anisotropy_enable:false as u32,
-                    compare_enable:false as u32,
-                    min_lod:0.0,
-                    max_lod:0.0,
-                    border_color:vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
-                    unnormalized_coordinates:false as u32,
+                let descriptor_set_layout_create_info=vk::DescriptorSetLayoutCreateInfo{
+                    binding_count:descriptor_set_layout_bindings.len() as u32,
+                    p_bindings:descriptor_set_layout_bindings.as_ptr(),
                     ..Default::default()
                 };
 
                 unsafe{
-                    device.create_sampler(&sampler_create_info,temp_allocation_callbacks)
+                    device.create_descriptor_set_layout(&descriptor_set_layout_create_info,temp_allocation_callbacks)
                 }.unwrap()
             };
 
-            let descriptor_set_layout={
+            //lights uniform buffer, used by the lit pipelines as descriptor set 1
+            let lights_descriptor_set_layout={
                 let descriptor_set_layout_bindings=vec![
                     vk::DescriptorSetLayoutBinding{
                         binding:0,
-                        descriptor_type:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,//combine image and sampler into a single descriptor
+                        descriptor_type:vk::DescriptorType::UNIFORM_BUFFER,
                         descriptor_count:1,
                         stage_flags:vk::ShaderStageFlags::FRAGMENT,
                         p_immutable_samplers:std::ptr::null(),
@@ -682,11 +712,12 @@ impl Manager{
             };
 
             let descriptor_pool={
+                //one descriptor set for the lights (materials have pools of their own, see Painter::get_material)
                 let descriptor_pool_sizes=vec![
                     vk::DescriptorPoolSize{
-                        ty:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        ty:vk::DescriptorType::UNIFORM_BUFFER,
                         descriptor_count:1,
-                    }
+                    },
                 ];
                 let descriptor_pool_create_info=vk::DescriptorPoolCreateInfo{
                     max_sets:1,
@@ -703,20 +734,6 @@ impl Manager{
             let descriptor_set_layouts=vec![
                 descriptor_set_layout
             ];
-            let descriptor_sets={
-                let descriptor_set_allocate_info=vk::DescriptorSetAllocateInfo{
-                    descriptor_pool,
-                    descriptor_set_count:descriptor_set_layouts.len() as u32,
-                    p_set_layouts:descriptor_set_layouts.as_ptr(),
-                    ..Default::default()
-                };
-
-                unsafe{
-                    device.allocate_descriptor_sets(&descriptor_set_allocate_info)
-                }.unwrap()
-            };
-            let descriptor_set=descriptor_sets[0];
-
             let graphics_pipeline_layout_2d={
                 let graphics_pipeline_layout_create_info=vk::PipelineLayoutCreateInfo{
                     //descriptor set layouts
@@ -800,193 +817,332 @@ impl Manager{
                 }
             };
 
-            let graphics_pipelines={
-                let shader_entry_fn_name="main\0".as_ptr() as *const i8;
-
-                let shader_stage_create_infos_2d=vec![
-                    vk::PipelineShaderStageCreateInfo{
-                        stage:vk::ShaderStageFlags::VERTEX,
-                        module:vertex_2d,
-                        p_name:shader_entry_fn_name,
-                        ..Default::default()
-                    },
-                    vk::PipelineShaderStageCreateInfo{
-                        stage:vk::ShaderStageFlags::FRAGMENT,
-                        module:fragment_2d,
-                        p_name:shader_entry_fn_name,
-                        ..Default::default()
-                    }
-                ];
-                let vertex_binding_descriptions=vec![
-                    vk::VertexInputBindingDescription{
-                        binding: 0,
-                        stride: std::mem::size_of::<Vertex>() as u32,
-                        input_rate:vk::VertexInputRate::VERTEX,
-                    },
-                ];
-                let vertex_attribute_descriptions=vec![
-                    vk::VertexInputAttributeDescription{
-                        location:0,
-                        binding:vertex_binding_descriptions[0].binding,
-                        format:vk::Format::R32G32B32A32_SFLOAT,
-                        offset:offset_of!(Vertex,x) as u32,
-                    },
-                    vk::VertexInputAttributeDescription{
-                        location:1,
-                        binding:vertex_binding_descriptions[0].binding,
-                        format:vk::Format::R32G32_SFLOAT,
-                        offset:offset_of!(Vertex,u) as u32,
+            //instanced pipelines use the same push constants as the 3d pipelines, but read model matrices from instance data
+            let graphics_pipeline_layout_3d_instanced={
+                let push_constants=vec![
+                    vk::PushConstantRange{
+                        stage_flags:vk::ShaderStageFlags::VERTEX,
+                        offset:0,
+                        size:(std::mem::size_of::<glm::Mat4>()*3) as u32,
                     },
                 ];
-                let vertex_input_state_create_info=vk::PipelineVertexInputStateCreateInfo{
-                    vertex_binding_description_count:vertex_binding_descriptions.len() as u32,
-                    p_vertex_binding_descriptions:vertex_binding_descriptions.as_ptr(),
-                    vertex_attribute_description_count:vertex_attribute_descriptions.len() as u32,
-                    p_vertex_attribute_descriptions:vertex_attribute_descriptions.as_ptr(),
+                let graphics_pipeline_layout_create_info=vk::PipelineLayoutCreateInfo{
+                    //descriptor set layouts
+                    set_layout_count:descriptor_set_layouts.len() as u32,
+                    p_set_layouts:descriptor_set_layouts.as_ptr(),
+                    //push constant ranges
+                    push_constant_range_count:push_constants.len() as u32,
+                    p_push_constant_ranges:push_constants.as_ptr(),
                     ..Default::default()
                 };
-                let input_assembly_state_create_info=vk::PipelineInputAssemblyStateCreateInfo{
-                    //topology:vk::PrimitiveTopology::TRIANGLE_LIST,
-                    topology:vk::PrimitiveTopology::TRIANGLE_LIST,
-                    primitive_restart_enable:false as u32,
+
+                unsafe{
+                    device.create_pipeline_layout(&graphics_pipeline_layout_create_info,temp_allocation_callbacks)
+                }.unwrap()
+            };
+
+            let (vertex_3d_instanced,fragment_3d_instanced)={
+                let vertex_shader_code=std::fs::read("textured_polygon_3d_instanced.vert.spv").unwrap();
+                let vertex_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:vertex_shader_code.len(), //size in bytes
+                    p_code:vertex_shader_code.as_ptr() as *const u32,//but pointer to 4byte unsigned integers
                     ..Default::default()
                 };
-                let viewport_state_create_info=vk::PipelineViewportStateCreateInfo{
-                    viewport_count:1,
-                    //p_viewports:&viewport,//this is ignored if viewports are dynamic
-                    scissor_count:1,//must match viewport count
-                    //p_scissors:&scissor,//ignored if scissors are dynamic
+
+                let fragment_shader_code=std::fs::read("textured_polygon_3d_instanced.frag.spv").unwrap();
+                let fragment_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:fragment_shader_code.len(),
+                    p_code:fragment_shader_code.as_ptr() as *const u32,
                     ..Default::default()
                 };
-                let rasterization_state_create_info=vk::PipelineRasterizationStateCreateInfo{
-                    depth_clamp_enable:false as u32,
-                    rasterizer_discard_enable:false as u32,
-                    polygon_mode:vk::PolygonMode::FILL,
-                    cull_mode: vk::CullModeFlags::BACK,
-                    front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-                    depth_bias_enable:false as u32,
-                    line_width:1.0,//specs state this must be 1.0 if wide lines feature is not enabled
+                
+                unsafe{
+                    (
+                        device.create_shader_module(&vertex_shader_create_info,temp_allocation_callbacks).unwrap(),
+                        device.create_shader_module(&fragment_shader_create_info,temp_allocation_callbacks).unwrap(),
+                    )
+                }
+            };
+
+            //lit pipelines bind the material as set 0 and the lights as set 1
+            let graphics_pipeline_layout_3d_lit={
+                let descriptor_set_layouts=vec![
+                    descriptor_set_layout,
+                    lights_descriptor_set_layout,
+                ];
+                let push_constants=vec![
+                    vk::PushConstantRange{
+                        stage_flags:vk::ShaderStageFlags::VERTEX,
+                        offset:0,
+                        size:(std::mem::size_of::<glm::Mat4>()*3) as u32,
+                    },
+                ];
+                let graphics_pipeline_layout_create_info=vk::PipelineLayoutCreateInfo{
+                    //descriptor set layouts
+                    set_layout_count:descriptor_set_layouts.len() as u32,
+                    p_set_layouts:descriptor_set_layouts.as_ptr(),
+                    //push constant ranges
+                    push_constant_range_count:push_constants.len() as u32,
+                    p_push_constant_ranges:push_constants.as_ptr(),
                     ..Default::default()
                 };
-                let multisample_state_create_info=vk::PipelineMultisampleStateCreateInfo{
-                    rasterization_samples:vk::SampleCountFlags::TYPE_1,
-                    sample_shading_enable:false as u32,
-                    alpha_to_coverage_enable:false as u32,
-                    alpha_to_one_enable:false as u32,
+
+                unsafe{
+                    device.create_pipeline_layout(&graphics_pipeline_layout_create_info,temp_allocation_callbacks)
+                }.unwrap()
+            };
+
+            let (vertex_3d_lit,fragment_3d_lit)={
+                let vertex_shader_code=std::fs::read("textured_polygon_3d_lit.vert.spv").unwrap();
+                let vertex_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:vertex_shader_code.len(), //size in bytes
+                    p_code:vertex_shader_code.as_ptr() as *const u32,//but pointer to 4byte unsigned integers
                     ..Default::default()
                 };
-                let color_blend_attachment_state=vk::PipelineColorBlendAttachmentState{
-                    blend_enable:false as u32,
-                    //src_color_blend_factor:vk::BlendFactor::ONE,
-                    //dst_color_blend_factor:vk::BlendFactor::ZERO,
-                    //color_blend_op:vk::BlendOp::ADD,
-                    //src_alpha_blend_factor:vk::BlendFactor::ONE,
-                    //dst_alpha_blend_factor:vk::BlendFactor::ZERO,
-                    //alpha_blend_op:vk::BlendOp::ADD,
-                    color_write_mask:vk::ColorComponentFlags::all(),//disable blending, but which color channels are forwarded still needs to be specified
+
+                let fragment_shader_code=std::fs::read("textured_polygon_3d_lit.frag.spv").unwrap();
+                let fragment_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:fragment_shader_code.len(),
+                    p_code:fragment_shader_code.as_ptr() as *const u32,
                     ..Default::default()
                 };
-                let color_blend_state=vk::PipelineColorBlendStateCreateInfo{
-                    logic_op_enable:false as u32,
-                    logic_op:vk::LogicOp::COPY,
-                    attachment_count:1,
-                    p_attachments:&color_blend_attachment_state,
-                    blend_constants:[0.0,0.0,0.0,0.0,],
+                
+                unsafe{
+                    (
+                        device.create_shader_module(&vertex_shader_create_info,temp_allocation_callbacks).unwrap(),
+                        device.create_shader_module(&fragment_shader_create_info,temp_allocation_callbacks).unwrap(),
+                    )
+                }
+            };
+
+            //the skybox uses the same push constants as the 3d pipelines, and binds its cubemap as set 0
+            let graphics_pipeline_layout_skybox={
+                let push_constants=[
+                    vk::PushConstantRange{
+                        stage_flags:vk::ShaderStageFlags::VERTEX,
+                        offset:0,
+                        size:(std::mem::size_of::<glm::Mat4>()*3) as u32,
+                    },
+                ];
+                let graphics_pipeline_layout_create_info=vk::PipelineLayoutCreateInfo{
+                    //descriptor set layouts
+                    set_layout_count:descriptor_set_layouts.len() as u32,
+                    p_set_layouts:descriptor_set_layouts.as_ptr(),
+                    //push constant ranges
+                    push_constant_range_count:push_constants.len() as u32,
+                    p_push_constant_ranges:push_constants.as_ptr(),
                     ..Default::default()
                 };
-                let dynamic_states=vec![
-                    vk::DynamicState::VIEWPORT,
-                    vk::DynamicState::SCISSOR,
-                ];
-                let dynamic_state_create_info=vk::PipelineDynamicStateCreateInfo{
-                    dynamic_state_count:dynamic_states.len() as u32,
-                    p_dynamic_states:dynamic_states.as_ptr(),
+
+                unsafe{
+                    device.create_pipeline_layout(&graphics_pipeline_layout_create_info,temp_allocation_callbacks)
+                }.unwrap()
+            };
+
+            let (vertex_skybox,fragment_skybox)={
+                let vertex_shader_code=std::fs::read("skybox.vert.spv").unwrap();
+                let vertex_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:vertex_shader_code.len(), //size in bytes
+                    p_code:vertex_shader_code.as_ptr() as *const u32,//but pointer to 4byte unsigned integers
                     ..Default::default()
                 };
-                let graphics_pipeline_create_info_2d=vk::GraphicsPipelineCreateInfo{
-                    stage_count:shader_stage_create_infos_2d.len() as u32,
-                    p_stages:shader_stage_create_infos_2d.as_ptr(),
-                    p_vertex_input_state:&vertex_input_state_create_info,
-                    p_input_assembly_state:&input_assembly_state_create_info,
-                    p_viewport_state:&viewport_state_create_info,
-                    p_rasterization_state:&rasterization_state_create_info,
-                    p_multisample_state:&multisample_state_create_info,
-                    p_color_blend_state:&color_blend_state,
-                    p_dynamic_state:&dynamic_state_create_info,
-                    layout:graphics_pipeline_layout_2d,
-                    render_pass,
-                    subpass:0,
-                    base_pipeline_index:-1,
-                    base_pipeline_handle:vk::Pipeline::null(),
+
+                let fragment_shader_code=std::fs::read("skybox.frag.spv").unwrap();
+                let fragment_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:fragment_shader_code.len(),
+                    p_code:fragment_shader_code.as_ptr() as *const u32,
                     ..Default::default()
                 };
                 
-                let shader_stage_create_infos_3d=vec![
-                    vk::PipelineShaderStageCreateInfo{
-                        stage:vk::ShaderStageFlags::VERTEX,
-                        module:vertex_3d,
-                        p_name:shader_entry_fn_name,
-                        ..Default::default()
+                unsafe{
+                    (
+                        device.create_shader_module(&vertex_shader_create_info,temp_allocation_callbacks).unwrap(),
+                        device.create_shader_module(&fragment_shader_create_info,temp_allocation_callbacks).unwrap(),
+                    )
+                }
+            };
+
+            //debug lines use the same push constants as the 3d pipelines, and no descriptor sets
+            let graphics_pipeline_layout_lines={
+                let push_constants=[
+                    vk::PushConstantRange{
+                        stage_flags:vk::ShaderStageFlags::VERTEX,
+                        offset:0,
+                        size:(std::mem::size_of::<glm::Mat4>()*3) as u32,
                     },
-                    vk::PipelineShaderStageCreateInfo{
-                        stage:vk::ShaderStageFlags::FRAGMENT,
-                        module:fragment_3d,
-                        p_name:shader_entry_fn_name,
-                        ..Default::default()
-                    }
                 ];
-                let graphics_pipeline_create_info_3d=vk::GraphicsPipelineCreateInfo{
-                    stage_count:shader_stage_create_infos_3d.len() as u32,
-                    p_stages:shader_stage_create_infos_3d.as_ptr(),
-                    p_vertex_input_state:&vertex_input_state_create_info,
-                    p_input_assembly_state:&input_assembly_state_create_info,
-                    p_viewport_state:&viewport_state_create_info,
-                    p_rasterization_state:&rasterization_state_create_info,
-                    p_multisample_state:&multisample_state_create_info,
-                    p_color_blend_state:&color_blend_state,
-                    p_dynamic_state:&dynamic_state_create_info,
-                    layout:graphics_pipeline_layout_3d,
-                    render_pass,
-                    subpass:0,
-                    base_pipeline_index:-1,
-                    base_pipeline_handle:vk::Pipeline::null(),
+                let graphics_pipeline_layout_create_info=vk::PipelineLayoutCreateInfo{
+                    push_constant_range_count:push_constants.len() as u32,
+                    p_push_constant_ranges:push_constants.as_ptr(),
                     ..Default::default()
                 };
 
                 unsafe{
-                    device.create_graphics_pipelines(vk::PipelineCache::null(), &[graphics_pipeline_create_info_2d,graphics_pipeline_create_info_3d],temp_allocation_callbacks)
+                    device.create_pipeline_layout(&graphics_pipeline_layout_create_info,temp_allocation_callbacks)
                 }.unwrap()
             };
+
+            let (vertex_lines,fragment_lines)={
+                let vertex_shader_code=std::fs::read("debug_lines.vert.spv").unwrap();
+                let vertex_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:vertex_shader_code.len(), //size in bytes
+                    p_code:vertex_shader_code.as_ptr() as *const u32,//but pointer to 4byte unsigned integers
+                    ..Default::default()
+                };
+
+                let fragment_shader_code=std::fs::read("debug_lines.frag.spv").unwrap();
+                let fragment_shader_create_info=vk::ShaderModuleCreateInfo{
+                    code_size:fragment_shader_code.len(),
+                    p_code:fragment_shader_code.as_ptr() as *const u32,
+                    ..Default::default()
+                };
+                
+                unsafe{
+                    (
+                        device.create_shader_module(&vertex_shader_create_info,temp_allocation_callbacks).unwrap(),
+                        device.create_shader_module(&fragment_shader_create_info,temp_allocation_callbacks).unwrap(),
+                    )
+                }
+            };
+
             let graphics_pipeline_2d=GraphicsPipeline{
                 layout:graphics_pipeline_layout_2d,
-                pipeline:graphics_pipelines[0],
+                pipeline:vk::Pipeline::null(),//created with render pass below
                 vertex:vertex_2d,
                 fragment:fragment_2d
             };
             let graphics_pipeline_3d=GraphicsPipeline{
                 layout:graphics_pipeline_layout_3d,
-                pipeline:graphics_pipelines[1],
+                pipeline:vk::Pipeline::null(),
                 vertex:vertex_3d,
                 fragment:fragment_3d
             };
+            let graphics_pipeline_3d_instanced=GraphicsPipeline{
+                layout:graphics_pipeline_layout_3d_instanced,
+                pipeline:vk::Pipeline::null(),
+                vertex:vertex_3d_instanced,
+                fragment:fragment_3d_instanced
+            };
+            let graphics_pipeline_3d_lit=GraphicsPipeline{
+                layout:graphics_pipeline_layout_3d_lit,
+                pipeline:vk::Pipeline::null(),
+                vertex:vertex_3d_lit,
+                fragment:fragment_3d_lit
+            };
+            let graphics_pipeline_skybox=GraphicsPipeline{
+                layout:graphics_pipeline_layout_skybox,
+                pipeline:vk::Pipeline::null(),
+                vertex:vertex_skybox,
+                fragment:fragment_skybox
+            };
+            let graphics_pipeline_lines=GraphicsPipeline{
+                layout:graphics_pipeline_layout_lines,
+                pipeline:vk::Pipeline::null(),
+                vertex:vertex_lines,
+                fragment:fragment_lines
+            };
             
-            painter=std::mem::ManuallyDrop::new(Painter{
+            let device_memory_properties=unsafe{
+                instance.get_physical_device_memory_properties(physical_device)
+            };
+
+            let depth_format=render_target::find_depth_format(&instance,physical_device);
+
+            let physical_device_properties=unsafe{
+                instance.get_physical_device_properties(physical_device)
+            };
+            let supported_sample_counts=physical_device_properties.limits.framebuffer_color_sample_counts & physical_device_properties.limits.framebuffer_depth_sample_counts;
+            let max_anisotropy=if physical_device_features.sampler_anisotropy==vk::TRUE{
+                physical_device_properties.limits.max_sampler_anisotropy
+            }else{
+                0.0
+            };
+
+            let mut new_painter=Painter{
                 allocation_callbacks,
 
                 device:device.clone(),
 
+                device_memory_properties,
+
                 swapchain_surface_format,
+                depth_format,
+
+                supported_sample_counts,
+                sample_count:vk::SampleCountFlags::TYPE_1,
 
-                sampler,
+                samplers:SamplerCache::new(max_anisotropy),
 
                 descriptor_pool,
                 descriptor_set_layout,
-                descriptor_set,
 
-                render_pass,
+                material_descriptor_pools:Vec::new(),
+                materials:std::collections::HashMap::new(),
+                frame:0,
+
+                render_pass:vk::RenderPass::null(),
+
+                vertex_layout:Vertex::layout(),
 
                 graphics_pipeline_2d,
                 graphics_pipeline_3d,
+                graphics_pipelines_3d_blended:Vec::new(),
+                graphics_pipeline_3d_instanced,
+                graphics_pipelines_3d_instanced_blended:Vec::new(),
+                graphics_pipeline_3d_lit,
+                graphics_pipelines_3d_lit_blended:Vec::new(),
+                graphics_pipeline_skybox,
+                graphics_pipeline_lines,
+                graphics_pipelines_by_vertex_layout:std::collections::HashMap::new(),
+
+                instance_buffer:IntegratedBuffer{
+                    buffer_size:0,
+                    item_count:0,
+                    buffer:vk::Buffer::null(),
+                    memory:vk::DeviceMemory::null(),
+                },
+                instance_buffer_pointer:std::ptr::null_mut(),
+
+                lights:Lights::new(),
+                lights_descriptor_set_layout,
+                lights_descriptor_set:vk::DescriptorSet::null(),
+                lights_buffer:IntegratedBuffer{
+                    buffer_size:0,
+                    item_count:0,
+                    buffer:vk::Buffer::null(),
+                    memory:vk::DeviceMemory::null(),
+                },
+                lights_buffer_pointer:std::ptr::null_mut(),
+
+                sprite_buffer:IntegratedBuffer{
+                    buffer_size:0,
+                    item_count:0,
+                    buffer:vk::Buffer::null(),
+                    memory:vk::DeviceMemory::null(),
+                },
+                sprite_buffer_pointer:std::ptr::null_mut(),
+
+                lines:Vec::new(),
+                line_buffer:IntegratedBuffer{
+                    buffer_size:0,
+                    item_count:0,
+                    buffer:vk::Buffer::null(),
+                    memory:vk::DeviceMemory::null(),
+                },
+                line_buffer_pointer:std::ptr::null_mut(),
+
+                default_attributes_buffer:IntegratedBuffer{
+                    buffer_size:0,
+                    item_count:0,
+                    buffer:vk::Buffer::null(),
+                    memory:vk::DeviceMemory::null(),
+                },
+
+                camera:Camera::default(),
+                skybox:None,
+
+                render_queue:RenderQueue::new(),
+                statistics:FrameStatistics::default(),
 
                 rendering_done,
 
@@ -994,11 +1150,20 @@ impl Manager{
                 graphics_queue_family_index,
                 graphics_queue_command_pool,
                 graphics_queue_command_buffers,
-            });
+            };
+            //create render pass and pipelines
+            new_painter.set_sample_count(msaa_samples);
+            new_painter.create_instance_buffer();
+            new_painter.create_lights_buffer();
+            new_painter.create_default_attributes_buffer();
+            new_painter.create_sprite_buffer();
+            new_painter.create_line_buffer();
+
+            painter=std::mem::ManuallyDrop::new(new_painter);
         }
 
         let decoder={
-            //create staging buffer for resource upload with fixed size (should be enough for this exercise)
+            //create staging buffer for resource upload with fixed size (bigger uploads get a staging buffer of their own, see Decoder::write_staging)
             let buffer_size=10*1024*1024;
             let buffer_create_info=vk::BufferCreateInfo{
                 size:buffer_size,
@@ -1049,6 +1214,8 @@ impl Manager{
                 allocation_callbacks,
 
                 device:device.clone(),
+                instance:instance.clone(),
+                physical_device,
 
                 device_memory_properties,
 
@@ -1059,8 +1226,11 @@ impl Manager{
                     memory,
                 },
                 staging_buffer_in_use_size:0,
+                dedicated_staging_buffers:Vec::new(),
 
+                models:std::collections::HashMap::new(),
                 meshes:std::collections::HashMap::new(),
+                dynamic_meshes:std::collections::HashMap::new(),
                 textures:std::collections::HashMap::new(),
             })
         };
@@ -1088,6 +1258,10 @@ impl Manager{
 
             swapchain_surface_format,
 
+            offscreen_targets:Vec::new(),
+
+            scene:Scene::new(),
+
             painter,
 
             decoder,
@@ -1351,22 +1525,7 @@ impl Manager{
         let surface_formats=unsafe{
             self.surface.get_physical_device_surface_formats(self.physical_device, surface)
         }.unwrap();
-        //use first available format, but check for two 'better' alternatives
-        let mut surface_format=surface_formats[0];
-        //if the only supported format is 'undefined', there is no preferred format for the surface
-        //then use 'most widely used' format
-        if surface_formats.len()==1 && surface_format.format==vk::Format::UNDEFINED{
-            surface_format=vk::SurfaceFormatKHR{
-                format:vk::Format::R8G8B8A8_UNORM,
-                color_space:vk::ColorSpaceKHR::SRGB_NONLINEAR,
-            };
-        }else{
-            for format in surface_formats.iter(){
-                if format.format==vk::Format::R8G8B8A8_UNORM{
-                    surface_format=*format;
-                }
-            }
-        }
+        let surface_format=color::choose_surface_format(&surface_formats);
 
         //set extent to current extent, according to surface
         //if that is not available (indicated by special values of 'current extent')
@@ -1470,19 +1629,10 @@ impl Manager{
             }.unwrap()
         }).collect();
 
+        //multisampled color and depth attachments are shared by all framebuffers of this window
+        let transient_attachments=self.painter.create_transient_attachments(swapchain_extent);
         let swapchain_image_framebuffers:Vec<vk::Framebuffer>=swapchain_image_views.iter().map(|view|{
-            let framebuffer_create_info=vk::FramebufferCreateInfo{
-                render_pass:self.painter.render_pass,
-                attachment_count:1,
-                p_attachments:view,
-                width:swapchain_extent.width,
-                height:swapchain_extent.height,
-                layers:1,
-                ..Default::default()
-            };
-            unsafe{
-                self.device.create_framebuffer(&framebuffer_create_info, self.get_allocation_callbacks())
-            }.unwrap()
+            self.painter.create_framebuffer(swapchain_extent,*view,&transient_attachments)
         }).collect();
 
         let window=Window{
@@ -1496,16 +1646,70 @@ impl Manager{
             swapchain_handle,
             swapchain_images,
             swapchain_image_views,
+            transient_attachments,
             swapchain_image_framebuffers,
         };
         self.open_windows.push(window);
     }
-    fn destroy_window(&mut self,open_window_index:usize){
-        for framebuffer in self.open_windows[open_window_index].swapchain_image_framebuffers.iter(){
+    fn destroy_window_framebuffers(&mut self,open_window_index:usize){
+        let window=&mut self.open_windows[open_window_index];
+        for framebuffer in window.swapchain_image_framebuffers.drain(..){
             unsafe{
-                self.device.destroy_framebuffer(*framebuffer, self.get_allocation_callbacks());
+                self.device.destroy_framebuffer(framebuffer, self.allocation_callbacks.as_ref());
             }
         }
+        window.transient_attachments.destroy(&self.device,self.allocation_callbacks.as_ref());
+    }
+    fn create_window_framebuffers(&mut self,open_window_index:usize){
+        let painter=&self.painter;
+        let window=&mut self.open_windows[open_window_index];
+        window.transient_attachments=painter.create_transient_attachments(window.extent);
+        let transient_attachments=window.transient_attachments;
+        let extent=window.extent;
+        window.swapchain_image_framebuffers=window.swapchain_image_views.iter().map(|view|{
+            painter.create_framebuffer(extent,*view,&transient_attachments)
+        }).collect();
+    }
+
+    //create offscreen render target, rendered with the same render pass (and sample count) as windows
+    //returns index into offscreen_targets
+    pub fn new_offscreen_target(&mut self,width:u32,height:u32)->usize{
+        let render_target=self.painter.create_render_target(vk::Extent2D{
+            width,
+            height,
+        });
+        self.offscreen_targets.push(render_target);
+        self.offscreen_targets.len()-1
+    }
+    //change number of samples per pixel at runtime (clamped to what the device supports)
+    //rebuilds render pass, pipelines and all framebuffers, returns the sample count actually used
+    pub fn set_msaa_samples(&mut self,msaa_samples:u32)->vk::SampleCountFlags{
+        unsafe{
+            self.device.device_wait_idle()
+        }.unwrap();
+
+        for open_window_index in 0..self.open_windows.len(){
+            self.destroy_window_framebuffers(open_window_index);
+        }
+        let offscreen_extents:Vec<vk::Extent2D>=self.offscreen_targets.iter().map(|t| t.extent).collect();
+        for render_target in self.offscreen_targets.drain(..){
+            render_target.destroy(&self.device,self.allocation_callbacks.as_ref());
+        }
+
+        let sample_count=self.painter.set_sample_count(msaa_samples);
+
+        for open_window_index in 0..self.open_windows.len(){
+            self.create_window_framebuffers(open_window_index);
+        }
+        for extent in offscreen_extents{
+            self.new_offscreen_target(extent.width,extent.height);
+        }
+
+        sample_count
+    }
+
+    fn destroy_window(&mut self,open_window_index:usize){
+        self.destroy_window_framebuffers(open_window_index);
         for image_view in self.open_windows[open_window_index].swapchain_image_views.iter(){
             unsafe{
                 self.device.destroy_image_view(*image_view, self.get_allocation_callbacks());
@@ -1616,6 +1820,8 @@ impl Manager{
             self.device.wait_for_fences(&[self.frame_sync_fence], true, u64::MAX).unwrap();
             self.device.reset_fences(&[self.frame_sync_fence]).unwrap();
         }
+        //the last frame has finished copying from staging memory
+        self.decoder.reset_staging();
 
         //acquire next swapchain image for drawing and presenting
         let (image_index,suboptimal)=unsafe{
@@ -1716,48 +1922,45 @@ impl Manager{
                 self.device.cmd_pipeline_barrier(self.painter.graphics_queue_command_buffers[0],vk::PipelineStageFlags::TOP_OF_PIPE,vk::PipelineStageFlags::TRANSFER,vk::DependencyFlags::empty(),&[],&[],&[])
             };
 
-            //record mesh upload
-            let quad_data=self.decoder.get_mesh("quad.obj",self.painter.graphics_queue_command_buffers[0]);
+            //record mesh upload (and upload of textures referenced by the model materials)
+            let quad_model=self.decoder.get_model("quad.obj",self.painter.graphics_queue_command_buffers[0]);
 
             //record texture upload (use staging buffer range outside of potential mesh upload range)
-            let intel_truck=self.decoder.get_texture("inteltruck.png", self.painter.graphics_queue_command_buffers[0]);
+            let intel_truck=self.decoder.get_texture("inteltruck.png",TextureUsage::Color,self.painter.graphics_queue_command_buffers[0]);
             
-            //set descriptor set data here for now (only needs to be done once, ever, but i dont know where)
-            {
-                let descriptor_image_info=vk::DescriptorImageInfo{
-                    sampler:self.painter.sampler,
-                    image_view:intel_truck.image_view,
-                    image_layout:vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                };
-                let write_descriptor_set=vk::WriteDescriptorSet{
-                    dst_set:self.painter.descriptor_set,
-                    dst_binding:0,
-                    dst_array_element:0,
-                    descriptor_count:1,
-                    descriptor_type:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                    p_image_info:&descriptor_image_info,
-                    ..Default::default()
-                };
-                unsafe{
-                    self.device.update_descriptor_sets(&[write_descriptor_set],&[])
-                };
-                unsafe{
-                    self.device.device_wait_idle()
-                }.unwrap();
+            //descriptor set for the material is written once, when it is first requested
+            let intel_truck_material=self.painter.get_material(intel_truck,SamplerDescription::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE),BlendMode::Alpha,Shading::Lit);
+            //submeshes without a material use the truck texture
+            let quad_materials=self.painter.get_model_materials(&quad_model,&intel_truck_material);
+
+            //populate scene on first frame (resources are uploaded using the command buffer of a frame)
+            if self.scene.is_empty(){
+                let quad=self.scene.add_model("quad",self.scene.root,&quad_model,&quad_materials);
+                self.scene.set_local_transform(quad,glm::rotate_x(&glm::identity::<f32,4>(),0.4));
+
+                let camera=self.scene.add_node("camera",self.scene.root);
+                self.scene.node_mut(camera).camera=Some(Camera::default());
+                self.scene.active_camera=Some(camera);
+            }
+
+            //world axes as debug lines, x red, y green, z blue
+            for axis in 0..3{
+                let mut end=[0.0;3];
+                end[axis]=1.0;
+                let mut color=[0.0,0.0,0.0,1.0];
+                color[axis]=1.0;
+                self.painter.lines.extend_from_slice(&[ColorVertex{position:[0.0;3],color},ColorVertex{position:end,color}]);
             }
 
             self.painter.draw(
                 self.open_windows[0].swapchain_image_framebuffers[image_index as usize],
                 self.open_windows[0].extent,
-                &vec![Object{
-                    mesh:quad_data.clone(),
-                    texture:intel_truck
-                }],
+                &mut self.scene,
+                &[],
+                &[],
                 self.open_windows[0].image_transferable
             );
         }
-        self.decoder.staging_buffer_in_use_size=0;
-
         //retrieve swapchain image from graphics queue for presentation
         {
             let present_queue_command_buffer_begin_info=vk::CommandBufferBeginInfo{
@@ -1873,6 +2076,10 @@ impl Drop for Manager{
             self.device.device_wait_idle()
         }.unwrap();
 
+        for render_target in self.offscreen_targets.iter(){
+            render_target.destroy(&self.device,self.get_allocation_callbacks());
+        }
+
         unsafe{
             std::mem::ManuallyDrop::drop(&mut self.painter);
             std::mem::ManuallyDrop::drop(&mut self.decoder);
@@ -1897,7 +2104,7 @@ impl Drop for Manager{
 }
 
 fn main() {
-    let mut manager=Manager::new();
+    let mut manager=Manager::new(4);
     manager.new_window(600,400,"hello milena\0");
     manager.run();
 }
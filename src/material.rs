@@ -0,0 +1,79 @@
+use ash::{
+    vk,
+};
+
+use crate::Image;
+use crate::sampler::SamplerDescription;
+
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub enum BlendMode{
+    //no blending, writes depth
+    Opaque,
+    //color is blended using its alpha channel
+    Alpha,
+    //color was already multiplied by its alpha channel
+    Premultiplied,
+    //color is added onto the background
+    Additive,
+}
+impl BlendMode{
+    pub const ALL:[BlendMode;4]=[
+        BlendMode::Opaque,
+        BlendMode::Alpha,
+        BlendMode::Premultiplied,
+        BlendMode::Additive,
+    ];
+
+    //index into lists of per-blend-mode objects (e.g. pipelines), in order of BlendMode::ALL
+    pub fn index(&self)->usize{
+        match self{
+            BlendMode::Opaque=>0,
+            BlendMode::Alpha=>1,
+            BlendMode::Premultiplied=>2,
+            BlendMode::Additive=>3,
+        }
+    }
+
+    //transparent geometry is drawn after opaque geometry, back to front, and does not write depth
+    pub fn is_transparent(&self)->bool{
+        *self!=BlendMode::Opaque
+    }
+
+    pub fn color_blend_attachment_state(&self)->vk::PipelineColorBlendAttachmentState{
+        let (src_color_blend_factor,dst_color_blend_factor,src_alpha_blend_factor,dst_alpha_blend_factor)=match self{
+            BlendMode::Opaque=>(vk::BlendFactor::ONE,vk::BlendFactor::ZERO,vk::BlendFactor::ONE,vk::BlendFactor::ZERO),
+            BlendMode::Alpha=>(vk::BlendFactor::SRC_ALPHA,vk::BlendFactor::ONE_MINUS_SRC_ALPHA,vk::BlendFactor::ONE,vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Premultiplied=>(vk::BlendFactor::ONE,vk::BlendFactor::ONE_MINUS_SRC_ALPHA,vk::BlendFactor::ONE,vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive=>(vk::BlendFactor::SRC_ALPHA,vk::BlendFactor::ONE,vk::BlendFactor::ZERO,vk::BlendFactor::ONE),
+        };
+        vk::PipelineColorBlendAttachmentState{
+            blend_enable:self.is_transparent() as u32,
+            src_color_blend_factor,
+            dst_color_blend_factor,
+            color_blend_op:vk::BlendOp::ADD,
+            src_alpha_blend_factor,
+            dst_alpha_blend_factor,
+            alpha_blend_op:vk::BlendOp::ADD,
+            color_write_mask:vk::ColorComponentFlags::all(),
+        }
+    }
+}
+
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub enum Shading{
+    //texture color is drawn as is
+    Unlit,
+    //texture color is lit by the painter lights (see lighting::Lights)
+    Lit,
+}
+
+//texture and how it is sampled and blended, with the descriptor set that binds the texture for drawing
+#[derive(Debug,Clone)]
+pub struct Material{
+    pub texture:std::sync::Arc<Image>,
+    pub sampler:SamplerDescription,
+    pub blend_mode:BlendMode,
+    pub shading:Shading,
+    pub descriptor_pool:vk::DescriptorPool,//pool the descriptor set was allocated from, see Painter::release_unused_materials
+    pub descriptor_set:vk::DescriptorSet,
+}
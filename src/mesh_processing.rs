@@ -0,0 +1,439 @@
+//cpu side processing of vertex and index data, applied before a mesh is uploaded
+
+use crate::Vertex;
+use crate::decoder::VertexIndices;
+
+fn position(vertex:&Vertex)->glm::Vec3{
+    glm::vec3(vertex.x,vertex.y,vertex.z)
+}
+
+fn attributes(vertex:&Vertex)->[f32;17]{
+    [
+        vertex.x,vertex.y,vertex.z,vertex.w,
+        vertex.u,vertex.v,
+        vertex.nx,vertex.ny,vertex.nz,
+        vertex.tx,vertex.ty,vertex.tz,vertex.tw,
+        vertex.r,vertex.g,vertex.b,vertex.a,
+    ]
+}
+
+//exact position, used to find corners that share a position but not a vertex
+fn position_key(vertex:&Vertex)->[u32;3]{
+    [vertex.x.to_bits(),vertex.y.to_bits(),vertex.z.to_bits()]
+}
+
+fn is_degenerate(triangle:&VertexIndices)->bool{
+    let [a,b,c]=triangle.corners();
+    a==b || b==c || a==c
+}
+
+//merge vertices whose attributes all differ by at most epsilon, and remove triangles that collapse because of it
+pub fn weld_vertices(vertices:&mut Vec<Vertex>,vertex_indices:&mut Vec<VertexIndices>,epsilon:f32){
+    let mut welded:Vec<Vertex>=Vec::with_capacity(vertices.len());
+    let mut remap=Vec::with_capacity(vertices.len());
+    if epsilon<=0.0{
+        //only identical vertices are merged, which can be looked up by their bits (adding 0 turns -0 into 0, which compares equal to it)
+        let mut indices:std::collections::HashMap<[u32;17],u32>=std::collections::HashMap::new();
+        for vertex in vertices.iter(){
+            let key=attributes(vertex).map(|a| (a+0.0).to_bits());
+            remap.push(*indices.entry(key).or_insert_with(||{
+                welded.push(vertex.clone());
+                welded.len() as u32-1
+            }));
+        }
+    }else{
+        //vertices are sorted into a grid with the cell size of epsilon, so that matches can only be in neighbouring cells
+        //(coordinates far outside the grid saturate into the outermost cells, which only makes the search slower)
+        let cell=|vertex:&Vertex| [
+            (vertex.x/epsilon).floor() as i64,
+            (vertex.y/epsilon).floor() as i64,
+            (vertex.z/epsilon).floor() as i64,
+        ];
+
+        let mut grid:std::collections::HashMap<[i64;3],Vec<u32>>=std::collections::HashMap::new();
+        for vertex in vertices.iter(){
+            let [cx,cy,cz]=cell(vertex);
+            let vertex_attributes=attributes(vertex);
+            let mut matching_vertex=None;
+            'search: for x in cx.saturating_sub(1)..=cx.saturating_add(1){
+                for y in cy.saturating_sub(1)..=cy.saturating_add(1){
+                    for z in cz.saturating_sub(1)..=cz.saturating_add(1){
+                        for candidate in grid.get(&[x,y,z]).into_iter().flatten(){
+                            let candidate_attributes=attributes(&welded[*candidate as usize]);
+                            if vertex_attributes.iter().zip(candidate_attributes.iter()).all(|(a,b)| (a-b).abs()<=epsilon){
+                                matching_vertex=Some(*candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+            remap.push(matching_vertex.unwrap_or_else(||{
+                let index=welded.len() as u32;
+                welded.push(vertex.clone());
+                grid.entry([cx,cy,cz]).or_default().push(index);
+                index
+            }));
+        }
+    }
+
+    for triangle in vertex_indices.iter_mut(){
+        let [a,b,c]=triangle.corners().map(|i| remap[i as usize]);
+        *triangle=VertexIndices::new(a,b,c);
+    }
+    vertex_indices.retain(|triangle| !is_degenerate(triangle));
+    *vertices=welded;
+}
+
+//replace the vertex normals with normals generated from the triangles
+//corners sharing a position are smoothed together if the angle between their faces is at most crease_angle (radians),
+//so 0 gives flat normals and PI smooth normals everywhere. vertices are split where a vertex needs more than one normal.
+pub fn generate_normals(vertices:&mut Vec<Vertex>,vertex_indices:&mut [VertexIndices],crease_angle:f32){
+    //area weighted face normals (length of the cross product is twice the triangle area)
+    let face_normals:Vec<glm::Vec3>=vertex_indices.iter().map(|triangle|{
+        let [p0,p1,p2]=triangle.corners().map(|i| position(&vertices[i as usize]));
+        glm::cross(&(p1-p0),&(p2-p0))
+    }).collect();
+    let face_directions:Vec<glm::Vec3>=face_normals.iter().map(|normal|{
+        if glm::length(normal)>0.0{glm::normalize(normal)}else{*normal}
+    }).collect();
+
+    let mut corners_by_position:std::collections::HashMap<[u32;3],Vec<usize>>=std::collections::HashMap::new();
+    for (triangle_index,triangle) in vertex_indices.iter().enumerate(){
+        for vertex_index in triangle.corners().iter(){
+            corners_by_position.entry(position_key(&vertices[*vertex_index as usize])).or_default().push(triangle_index);
+        }
+    }
+
+    //small tolerance, so that coplanar faces are merged even with a crease angle of 0
+    let min_cos=crease_angle.cos()-1.0e-5;
+    let mut generated:Vec<Vertex>=Vec::with_capacity(vertices.len());
+    let mut generated_index:std::collections::HashMap<(u32,[u32;3]),u32>=std::collections::HashMap::new();
+    for (triangle_index,triangle) in vertex_indices.iter_mut().enumerate(){
+        let corners=triangle.corners().map(|vertex_index|{
+            let vertex=&vertices[vertex_index as usize];
+            let mut normal=glm::vec3(0.0f32,0.0,0.0);
+            for other in corners_by_position[&position_key(vertex)].iter(){
+                if *other==triangle_index || glm::dot(&face_directions[triangle_index],&face_directions[*other])>=min_cos{
+                    normal+=face_normals[*other];
+                }
+            }
+            //the same face can be counted more than once if the triangle is degenerate, which only changes the weight
+            let normal=if glm::length(&normal)>0.0{glm::normalize(&normal)}else{glm::vec3(0.0,0.0,1.0)};
+
+            *generated_index.entry((vertex_index,[normal.x.to_bits(),normal.y.to_bits(),normal.z.to_bits()])).or_insert_with(||{
+                generated.push(vertex.clone().with_normal(normal.x,normal.y,normal.z));
+                (generated.len()-1) as u32
+            })
+        });
+        *triangle=VertexIndices::new(corners[0],corners[1],corners[2]);
+    }
+    *vertices=generated;
+}
+
+//triangles of a mesh as seen by mikktspace, collecting one tangent per corner
+struct TangentSpaceMesh<'a>{
+    vertices:&'a [Vertex],
+    vertex_indices:&'a [VertexIndices],
+    tangents:Vec<[f32;4]>,
+}
+impl TangentSpaceMesh<'_>{
+    fn vertex(&self,face:usize,corner:usize)->&Vertex{
+        &self.vertices[self.vertex_indices[face].corners()[corner] as usize]
+    }
+}
+impl bevy_mikktspace::Geometry for TangentSpaceMesh<'_>{
+    fn num_faces(&self)->usize{
+        self.vertex_indices.len()
+    }
+    fn num_vertices_of_face(&self,_face:usize)->usize{
+        3
+    }
+    fn position(&self,face:usize,corner:usize)->[f32;3]{
+        let vertex=self.vertex(face,corner);
+        [vertex.x,vertex.y,vertex.z]
+    }
+    fn normal(&self,face:usize,corner:usize)->[f32;3]{
+        let vertex=self.vertex(face,corner);
+        [vertex.nx,vertex.ny,vertex.nz]
+    }
+    fn tex_coord(&self,face:usize,corner:usize)->[f32;2]{
+        let vertex=self.vertex(face,corner);
+        [vertex.u,vertex.v]
+    }
+    fn set_tangent_encoded(&mut self,tangent:[f32;4],face:usize,corner:usize){
+        self.tangents[face*3+corner]=tangent;
+    }
+}
+
+//generate tangents from the texture coordinates with mikktspace (the reference implementation, as used by baking tools),
+//so that normal maps baked against it are reproduced exactly. the handedness is stored in tangent w, with bitangent=w*cross(normal,tangent).
+//corners of a vertex that get different tangents (e.g. on mirrored uv seams, or across tangent space groups) are split into separate vertices.
+//requires normals, so generate_normals should be called first if the mesh has none.
+pub fn generate_tangents(vertices:&mut Vec<Vertex>,vertex_indices:&mut [VertexIndices]){
+    let mut mesh=TangentSpaceMesh{
+        vertices,
+        vertex_indices,
+        tangents:vec![[1.0,0.0,0.0,1.0];vertex_indices.len()*3],
+    };
+    if !bevy_mikktspace::generate_tangents(&mut mesh){
+        return;
+    }
+    let tangents=mesh.tangents;
+
+    //vertices keep their index for the first tangent they get, and are duplicated for every other one
+    let mut assigned:std::collections::HashMap<(u32,[u32;4]),u32>=std::collections::HashMap::new();
+    let mut used=vec![false;vertices.len()];
+    for (face,triangle) in vertex_indices.iter_mut().enumerate(){
+        let mut corners=triangle.corners();
+        for (corner,vertex_index) in corners.iter_mut().enumerate(){
+            let [tx,ty,tz,tw]=tangents[face*3+corner];
+            let index=*assigned.entry((*vertex_index,[tx,ty,tz,tw].map(f32::to_bits))).or_insert_with(||{
+                let vertex=vertices[*vertex_index as usize].clone().with_tangent(tx,ty,tz,tw);
+                if used[*vertex_index as usize]{
+                    vertices.push(vertex);
+                    (vertices.len()-1) as u32
+                }else{
+                    used[*vertex_index as usize]=true;
+                    vertices[*vertex_index as usize]=vertex;
+                    *vertex_index
+                }
+            });
+            *vertex_index=index;
+        }
+        *triangle=VertexIndices::new(corners[0],corners[1],corners[2]);
+    }
+}
+
+const VERTEX_CACHE_SIZE:usize=32;
+
+//score of a vertex for the vertex cache optimization (Tom Forsyth, "Linear-Speed Vertex Cache Optimisation")
+fn vertex_score(cache_position:Option<usize>,remaining_triangles:usize)->f32{
+    if remaining_triangles==0{
+        return -1.0;
+    }
+    let cache_score=match cache_position{
+        //the vertices of the last triangle get a fixed score, so that strips are not preferred over fans
+        Some(position) if position<3=>0.75,
+        Some(position)=>(1.0-(position-3) as f32/(VERTEX_CACHE_SIZE-3) as f32).powf(1.5),
+        None=>0.0,
+    };
+    //boost vertices with few remaining triangles, to avoid leaving single triangles behind
+    cache_score+2.0/(remaining_triangles as f32).sqrt()
+}
+
+//reorder triangles to improve reuse of the post-transform vertex cache
+pub fn optimize_vertex_cache(vertex_indices:&mut [VertexIndices],vertex_count:usize){
+    let triangle_count=vertex_indices.len();
+    let mut vertex_triangles:Vec<Vec<usize>>=vec![Vec::new();vertex_count];
+    for (triangle_index,triangle) in vertex_indices.iter().enumerate(){
+        for vertex_index in triangle.corners().iter(){
+            vertex_triangles[*vertex_index as usize].push(triangle_index);
+        }
+    }
+
+    let mut cache_positions:Vec<Option<usize>>=vec![None;vertex_count];
+    let mut vertex_scores:Vec<f32>=vertex_triangles.iter().map(|triangles| vertex_score(None,triangles.len())).collect();
+    let triangle_score=|triangle:&VertexIndices,vertex_scores:&[f32]| triangle.corners().iter().map(|i| vertex_scores[*i as usize]).sum::<f32>();
+    let mut emitted=vec![false;triangle_count];
+
+    let mut order=Vec::with_capacity(triangle_count);
+    let mut cache:Vec<u32>=Vec::with_capacity(VERTEX_CACHE_SIZE+3);
+    let mut best_triangle=None;
+    let mut next_unemitted=0;
+    while order.len()<triangle_count{
+        //if no triangle uses a cached vertex, continue with the next triangle in the original order
+        let triangle_index=match best_triangle{
+            Some(triangle_index)=>triangle_index,
+            None=>{
+                while emitted[next_unemitted]{
+                    next_unemitted+=1;
+                }
+                next_unemitted
+            },
+        };
+        emitted[triangle_index]=true;
+        order.push(triangle_index);
+
+        let corners=vertex_indices[triangle_index].corners();
+        for vertex_index in corners.iter(){
+            vertex_triangles[*vertex_index as usize].retain(|t| *t!=triangle_index);
+        }
+
+        //move the triangle vertices to the front of the cache
+        cache.retain(|vertex_index| !corners.contains(vertex_index));
+        for vertex_index in corners.iter().rev(){
+            cache.insert(0,*vertex_index);
+        }
+        for (position,vertex_index) in cache.iter().enumerate(){
+            let vertex_index=*vertex_index as usize;
+            cache_positions[vertex_index]=if position<VERTEX_CACHE_SIZE{Some(position)}else{None};
+            vertex_scores[vertex_index]=vertex_score(cache_positions[vertex_index],vertex_triangles[vertex_index].len());
+        }
+
+        //only triangles using vertices that were in the cache change their score
+        best_triangle=None;
+        let mut best_score=f32::MIN;
+        for vertex_index in cache.iter(){
+            for triangle in vertex_triangles[*vertex_index as usize].iter(){
+                let score=triangle_score(&vertex_indices[*triangle],&vertex_scores);
+                if score>best_score{
+                    best_score=score;
+                    best_triangle=Some(*triangle);
+                }
+            }
+        }
+        cache.truncate(VERTEX_CACHE_SIZE);
+    }
+
+    let reordered:Vec<VertexIndices>=order.iter().map(|triangle_index| vertex_indices[*triangle_index]).collect();
+    vertex_indices.copy_from_slice(&reordered);
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //unindexed unit quad in the xy plane, facing +z
+    fn quad()->(Vec<Vertex>,Vec<VertexIndices>){
+        let corner=|x:f32,y:f32| Vertex::new(x,y,0.0,1.0,x,y).with_normal(0.0,0.0,1.0);
+        let vertices=vec![corner(0.0,0.0),corner(1.0,0.0),corner(1.0,1.0),corner(0.0,0.0),corner(1.0,1.0),corner(0.0,1.0)];
+        (vertices,vec![VertexIndices::new(0,1,2),VertexIndices::new(3,4,5)])
+    }
+
+    //unit cube with 8 shared corners, counter clockwise seen from outside
+    fn cube()->(Vec<Vertex>,Vec<VertexIndices>){
+        let vertices=(0..8).map(|i| Vertex::new((i&1) as f32,((i>>1)&1) as f32,((i>>2)&1) as f32,1.0,0.0,0.0)).collect();
+        let quads=[[0,2,3,1],[4,5,7,6],[0,1,5,4],[2,6,7,3],[0,4,6,2],[1,3,7,5]];
+        let vertex_indices=quads.iter().flat_map(|[a,b,c,d]| [VertexIndices::new(*a,*b,*c),VertexIndices::new(*a,*c,*d)]).collect();
+        (vertices,vertex_indices)
+    }
+
+    fn normal(vertex:&Vertex)->[f32;3]{
+        [vertex.nx,vertex.ny,vertex.nz]
+    }
+
+    #[test]
+    fn weld_duplicated_quad(){
+        let (mut vertices,mut vertex_indices)=quad();
+        weld_vertices(&mut vertices,&mut vertex_indices,0.0);
+        assert_eq!(vertices.len(),4);
+        assert_eq!(vertex_indices.iter().map(|triangle| triangle.corners()).collect::<Vec<_>>(),vec![[0,1,2],[0,2,3]]);
+
+        //vertices within epsilon are merged, and the triangle that collapses is removed
+        let (mut vertices,_)=quad();
+        vertices[3].x=1.0e-4;
+        vertices[5].y=1.0-1.0e-4;
+        let mut vertex_indices=vec![VertexIndices::new(0,1,2),VertexIndices::new(3,4,5),VertexIndices::new(0,3,1)];
+        weld_vertices(&mut vertices,&mut vertex_indices,1.0e-3);
+        assert_eq!(vertices.len(),4);
+        assert_eq!(vertex_indices.len(),2);
+
+        //differing texture coordinates keep vertices apart
+        let (mut vertices,mut vertex_indices)=quad();
+        vertices[3].u=0.5;
+        weld_vertices(&mut vertices,&mut vertex_indices,1.0e-3);
+        assert_eq!(vertices.len(),5);
+    }
+
+    #[test]
+    fn crease_split_on_cube(){
+        //right angles are creases below 90 degrees: every face gets its own corners with the face normal
+        let (mut vertices,mut vertex_indices)=cube();
+        generate_normals(&mut vertices,&mut vertex_indices,30f32.to_radians());
+        assert_eq!(vertices.len(),24);
+        for triangle in vertex_indices.iter(){
+            let [a,b,c]=triangle.corners().map(|i| &vertices[i as usize]);
+            let face_normal=glm::normalize(&glm::cross(&(position(b)-position(a)),&(position(c)-position(a))));
+            //faces point away from the center of the cube
+            assert!(glm::dot(&face_normal,&(position(a)-glm::vec3(0.5,0.5,0.5)))>0.0);
+            for vertex in [a,b,c]{
+                assert_eq!(normal(vertex),[face_normal.x,face_normal.y,face_normal.z]);
+            }
+        }
+
+        //above 90 degrees, all faces of a corner are smoothed together, towards the diagonal
+        //(faces are weighted by the area of their triangles at the corner, which is 1 or 2 halves of the face)
+        let (mut vertices,mut vertex_indices)=cube();
+        generate_normals(&mut vertices,&mut vertex_indices,100f32.to_radians());
+        assert_eq!(vertices.len(),8);
+        for vertex in vertices.iter(){
+            let diagonal=glm::normalize(&(position(vertex)-glm::vec3(0.5,0.5,0.5)));
+            let normal=glm::vec3(vertex.nx,vertex.ny,vertex.nz);
+            assert!((glm::length(&normal)-1.0).abs()<1.0e-5);
+            assert!(glm::dot(&normal,&diagonal)>=(2.0*2f32.sqrt()/3.0)-1.0e-5);
+        }
+    }
+
+    #[test]
+    fn tangents_on_mirrored_uv_quad(){
+        //two quads side by side, the right one with mirrored u, sharing the vertices on x=1
+        let corner=|x:f32,y:f32,u:f32| Vertex::new(x,y,0.0,1.0,u,y).with_normal(0.0,0.0,1.0);
+        let mut vertices=vec![
+            corner(0.0,0.0,0.0),corner(1.0,0.0,1.0),corner(2.0,0.0,0.0),
+            corner(0.0,1.0,0.0),corner(1.0,1.0,1.0),corner(2.0,1.0,0.0),
+        ];
+        let mut vertex_indices=vec![
+            VertexIndices::new(0,1,4),VertexIndices::new(0,4,3),
+            VertexIndices::new(1,2,5),VertexIndices::new(1,5,4),
+        ];
+        generate_tangents(&mut vertices,&mut vertex_indices);
+
+        //the shared vertices are split, one copy per handedness
+        assert_eq!(vertices.len(),8);
+        for (triangle_index,triangle) in vertex_indices.iter().enumerate(){
+            let expected=if triangle_index<2{[1.0,0.0,0.0,1.0]}else{[-1.0,0.0,0.0,-1.0]};
+            for vertex_index in triangle.corners(){
+                let vertex=&vertices[vertex_index as usize];
+                let tangent=[vertex.tx,vertex.ty,vertex.tz,vertex.tw];
+                assert!(tangent.iter().zip(expected.iter()).all(|(a,b)| (a-b).abs()<1.0e-5),"{:?} instead of {:?}",tangent,expected);
+            }
+        }
+        //the bitangent points along +v on both sides
+        for vertex in vertices.iter(){
+            let bitangent=glm::cross(&glm::vec3(vertex.nx,vertex.ny,vertex.nz),&glm::vec3(vertex.tx,vertex.ty,vertex.tz))*vertex.tw;
+            assert!(glm::distance(&bitangent,&glm::vec3(0.0,1.0,0.0))<1.0e-5);
+        }
+    }
+
+    //average number of vertex shader invocations per triangle, with a fifo cache of the given size
+    fn average_cache_miss_ratio(vertex_indices:&[VertexIndices],cache_size:usize)->f32{
+        let mut cache=std::collections::VecDeque::new();
+        let mut misses=0;
+        for vertex_index in vertex_indices.iter().flat_map(|triangle| triangle.corners()){
+            if !cache.contains(&vertex_index){
+                misses+=1;
+                cache.push_back(vertex_index);
+                if cache.len()>cache_size{
+                    cache.pop_front();
+                }
+            }
+        }
+        misses as f32/vertex_indices.len() as f32
+    }
+
+    #[test]
+    fn vertex_cache_order_is_a_permutation(){
+        //32x32 quad grid with its triangles in a scrambled order
+        let size=32;
+        let mut vertex_indices:Vec<VertexIndices>=(0..size*size).flat_map(|quad|{
+            let (x,y)=(quad%size,quad/size);
+            let vertex=|x:u32,y:u32| y*(size+1)+x;
+            [VertexIndices::new(vertex(x,y),vertex(x+1,y),vertex(x+1,y+1)),VertexIndices::new(vertex(x,y),vertex(x+1,y+1),vertex(x,y+1))]
+        }).collect();
+        let count=vertex_indices.len();
+        let scrambled:Vec<VertexIndices>=(0..count).map(|i| vertex_indices[i*769%count]).collect();
+        vertex_indices.copy_from_slice(&scrambled);
+
+        optimize_vertex_cache(&mut vertex_indices,((size+1)*(size+1)) as usize);
+        let mut optimized:Vec<[u32;3]>=vertex_indices.iter().map(|triangle| triangle.corners()).collect();
+        let mut original:Vec<[u32;3]>=scrambled.iter().map(|triangle| triangle.corners()).collect();
+        optimized.sort();
+        original.sort();
+        assert_eq!(optimized,original);
+
+        let before=average_cache_miss_ratio(&scrambled,16);
+        let after=average_cache_miss_ratio(&vertex_indices,16);
+        assert!(after<1.0 && after<before*0.5,"{} vertices per triangle before, {} after",before,after);
+    }
+}
@@ -0,0 +1,134 @@
+use ash::{
+    vk,
+    version::{
+        DeviceV1_0,
+    },
+    Device,
+};
+
+use crate::color::{srgb_to_linear,linear_to_srgb};
+
+//number of levels in a full mip chain, down to 1x1
+pub fn mip_level_count(width:u32,height:u32)->u32{
+    32-width.max(height).max(1).leading_zeros()
+}
+
+//size of a mip level, each level is half the size of the previous one (rounded down, at least 1)
+pub fn mip_extent(width:u32,height:u32,level:u32)->(u32,u32){
+    ((width>>level).max(1),(height>>level).max(1))
+}
+
+//source rows (or columns) covered by a texel of the next level, the last row/column of odd sizes is averaged into the previous texel
+fn covered(index:u32,size:u32,next_size:u32)->Vec<u32>{
+    (index*2..((index+1)*2).min(size)).chain(if index+1==next_size && size>next_size*2{Some(size-1)}else{None}).collect()
+}
+
+//halve an image with a box filter, channels are interleaved
+pub fn downsample(width:u32,height:u32,channels:usize,values:&[f32])->(u32,u32,Vec<f32>){
+    let (next_width,next_height)=mip_extent(width,height,1);
+    let mut next=vec![0.0f32;next_width as usize*next_height as usize*channels];
+    for y in 0..next_height{
+        let rows=covered(y,height,next_height);
+        for x in 0..next_width{
+            let columns=covered(x,width,next_width);
+            let offset=(y*next_width+x) as usize*channels;
+            for row in rows.iter(){
+                for column in columns.iter(){
+                    let source_offset=(row*width+column) as usize*channels;
+                    for channel in 0..channels{
+                        next[offset+channel]+=values[source_offset+channel];
+                    }
+                }
+            }
+            let count=(rows.len()*columns.len()) as f32;
+            for value in next[offset..offset+channels].iter_mut(){
+                *value/=count;
+            }
+        }
+    }
+    (next_width,next_height,next)
+}
+
+//halve an r8g8b8a8 image with a box filter
+//the color channels of sRGB images are averaged in linear space (alpha is always linear)
+pub fn downsample_rgba8(width:u32,height:u32,pixels:&[u8],srgb:bool)->(u32,u32,Vec<u8>){
+    let decode=|channel:usize,value:u8| if srgb && channel<3{srgb_to_linear(value as f32/255.0)}else{value as f32/255.0};
+    let encode=|channel:usize,value:f32| (if srgb && channel<3{linear_to_srgb(value)}else{value}*255.0).round() as u8;
+    let values:Vec<f32>=pixels.iter().enumerate().map(|(i,&value)| decode(i%4,value)).collect();
+    let (next_width,next_height,next)=downsample(width,height,4,&values);
+    (next_width,next_height,next.iter().enumerate().map(|(i,&value)| encode(i%4,value)).collect())
+}
+
+//levels 1..mip_levels of a mip chain (at most down to 1x1), computed on the cpu
+pub fn generate_mips_rgba8(width:u32,height:u32,pixels:&[u8],srgb:bool,mip_levels:u32)->Vec<Vec<u8>>{
+    let mut levels=Vec::new();
+    let (mut level_width,mut level_height)=(width,height);
+    for _ in 1..mip_levels.min(mip_level_count(width,height)){
+        let (next_width,next_height,next)=downsample_rgba8(level_width,level_height,levels.last().map(|level:&Vec<u8>| &level[..]).unwrap_or(pixels),srgb);
+        levels.push(next);
+        level_width=next_width;
+        level_height=next_height;
+    }
+    levels
+}
+
+//fill levels 1.. of an image from level 0 with linear blits, and transition all levels to shader read.
+//level 0 must be in TRANSFER_DST_OPTIMAL (the other levels may be undefined), the image needs TRANSFER_SRC and TRANSFER_DST usage
+pub fn record_mip_blits(device:&Device,command_buffer:vk::CommandBuffer,image:vk::Image,width:u32,height:u32,mip_levels:u32,array_layers:u32){
+    let level_range=|level:u32| vk::ImageSubresourceRange{
+        aspect_mask:vk::ImageAspectFlags::COLOR,
+        base_mip_level:level,
+        level_count:1,
+        base_array_layer:0,
+        layer_count:array_layers,
+    };
+    let barrier=|level:u32,src_access_mask:vk::AccessFlags,dst_access_mask:vk::AccessFlags,old_layout:vk::ImageLayout,new_layout:vk::ImageLayout| vk::ImageMemoryBarrier{
+        src_access_mask,
+        dst_access_mask,
+        old_layout,
+        new_layout,
+        src_queue_family_index:vk::QUEUE_FAMILY_IGNORED,
+        dst_queue_family_index:vk::QUEUE_FAMILY_IGNORED,
+        image,
+        subresource_range:level_range(level),
+        ..Default::default()
+    };
+    let offset=|(width,height):(u32,u32)| vk::Offset3D{
+        x:width as i32,
+        y:height as i32,
+        z:1,
+    };
+    let layers=|level:u32| vk::ImageSubresourceLayers{
+        aspect_mask:vk::ImageAspectFlags::COLOR,
+        mip_level:level,
+        base_array_layer:0,
+        layer_count:array_layers,
+    };
+
+    for level in 1..mip_levels{
+        let source=level-1;
+        let to_transfer=[
+            //previous level has been written, and is now read
+            barrier(source,vk::AccessFlags::TRANSFER_WRITE,vk::AccessFlags::TRANSFER_READ,vk::ImageLayout::TRANSFER_DST_OPTIMAL,vk::ImageLayout::TRANSFER_SRC_OPTIMAL),
+            barrier(level,vk::AccessFlags::empty(),vk::AccessFlags::TRANSFER_WRITE,vk::ImageLayout::UNDEFINED,vk::ImageLayout::TRANSFER_DST_OPTIMAL),
+        ];
+        let image_blit=vk::ImageBlit{
+            src_subresource:layers(source),
+            src_offsets:[vk::Offset3D{x:0,y:0,z:0},offset(mip_extent(width,height,source))],
+            dst_subresource:layers(level),
+            dst_offsets:[vk::Offset3D{x:0,y:0,z:0},offset(mip_extent(width,height,level))],
+        };
+        let to_shader_read=barrier(source,vk::AccessFlags::TRANSFER_READ,vk::AccessFlags::SHADER_READ,vk::ImageLayout::TRANSFER_SRC_OPTIMAL,vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        unsafe{
+            device.cmd_pipeline_barrier(command_buffer,vk::PipelineStageFlags::TRANSFER,vk::PipelineStageFlags::TRANSFER,vk::DependencyFlags::empty(),&[],&[],&to_transfer);
+            device.cmd_blit_image(command_buffer,image,vk::ImageLayout::TRANSFER_SRC_OPTIMAL,image,vk::ImageLayout::TRANSFER_DST_OPTIMAL,&[image_blit],vk::Filter::LINEAR);
+            device.cmd_pipeline_barrier(command_buffer,vk::PipelineStageFlags::TRANSFER,vk::PipelineStageFlags::FRAGMENT_SHADER,vk::DependencyFlags::empty(),&[],&[],&[to_shader_read]);
+        }
+    }
+
+    //last level is only written
+    let last_level=barrier(mip_levels-1,vk::AccessFlags::TRANSFER_WRITE,vk::AccessFlags::SHADER_READ,vk::ImageLayout::TRANSFER_DST_OPTIMAL,vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    unsafe{
+        device.cmd_pipeline_barrier(command_buffer,vk::PipelineStageFlags::TRANSFER,vk::PipelineStageFlags::FRAGMENT_SHADER,vk::DependencyFlags::empty(),&[],&[],&[last_level]);
+    }
+}
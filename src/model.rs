@@ -0,0 +1,65 @@
+use crate::{Mesh,Image};
+use crate::bounds::BoundingBox;
+use crate::sampler::SamplerDescription;
+
+//material of a model file (e.g. from an mtl library), with its textures loaded through the decoder texture cache
+#[derive(Debug,Clone)]
+pub struct ModelMaterial{
+    pub name:String,
+    pub diffuse_color:glm::Vec3,
+    pub alpha:f32,//1 is opaque
+    pub transparent:bool,//drawn with alpha blending
+    //diffuse map, or a 1x1 texture of the diffuse color if the material has none
+    pub diffuse_texture:std::sync::Arc<Image>,
+    pub sampler:SamplerDescription,
+    //linear textures, not used by the current shaders
+    pub normal_map:Option<std::sync::Arc<Image>>,
+    pub specular_map:Option<std::sync::Arc<Image>>,
+}
+
+//part of a model drawn with a single material
+#[derive(Debug,Clone)]
+pub struct Submesh{
+    pub name:String,//object name, followed by group names if the geometry is grouped
+    pub material_name:Option<String>,
+    pub material:Option<std::sync::Arc<ModelMaterial>>,//None if the material name is not found in any material library
+    pub mesh:std::sync::Arc<Mesh>,
+}
+
+//node of the model hierarchy
+#[derive(Debug,Clone)]
+pub struct ModelNode{
+    pub name:String,
+    pub local_transform:glm::Mat4,//relative to parent node
+    pub submeshes:Vec<usize>,//indices into Model::submeshes
+    pub children:Vec<usize>,//indices into Model::nodes
+}
+
+//all meshes loaded from a single model file
+#[derive(Debug,Clone)]
+pub struct Model{
+    pub submeshes:Vec<Submesh>,
+    //node hierarchy, if the file has one (otherwise all submeshes are placed at the model origin)
+    pub nodes:Vec<ModelNode>,
+    pub root_nodes:Vec<usize>,
+    //bounds of all submeshes, in model space (ignoring node transforms)
+    pub bounding_box:BoundingBox,
+}
+impl Model{
+    pub fn new(submeshes:Vec<Submesh>)->Self{
+        Self::with_nodes(submeshes,Vec::new(),Vec::new())
+    }
+
+    pub fn with_nodes(submeshes:Vec<Submesh>,nodes:Vec<ModelNode>,root_nodes:Vec<usize>)->Self{
+        let bounding_box=BoundingBox::from_points(submeshes.iter().flat_map(|submesh|{
+            let bounding_box=submesh.mesh.bounding_box;
+            vec![bounding_box.min,bounding_box.max]
+        }));
+        Self{
+            submeshes,
+            nodes,
+            root_nodes,
+            bounding_box,
+        }
+    }
+}
@@ -67,25 +67,146 @@ use ash::{
     extensions,
 };
 
-use crate::{Object,GraphicsPipeline};
+use crate::{Object,GraphicsPipeline,Vertex,Image,Material,BlendMode,Shading,Camera,IntegratedBuffer,Model,ModelMaterial};
+use crate::decoder::create_mapped_buffer;
+use crate::sampler::{SamplerDescription,SamplerCache};
+use crate::instancing::{InstanceData,InstanceBatch,InstanceGroup};
+use crate::render_queue::{DrawItem,RenderQueue,FrameStatistics};
+use crate::bounds::Frustum;
+use crate::scene::Scene;
+use crate::render_target::{Attachment,TransientAttachments,RenderTarget};
+use crate::atlas::Sprite;
+use crate::lighting::{Lights,LightsUniform};
+use crate::vertex_layout::{self,VertexLayout,VertexLayoutDescription,ColorVertex,SpriteVertex};
+
+//number of material descriptor sets per descriptor pool, another pool is created when all are full
+pub const MATERIALS_PER_DESCRIPTOR_POOL:u32=256;
+
+//materials are cached by texture image view, sampler, blend mode and shading
+pub type MaterialKey=(vk::ImageView,SamplerDescription,BlendMode,Shading);
+
+//initial number of instances the instance buffer holds, over all instance batches of a frame
+//the buffer grows when a frame draws more
+pub const INITIAL_INSTANCES:u64=16*1024;
+
+//maximum number of sprites drawn per frame, each takes 6 vertices of the sprite buffer
+pub const MAX_SPRITES:u64=4*1024;
+
+//maximum number of debug line vertices drawn per frame, two per line
+pub const MAX_LINE_VERTICES:u64=32*1024;
+
+//3d pipelines that exist for every vertex layout, differing in shaders and vertex input
+//the skybox pipeline has no vertex input, and the debug lines pipeline draws lines of ColorVertex, both exist only once
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub enum PipelineVariant{
+    Unlit,
+    Lit,
+    Instanced,
+    Skybox,
+    Lines,
+}
+impl PipelineVariant{
+    //shader input locations read from the mesh vertices
+    pub fn vertex_locations(&self)->&'static [u32]{
+        match self{
+            PipelineVariant::Unlit | PipelineVariant::Instanced=>&[vertex_layout::POSITION,vertex_layout::TEXCOORD],
+            PipelineVariant::Lit=>&[vertex_layout::POSITION,vertex_layout::TEXCOORD,vertex_layout::NORMAL,vertex_layout::COLOR],
+            PipelineVariant::Skybox=>&[],
+            PipelineVariant::Lines=>&[vertex_layout::POSITION,vertex_layout::COLOR],
+        }
+    }
+
+    //locations that a mesh vertex layout must contain, the others are read from the default attributes buffer
+    //(lit meshes without a color are drawn white)
+    pub fn required_vertex_locations(&self)->&'static [u32]{
+        match self{
+            PipelineVariant::Unlit | PipelineVariant::Instanced=>&[vertex_layout::POSITION],
+            PipelineVariant::Lit=>&[vertex_layout::POSITION,vertex_layout::TEXCOORD,vertex_layout::NORMAL],
+            PipelineVariant::Skybox=>&[],
+            PipelineVariant::Lines=>&[vertex_layout::POSITION,vertex_layout::COLOR],
+        }
+    }
+}
 
 pub struct Painter{
     pub allocation_callbacks:Option<vk::AllocationCallbacks>,
 
     pub device:Device,
 
+    pub device_memory_properties:vk::PhysicalDeviceMemoryProperties,
+
     pub swapchain_surface_format:vk::SurfaceFormatKHR,
+    pub depth_format:vk::Format,
+
+    pub supported_sample_counts:vk::SampleCountFlags,//sample counts supported for both color and depth attachments
+    pub sample_count:vk::SampleCountFlags,
 
-    pub sampler:vk::Sampler,
+    pub samplers:SamplerCache,
 
     pub descriptor_set_layout:vk::DescriptorSetLayout,//list of descriptor types ("descriptorSetLayoutBindings"), the shader stages they are used in and their type
-    pub descriptor_set:vk::DescriptorSet,//contains handles to descriptors of types specified in layout
-    pub descriptor_pool:vk::DescriptorPool,//allocate descriptors
+    pub descriptor_pool:vk::DescriptorPool,//allocate the lights descriptor set
+    //pools that material descriptor sets are allocated from, with the number of sets allocated from each
+    pub material_descriptor_pools:Vec<(vk::DescriptorPool,u32)>,
+
+    //materials by texture image view, sampler, blend mode and shading, each with its own descriptor set (contains handles to descriptors of types specified in layout)
+    //stored with the frame they were last requested in, see release_unused_materials
+    pub materials:std::collections::HashMap<MaterialKey,(std::sync::Arc<Material>,u64)>,
+    //number of frames drawn so far
+    pub frame:u64,
 
     pub render_pass:vk::RenderPass,
 
-    pub graphics_pipeline_2d:GraphicsPipeline,
-    pub graphics_pipeline_3d:GraphicsPipeline,
+    pub vertex_layout:VertexLayoutDescription,//layout of decoder::Vertex, which the 3d pipelines below are created for
+
+    pub graphics_pipeline_2d:GraphicsPipeline,//draws sprites, with vertex_layout::SpriteVertex
+    pub graphics_pipeline_3d:GraphicsPipeline,//draws opaque geometry
+    pub graphics_pipelines_3d_blended:Vec<vk::Pipeline>,//same layout and shaders as graphics_pipeline_3d, one per transparent blend mode
+
+    pub graphics_pipeline_3d_instanced:GraphicsPipeline,//draws opaque instance batches
+    pub graphics_pipelines_3d_instanced_blended:Vec<vk::Pipeline>,
+
+    pub graphics_pipeline_3d_lit:GraphicsPipeline,//draws opaque geometry with lit materials
+    pub graphics_pipelines_3d_lit_blended:Vec<vk::Pipeline>,
+
+    pub graphics_pipeline_skybox:GraphicsPipeline,//draws the skybox cubemap behind all geometry
+
+    pub graphics_pipeline_lines:GraphicsPipeline,//draws debug lines, with vertex_layout::ColorVertex
+
+    //pipelines for meshes with other vertex layouts, one per blend mode
+    pub graphics_pipelines_by_vertex_layout:std::collections::HashMap<(VertexLayoutDescription,PipelineVariant),Vec<vk::Pipeline>>,
+
+    //host visible buffer that instance data is written to every frame
+    //only one frame is in flight at a time (see Manager::frame_sync_fence), so one buffer is enough
+    pub instance_buffer:IntegratedBuffer,
+    pub instance_buffer_pointer:*mut libc::c_void,//persistently mapped
+
+    //lights used by lit materials, written to the lights buffer every frame (same as the instance buffer)
+    pub lights:Lights,
+    pub lights_descriptor_set_layout:vk::DescriptorSetLayout,
+    pub lights_descriptor_set:vk::DescriptorSet,
+    pub lights_buffer:IntegratedBuffer,
+    pub lights_buffer_pointer:*mut libc::c_void,//persistently mapped
+
+    //quads of the sprites drawn in a frame, written every frame (same as the instance buffer)
+    pub sprite_buffer:IntegratedBuffer,
+    pub sprite_buffer_pointer:*mut libc::c_void,//persistently mapped
+
+    //debug lines drawn by the next draw, as pairs of vertices in world space, cleared after drawing
+    pub lines:Vec<ColorVertex>,
+    //vertices of the debug lines drawn in a frame, written every frame (same as the instance buffer)
+    pub line_buffer:IntegratedBuffer,
+    pub line_buffer_pointer:*mut libc::c_void,//persistently mapped
+
+    //values of attributes that a mesh vertex layout does not contain (see vertex_layout::DEFAULT_ATTRIBUTE_VALUES), bound for the whole frame
+    pub default_attributes_buffer:IntegratedBuffer,
+
+    pub camera:Camera,
+
+    //material with the skybox cubemap, drawn before all geometry if set
+    pub skybox:Option<std::sync::Arc<Material>>,
+
+    pub render_queue:RenderQueue,//reused every frame to avoid reallocation
+    pub statistics:FrameStatistics,//binds and draws recorded for the last frame
 
     pub rendering_done:vk::Semaphore,
 
@@ -97,13 +218,40 @@ pub struct Painter{
 impl Drop for Painter{
     fn drop(&mut self){
         unsafe{
-            self.device.destroy_sampler(self.sampler,self.get_allocation_callbacks());
+            self.samplers.destroy(&self.device,self.allocation_callbacks.as_ref());
 
             self.device.destroy_descriptor_set_layout(self.descriptor_set_layout, self.get_allocation_callbacks());
+            self.device.destroy_descriptor_set_layout(self.lights_descriptor_set_layout, self.get_allocation_callbacks());
 
             self.device.destroy_descriptor_pool(self.descriptor_pool, self.get_allocation_callbacks());
+            for (descriptor_pool,_) in self.material_descriptor_pools.iter(){
+                self.device.destroy_descriptor_pool(*descriptor_pool, self.get_allocation_callbacks());
+            }
+
+            self.device.unmap_memory(self.instance_buffer.memory);
+            self.device.destroy_buffer(self.instance_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.instance_buffer.memory, self.get_allocation_callbacks());
+
+            self.device.unmap_memory(self.lights_buffer.memory);
+            self.device.destroy_buffer(self.lights_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.lights_buffer.memory, self.get_allocation_callbacks());
+
+            self.device.unmap_memory(self.sprite_buffer.memory);
+            self.device.destroy_buffer(self.sprite_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.sprite_buffer.memory, self.get_allocation_callbacks());
+
+            self.device.unmap_memory(self.line_buffer.memory);
+            self.device.destroy_buffer(self.line_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.line_buffer.memory, self.get_allocation_callbacks());
 
-            for pipeline in &[&self.graphics_pipeline_2d,&self.graphics_pipeline_3d]{
+            self.device.unmap_memory(self.default_attributes_buffer.memory);
+            self.device.destroy_buffer(self.default_attributes_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.default_attributes_buffer.memory, self.get_allocation_callbacks());
+
+            for pipeline in self.graphics_pipelines_3d_blended.iter().chain(self.graphics_pipelines_3d_instanced_blended.iter()).chain(self.graphics_pipelines_3d_lit_blended.iter()).chain(self.graphics_pipelines_by_vertex_layout.values().flatten()){
+                self.device.destroy_pipeline(*pipeline, self.get_allocation_callbacks());
+            }
+            for pipeline in &[&self.graphics_pipeline_2d,&self.graphics_pipeline_3d,&self.graphics_pipeline_3d_instanced,&self.graphics_pipeline_3d_lit,&self.graphics_pipeline_skybox,&self.graphics_pipeline_lines]{
                 self.device.destroy_pipeline(pipeline.pipeline, self.get_allocation_callbacks());
                 self.device.destroy_pipeline_layout(pipeline.layout, self.get_allocation_callbacks());
                 
@@ -123,7 +271,987 @@ impl Painter{
     fn get_allocation_callbacks(&self)->Option<&vk::AllocationCallbacks>{
         self.allocation_callbacks.as_ref()
     }
-    pub fn draw(&mut self,framebuffer:vk::Framebuffer,window_extent:vk::Extent2D,objects:&Vec<Object>,done:vk::Semaphore){
+
+    //set number of samples used for rendering, clamped to supported sample counts, and rebuild render pass and pipelines
+    //caller must make sure the device is idle, and recreate all framebuffers (and their attachments) after this
+    pub fn set_sample_count(&mut self,requested_samples:u32)->vk::SampleCountFlags{
+        self.sample_count=crate::render_target::clamp_sample_count(requested_samples,self.supported_sample_counts);
+
+        unsafe{
+            self.device.destroy_pipeline(self.graphics_pipeline_2d.pipeline,self.get_allocation_callbacks());
+            self.device.destroy_pipeline(self.graphics_pipeline_3d.pipeline,self.get_allocation_callbacks());
+            self.device.destroy_pipeline(self.graphics_pipeline_3d_instanced.pipeline,self.get_allocation_callbacks());
+            self.device.destroy_pipeline(self.graphics_pipeline_3d_lit.pipeline,self.get_allocation_callbacks());
+            self.device.destroy_pipeline(self.graphics_pipeline_skybox.pipeline,self.get_allocation_callbacks());
+            self.device.destroy_pipeline(self.graphics_pipeline_lines.pipeline,self.get_allocation_callbacks());
+            for pipeline in self.graphics_pipelines_3d_blended.iter().chain(self.graphics_pipelines_3d_instanced_blended.iter()).chain(self.graphics_pipelines_3d_lit_blended.iter()).chain(self.graphics_pipelines_by_vertex_layout.values().flatten()){
+                self.device.destroy_pipeline(*pipeline,self.get_allocation_callbacks());
+            }
+            self.device.destroy_render_pass(self.render_pass,self.get_allocation_callbacks());
+        }
+        //recreated when used again
+        self.graphics_pipelines_by_vertex_layout.clear();
+
+        self.render_pass=self.create_render_pass();
+
+        let mut graphics_pipelines=self.create_graphics_pipelines();
+        let blend_mode_count=BlendMode::ALL.len();
+        let graphics_pipelines_3d_lit_blended=graphics_pipelines.split_off(2+2*blend_mode_count);
+        self.graphics_pipeline_3d_lit.pipeline=graphics_pipelines_3d_lit_blended[0];
+        self.graphics_pipelines_3d_lit_blended=graphics_pipelines_3d_lit_blended[1..].to_vec();
+        let graphics_pipelines_3d_instanced_blended=graphics_pipelines.split_off(2+blend_mode_count);
+        self.graphics_pipeline_3d_instanced.pipeline=graphics_pipelines_3d_instanced_blended[0];
+        self.graphics_pipelines_3d_instanced_blended=graphics_pipelines_3d_instanced_blended[1..].to_vec();
+        let graphics_pipelines_3d_blended=graphics_pipelines.split_off(2);
+        self.graphics_pipeline_3d.pipeline=graphics_pipelines_3d_blended[0];
+        self.graphics_pipelines_3d_blended=graphics_pipelines_3d_blended[1..].to_vec();
+        self.graphics_pipeline_2d.pipeline=graphics_pipelines[0];
+        //drawn first without depth test and depth writes, so all geometry is drawn over it
+        self.graphics_pipeline_skybox.pipeline=self.create_graphics_pipelines_for(&self.graphics_pipeline_skybox,&self.vertex_layout,PipelineVariant::Skybox,false,&[BlendMode::Opaque])[0];
+        //depth tested, so that geometry in front of debug lines hides them
+        self.graphics_pipeline_lines.pipeline=self.create_graphics_pipelines_for(&self.graphics_pipeline_lines,&ColorVertex::layout(),PipelineVariant::Lines,true,&[BlendMode::Opaque])[0];
+
+        self.sample_count
+    }
+
+    //attachment 0 is the final color image (swapchain or offscreen image), attachment 1 is depth
+    //if multisampling is enabled, attachment 2 is the multisampled color image, which is resolved into attachment 0
+    fn create_render_pass(&self)->vk::RenderPass{
+        let multisampled=self.sample_count!=vk::SampleCountFlags::TYPE_1;
+
+        let mut render_pass_attachment_descriptions=vec![
+            vk::AttachmentDescription{
+                format:self.swapchain_surface_format.format,
+                samples:vk::SampleCountFlags::TYPE_1,
+                load_op:if multisampled{
+                    vk::AttachmentLoadOp::DONT_CARE//is fully overwritten by resolve
+                }else{
+                    vk::AttachmentLoadOp::CLEAR
+                },
+                store_op:vk::AttachmentStoreOp::STORE,
+                //previous contents are never loaded, so new offscreen images need no transition before their first use
+                initial_layout:vk::ImageLayout::UNDEFINED,
+                final_layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            },
+            vk::AttachmentDescription{
+                format:self.depth_format,
+                samples:self.sample_count,
+                load_op:vk::AttachmentLoadOp::CLEAR,
+                store_op:vk::AttachmentStoreOp::DONT_CARE,
+                stencil_load_op:vk::AttachmentLoadOp::DONT_CARE,
+                stencil_store_op:vk::AttachmentStoreOp::DONT_CARE,
+                initial_layout:vk::ImageLayout::UNDEFINED,
+                final_layout:vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            },
+        ];
+        if multisampled{
+            render_pass_attachment_descriptions.push(vk::AttachmentDescription{
+                format:self.swapchain_surface_format.format,
+                samples:self.sample_count,
+                load_op:vk::AttachmentLoadOp::CLEAR,
+                store_op:vk::AttachmentStoreOp::DONT_CARE,//only the resolved image is kept
+                initial_layout:vk::ImageLayout::UNDEFINED,
+                final_layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ..Default::default()
+            });
+        }
+        let render_pass_subpass_color_attachment_references=vec![
+            vk::AttachmentReference{
+                attachment:if multisampled{2}else{0},
+                layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            }
+        ];
+        let render_pass_subpass_resolve_attachment_references=vec![
+            vk::AttachmentReference{
+                attachment:0,
+                layout:vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            }
+        ];
+        let render_pass_subpass_depth_attachment_reference=vk::AttachmentReference{
+            attachment:1,
+            layout:vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+        let render_pass_subpass_descriptions=vec![
+            vk::SubpassDescription{
+                pipeline_bind_point:vk::PipelineBindPoint::GRAPHICS,
+                color_attachment_count:render_pass_subpass_color_attachment_references.len() as u32,
+                p_color_attachments:render_pass_subpass_color_attachment_references.as_ptr(),
+                p_resolve_attachments:if multisampled{
+                    render_pass_subpass_resolve_attachment_references.as_ptr()
+                }else{
+                    std::ptr::null()
+                },
+                p_depth_stencil_attachment:&render_pass_subpass_depth_attachment_reference,
+                ..Default::default()
+            }
+        ];
+        //the transient attachments are shared between frames, so wait for writes from the last frame to finish
+        let render_pass_subpass_dependencies=vec![
+            vk::SubpassDependency{
+                src_subpass:vk::SUBPASS_EXTERNAL,
+                dst_subpass:0,
+                src_stage_mask:vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                dst_stage_mask:vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                src_access_mask:vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask:vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ..Default::default()
+            }
+        ];
+        let render_pass_create_info=vk::RenderPassCreateInfo{
+            attachment_count:render_pass_attachment_descriptions.len() as u32,
+            p_attachments:render_pass_attachment_descriptions.as_ptr(),
+            subpass_count:render_pass_subpass_descriptions.len() as u32,
+            p_subpasses:render_pass_subpass_descriptions.as_ptr(),
+            dependency_count:render_pass_subpass_dependencies.len() as u32,
+            p_dependencies:render_pass_subpass_dependencies.as_ptr(),
+            ..Default::default()
+        };
+
+        unsafe{
+            self.device.create_render_pass(&render_pass_create_info,self.get_allocation_callbacks())
+        }.unwrap()
+    }
+
+    //vertex input state of a pipeline variant for meshes with the given vertex layout
+    //the returned create info points into the returned descriptions, which must be kept alive while it is used
+    fn vertex_input_state(variant:PipelineVariant,vertex_layout:&VertexLayoutDescription)->(Vec<vk::VertexInputBindingDescription>,Vec<vk::VertexInputAttributeDescription>){
+        //skybox vertices are generated in the vertex shader
+        if variant==PipelineVariant::Skybox{
+            return (Vec::new(),Vec::new());
+        }
+        let mut vertex_binding_descriptions=vec![
+            vertex_layout.binding_description(0,vk::VertexInputRate::VERTEX),
+        ];
+        let mut vertex_attribute_descriptions=vertex_layout.attribute_descriptions(0,variant.vertex_locations());
+        //attributes missing from the layout are read from the default attributes buffer
+        if !vertex_layout.provides(variant.vertex_locations()){
+            vertex_binding_descriptions.push(vk::VertexInputBindingDescription{
+                binding:vertex_layout::DEFAULT_ATTRIBUTES_BINDING,
+                stride:0,
+                input_rate:vk::VertexInputRate::VERTEX,
+            });
+        }
+        //instanced pipelines read vertices from binding 0, and instance data from binding 1
+        if variant==PipelineVariant::Instanced{
+            vertex_binding_descriptions.push(vk::VertexInputBindingDescription{
+                binding: 1,
+                stride: std::mem::size_of::<InstanceData>() as u32,
+                input_rate:vk::VertexInputRate::INSTANCE,
+            });
+            //model matrix is passed as 4 column vectors
+            for column in 0..4{
+                vertex_attribute_descriptions.push(vk::VertexInputAttributeDescription{
+                    location:2+column,
+                    binding:1,
+                    format:vk::Format::R32G32B32A32_SFLOAT,
+                    offset:(offset_of!(InstanceData,transform)+column as usize*std::mem::size_of::<glm::Vec4>()) as u32,
+                });
+            }
+            vertex_attribute_descriptions.push(vk::VertexInputAttributeDescription{
+                location:6,
+                binding:1,
+                format:vk::Format::R32G32B32A32_SFLOAT,
+                offset:offset_of!(InstanceData,tint) as u32,
+            });
+            vertex_attribute_descriptions.push(vk::VertexInputAttributeDescription{
+                location:7,
+                binding:1,
+                format:vk::Format::R32_UINT,
+                offset:offset_of!(InstanceData,material_index) as u32,
+            });
+        }
+        (vertex_binding_descriptions,vertex_attribute_descriptions)
+    }
+
+    //create one pipeline per given blend mode for the current render pass and sample count
+    //without depth test, geometry is drawn in submission order
+    fn create_graphics_pipelines_for(&self,graphics_pipeline:&GraphicsPipeline,vertex_layout:&VertexLayoutDescription,variant:PipelineVariant,depth_test:bool,blend_modes:&[BlendMode])->Vec<vk::Pipeline>{
+        let shader_entry_fn_name="main\0".as_ptr() as *const i8;
+
+        let shader_stage_create_infos=vec![
+            vk::PipelineShaderStageCreateInfo{
+                stage:vk::ShaderStageFlags::VERTEX,
+                module:graphics_pipeline.vertex,
+                p_name:shader_entry_fn_name,
+                ..Default::default()
+            },
+            vk::PipelineShaderStageCreateInfo{
+                stage:vk::ShaderStageFlags::FRAGMENT,
+                module:graphics_pipeline.fragment,
+                p_name:shader_entry_fn_name,
+                ..Default::default()
+            }
+        ];
+        let (vertex_binding_descriptions,vertex_attribute_descriptions)=Self::vertex_input_state(variant,vertex_layout);
+        let vertex_input_state_create_info=vk::PipelineVertexInputStateCreateInfo{
+            vertex_binding_description_count:vertex_binding_descriptions.len() as u32,
+            p_vertex_binding_descriptions:vertex_binding_descriptions.as_ptr(),
+            vertex_attribute_description_count:vertex_attribute_descriptions.len() as u32,
+            p_vertex_attribute_descriptions:vertex_attribute_descriptions.as_ptr(),
+            ..Default::default()
+        };
+        let input_assembly_state_create_info=vk::PipelineInputAssemblyStateCreateInfo{
+            topology:if variant==PipelineVariant::Lines{
+                vk::PrimitiveTopology::LINE_LIST
+            }else{
+                vk::PrimitiveTopology::TRIANGLE_LIST
+            },
+            primitive_restart_enable:false as u32,
+            ..Default::default()
+        };
+        let viewport_state_create_info=vk::PipelineViewportStateCreateInfo{
+            viewport_count:1,
+            //p_viewports:&viewport,//this is ignored if viewports are dynamic
+            scissor_count:1,//must match viewport count
+            //p_scissors:&scissor,//ignored if scissors are dynamic
+            ..Default::default()
+        };
+        let rasterization_state_create_info=vk::PipelineRasterizationStateCreateInfo{
+            depth_clamp_enable:false as u32,
+            rasterizer_discard_enable:false as u32,
+            polygon_mode:vk::PolygonMode::FILL,
+            //the skybox cube is seen from the inside, and lines have no front face
+            cull_mode:if variant==PipelineVariant::Skybox || variant==PipelineVariant::Lines{
+                vk::CullModeFlags::NONE
+            }else{
+                vk::CullModeFlags::BACK
+            },
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_bias_enable:false as u32,
+            line_width:1.0,//specs state this must be 1.0 if wide lines feature is not enabled
+            ..Default::default()
+        };
+        let multisample_state_create_info=vk::PipelineMultisampleStateCreateInfo{
+            rasterization_samples:self.sample_count,
+            sample_shading_enable:false as u32,
+            alpha_to_coverage_enable:false as u32,
+            alpha_to_one_enable:false as u32,
+            ..Default::default()
+        };
+        let depth_stencil_state_create_info=vk::PipelineDepthStencilStateCreateInfo{
+            depth_test_enable:depth_test as u32,
+            depth_write_enable:depth_test as u32,
+            depth_compare_op:if depth_test{
+                vk::CompareOp::LESS
+            }else{
+                vk::CompareOp::ALWAYS
+            },
+            depth_bounds_test_enable:false as u32,
+            stencil_test_enable:false as u32,
+            ..Default::default()
+        };
+        //transparent geometry is occluded by opaque geometry, but does not occlude anything itself
+        let depth_stencil_state_create_info_transparent=vk::PipelineDepthStencilStateCreateInfo{
+            depth_write_enable:false as u32,
+            ..depth_stencil_state_create_info
+        };
+        let color_blend_attachment_states:Vec<vk::PipelineColorBlendAttachmentState>=blend_modes.iter().map(|blend_mode| blend_mode.color_blend_attachment_state()).collect();
+        let color_blend_states:Vec<vk::PipelineColorBlendStateCreateInfo>=color_blend_attachment_states.iter().map(|color_blend_attachment_state|{
+            vk::PipelineColorBlendStateCreateInfo{
+                logic_op_enable:false as u32,
+                logic_op:vk::LogicOp::COPY,
+                attachment_count:1,
+                p_attachments:color_blend_attachment_state,
+                blend_constants:[0.0,0.0,0.0,0.0,],
+                ..Default::default()
+            }
+        }).collect();
+        let dynamic_states=vec![
+            vk::DynamicState::VIEWPORT,
+            vk::DynamicState::SCISSOR,
+        ];
+        let dynamic_state_create_info=vk::PipelineDynamicStateCreateInfo{
+            dynamic_state_count:dynamic_states.len() as u32,
+            p_dynamic_states:dynamic_states.as_ptr(),
+            ..Default::default()
+        };
+
+        let graphics_pipeline_create_infos:Vec<vk::GraphicsPipelineCreateInfo>=blend_modes.iter().zip(color_blend_states.iter()).map(|(blend_mode,color_blend_state)|{
+            vk::GraphicsPipelineCreateInfo{
+                stage_count:shader_stage_create_infos.len() as u32,
+                p_stages:shader_stage_create_infos.as_ptr(),
+                p_vertex_input_state:&vertex_input_state_create_info,
+                p_input_assembly_state:&input_assembly_state_create_info,
+                p_viewport_state:&viewport_state_create_info,
+                p_rasterization_state:&rasterization_state_create_info,
+                p_multisample_state:&multisample_state_create_info,
+                p_depth_stencil_state:if blend_mode.is_transparent(){
+                    &depth_stencil_state_create_info_transparent
+                }else{
+                    &depth_stencil_state_create_info
+                },
+                p_color_blend_state:color_blend_state,
+                p_dynamic_state:&dynamic_state_create_info,
+                layout:graphics_pipeline.layout,
+                render_pass:self.render_pass,
+                subpass:0,
+                base_pipeline_index:-1,
+                base_pipeline_handle:vk::Pipeline::null(),
+                ..Default::default()
+            }
+        }).collect();
+
+        unsafe{
+            self.device.create_graphics_pipelines(vk::PipelineCache::null(), &graphics_pipeline_create_infos,self.get_allocation_callbacks())
+        }.unwrap()
+    }
+
+    //create 2d pipeline (alpha blended, for sprites), then one 3d pipeline per blend mode (in order of BlendMode::ALL), then one instanced 3d pipeline per blend mode,
+    //then one lit 3d pipeline per blend mode, the 3d pipelines for the default vertex layout
+    fn create_graphics_pipelines(&self)->Vec<vk::Pipeline>{
+        let mut graphics_pipelines=self.create_graphics_pipelines_for(&self.graphics_pipeline_2d,&SpriteVertex::layout(),PipelineVariant::Unlit,false,&[BlendMode::Alpha]);
+        for variant in &[PipelineVariant::Unlit,PipelineVariant::Instanced,PipelineVariant::Lit]{
+            graphics_pipelines.extend(self.create_graphics_pipelines_for(self.graphics_pipeline(*variant),&self.vertex_layout,*variant,true,&BlendMode::ALL));
+        }
+        graphics_pipelines
+    }
+
+    fn graphics_pipeline(&self,variant:PipelineVariant)->&GraphicsPipeline{
+        match variant{
+            PipelineVariant::Unlit=>&self.graphics_pipeline_3d,
+            PipelineVariant::Lit=>&self.graphics_pipeline_3d_lit,
+            PipelineVariant::Instanced=>&self.graphics_pipeline_3d_instanced,
+            PipelineVariant::Skybox=>&self.graphics_pipeline_skybox,
+            PipelineVariant::Lines=>&self.graphics_pipeline_lines,
+        }
+    }
+
+    //3d pipeline (and its layout) for meshes with the given vertex layout
+    //pipelines for vertex layouts other than the default one are created when first used
+    fn graphics_pipeline_for(&mut self,variant:PipelineVariant,vertex_layout:&VertexLayoutDescription,blend_mode:BlendMode)->(vk::Pipeline,vk::PipelineLayout){
+        if variant==PipelineVariant::Skybox || variant==PipelineVariant::Lines{
+            panic!("the {:?} pipeline does not draw meshes",variant);
+        }
+        let pipeline_layout=self.graphics_pipeline(variant).layout;
+        if *vertex_layout==self.vertex_layout{
+            let pipeline=match variant{
+                PipelineVariant::Unlit=>self.graphics_pipeline_3d(blend_mode),
+                PipelineVariant::Lit=>self.graphics_pipeline_3d_lit(blend_mode),
+                _=>self.graphics_pipeline_3d_instanced(blend_mode),
+            };
+            return (pipeline,pipeline_layout);
+        }
+        if !vertex_layout.provides(variant.required_vertex_locations()){
+            panic!("mesh vertex layout does not provide all attributes required by the {:?} pipeline",variant);
+        }
+        let key=(vertex_layout.clone(),variant);
+        if !self.graphics_pipelines_by_vertex_layout.contains_key(&key){
+            let graphics_pipelines=self.create_graphics_pipelines_for(self.graphics_pipeline(variant),vertex_layout,variant,true,&BlendMode::ALL);
+            self.graphics_pipelines_by_vertex_layout.insert(key.clone(),graphics_pipelines);
+        }
+        (self.graphics_pipelines_by_vertex_layout[&key][blend_mode.index()],pipeline_layout)
+    }
+
+    //3d pipeline used to draw geometry with the given blend mode
+    pub fn graphics_pipeline_3d(&self,blend_mode:BlendMode)->vk::Pipeline{
+        match blend_mode{
+            BlendMode::Opaque=>self.graphics_pipeline_3d.pipeline,
+            _=>self.graphics_pipelines_3d_blended[blend_mode.index()-1],
+        }
+    }
+
+    //lit 3d pipeline used to draw geometry with the given blend mode
+    pub fn graphics_pipeline_3d_lit(&self,blend_mode:BlendMode)->vk::Pipeline{
+        match blend_mode{
+            BlendMode::Opaque=>self.graphics_pipeline_3d_lit.pipeline,
+            _=>self.graphics_pipelines_3d_lit_blended[blend_mode.index()-1],
+        }
+    }
+
+    //allocate a material descriptor set from the first pool that is not full, creating a new pool if all are
+    fn allocate_material_descriptor_set(&mut self)->(vk::DescriptorPool,vk::DescriptorSet){
+        let pool_index=match self.material_descriptor_pools.iter().position(|(_,allocated)| *allocated<MATERIALS_PER_DESCRIPTOR_POOL){
+            Some(pool_index)=>pool_index,
+            None=>{
+                let descriptor_pool_sizes=[
+                    vk::DescriptorPoolSize{
+                        ty:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                        descriptor_count:MATERIALS_PER_DESCRIPTOR_POOL,
+                    },
+                ];
+                let descriptor_pool_create_info=vk::DescriptorPoolCreateInfo{
+                    //sets of released materials are freed individually
+                    flags:vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+                    max_sets:MATERIALS_PER_DESCRIPTOR_POOL,
+                    pool_size_count:descriptor_pool_sizes.len() as u32,
+                    p_pool_sizes:descriptor_pool_sizes.as_ptr(),
+                    ..Default::default()
+                };
+                let descriptor_pool=unsafe{
+                    self.device.create_descriptor_pool(&descriptor_pool_create_info,self.get_allocation_callbacks())
+                }.unwrap();
+                self.material_descriptor_pools.push((descriptor_pool,0));
+                self.material_descriptor_pools.len()-1
+            },
+        };
+
+        let descriptor_pool=self.material_descriptor_pools[pool_index].0;
+        let descriptor_set_layouts=[
+            self.descriptor_set_layout
+        ];
+        let descriptor_set_allocate_info=vk::DescriptorSetAllocateInfo{
+            descriptor_pool,
+            descriptor_set_count:descriptor_set_layouts.len() as u32,
+            p_set_layouts:descriptor_set_layouts.as_ptr(),
+            ..Default::default()
+        };
+        let descriptor_set=unsafe{
+            self.device.allocate_descriptor_sets(&descriptor_set_allocate_info)
+        }.unwrap()[0];
+        self.material_descriptor_pools[pool_index].1+=1;
+        (descriptor_pool,descriptor_set)
+    }
+
+    //free materials that are only referenced by the cache and were not requested in the last frame (so that materials requested while drawing, like those of sprites, are kept),
+    //which also releases their textures. called by draw before recording, when no frame using the materials is in flight anymore
+    pub fn release_unused_materials(&mut self){
+        let frame=self.frame;
+        let device=&self.device;
+        let material_descriptor_pools=&mut self.material_descriptor_pools;
+        self.materials.retain(|_,(material,last_requested)|{
+            if std::sync::Arc::strong_count(material)>1 || *last_requested+1>=frame{
+                return true;
+            }
+            unsafe{
+                device.free_descriptor_sets(material.descriptor_pool,&[material.descriptor_set])
+            }.unwrap();
+            if let Some((_,allocated))=material_descriptor_pools.iter_mut().find(|(descriptor_pool,_)| *descriptor_pool==material.descriptor_pool){
+                *allocated-=1;
+            }
+            false
+        });
+    }
+
+    //return cached material, or allocate and write descriptor set for a new one
+    pub fn get_material(&mut self,texture:std::sync::Arc<Image>,sampler:SamplerDescription,blend_mode:BlendMode,shading:Shading)->std::sync::Arc<Material>{
+        let frame=self.frame;
+        if let Some((material,last_requested))=self.materials.get_mut(&(texture.image_view,sampler,blend_mode,shading)){
+            *last_requested=frame;
+            return material.clone();
+        }
+
+        let (descriptor_pool,descriptor_set)=self.allocate_material_descriptor_set();
+
+        let descriptor_image_info=vk::DescriptorImageInfo{
+            sampler:self.samplers.get(&self.device,self.allocation_callbacks.as_ref(),&sampler),
+            image_view:texture.image_view,
+            image_layout:vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let write_descriptor_set=vk::WriteDescriptorSet{
+            dst_set:descriptor_set,
+            dst_binding:0,
+            dst_array_element:0,
+            descriptor_count:1,
+            descriptor_type:vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info:&descriptor_image_info,
+            ..Default::default()
+        };
+        unsafe{
+            self.device.update_descriptor_sets(&[write_descriptor_set],&[])
+        };
+
+        let material=std::sync::Arc::new(Material{
+            texture:texture.clone(),
+            sampler,
+            blend_mode,
+            shading,
+            descriptor_pool,
+            descriptor_set,
+        });
+
+        self.materials.insert((texture.image_view,sampler,blend_mode,shading),(material.clone(),frame));
+
+        material
+    }
+
+    //draw the cubemap behind all geometry, or nothing (only the clear color) if None
+    pub fn set_skybox(&mut self,cubemap:Option<std::sync::Arc<Image>>){
+        self.skybox=cubemap.map(|cubemap|{
+            if cubemap.view_type!=vk::ImageViewType::CUBE{
+                panic!("skybox texture is not a cubemap");
+            }
+            let sampler=SamplerDescription::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+            self.get_material(cubemap,sampler,BlendMode::Opaque,Shading::Unlit)
+        });
+    }
+
+    //lit material for a model material
+    pub fn get_model_material(&mut self,model_material:&ModelMaterial)->std::sync::Arc<Material>{
+        let blend_mode=if model_material.transparent{
+            BlendMode::Alpha
+        }else{
+            BlendMode::Opaque
+        };
+        self.get_material(model_material.diffuse_texture.clone(),model_material.sampler,blend_mode,Shading::Lit)
+    }
+
+    //material for each submesh of the model, in order, using default_material for submeshes without a material
+    pub fn get_model_materials(&mut self,model:&Model,default_material:&std::sync::Arc<Material>)->Vec<std::sync::Arc<Material>>{
+        model.submeshes.iter().map(|submesh| match &submesh.material{
+            Some(model_material)=>self.get_model_material(model_material),
+            None=>default_material.clone(),
+        }).collect()
+    }
+
+    //instanced 3d pipeline used to draw instance batches with the given blend mode
+    pub fn graphics_pipeline_3d_instanced(&self,blend_mode:BlendMode)->vk::Pipeline{
+        match blend_mode{
+            BlendMode::Opaque=>self.graphics_pipeline_3d_instanced.pipeline,
+            _=>self.graphics_pipelines_3d_instanced_blended[blend_mode.index()-1],
+        }
+    }
+
+    //allocate and persistently map a host visible buffer
+    fn create_mapped_buffer(&self,size:u64,usage:vk::BufferUsageFlags)->(vk::Buffer,vk::DeviceMemory,*mut libc::c_void){
+        create_mapped_buffer(&self.device,&self.device_memory_properties,self.get_allocation_callbacks(),size,usage)
+    }
+
+    //allocate and map the instance buffer
+    pub fn create_instance_buffer(&mut self){
+        let size=INITIAL_INSTANCES*std::mem::size_of::<InstanceData>() as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::VERTEX_BUFFER);
+
+        self.instance_buffer_pointer=pointer;
+        self.instance_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count:INITIAL_INSTANCES,
+            buffer,
+            memory,
+        };
+    }
+
+    //replace the instance buffer with one that holds at least instance_count instances (at least twice as many as before), keeping the first written_count instances
+    //must only be called after the last frame that used the instance buffer has finished
+    fn grow_instance_buffer(&mut self,instance_count:u64,written_count:u64){
+        let item_count=instance_count.max(self.instance_buffer.item_count*2);
+        let size=item_count*std::mem::size_of::<InstanceData>() as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::VERTEX_BUFFER);
+        unsafe{
+            std::ptr::copy_nonoverlapping(self.instance_buffer_pointer as *const InstanceData,pointer as *mut InstanceData,written_count as usize);
+
+            self.device.unmap_memory(self.instance_buffer.memory);
+            self.device.destroy_buffer(self.instance_buffer.buffer, self.get_allocation_callbacks());
+            self.device.free_memory(self.instance_buffer.memory, self.get_allocation_callbacks());
+        }
+
+        self.instance_buffer_pointer=pointer;
+        self.instance_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count,
+            buffer,
+            memory,
+        };
+    }
+
+    //allocate and map the sprite buffer
+    pub fn create_sprite_buffer(&mut self){
+        let size=MAX_SPRITES*6*std::mem::size_of::<SpriteVertex>() as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::VERTEX_BUFFER);
+
+        self.sprite_buffer_pointer=pointer;
+        self.sprite_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count:MAX_SPRITES*6,
+            buffer,
+            memory,
+        };
+    }
+
+    //allocate and map the debug line buffer
+    pub fn create_line_buffer(&mut self){
+        let size=MAX_LINE_VERTICES*std::mem::size_of::<ColorVertex>() as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::VERTEX_BUFFER);
+
+        self.line_buffer_pointer=pointer;
+        self.line_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count:MAX_LINE_VERTICES,
+            buffer,
+            memory,
+        };
+    }
+
+    //allocate the default attributes buffer and write the default values into it, which never change
+    pub fn create_default_attributes_buffer(&mut self){
+        let size=std::mem::size_of_val(&vertex_layout::DEFAULT_ATTRIBUTE_VALUES) as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::VERTEX_BUFFER);
+        unsafe{
+            (pointer as *mut [[f32;4];5]).write(vertex_layout::DEFAULT_ATTRIBUTE_VALUES);
+        }
+
+        self.default_attributes_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count:vertex_layout::DEFAULT_ATTRIBUTE_VALUES.len() as u64,
+            buffer,
+            memory,
+        };
+    }
+
+    //allocate and map the lights buffer, and allocate and write the lights descriptor set
+    pub fn create_lights_buffer(&mut self){
+        let size=std::mem::size_of::<LightsUniform>() as u64;
+        let (buffer,memory,pointer)=self.create_mapped_buffer(size,vk::BufferUsageFlags::UNIFORM_BUFFER);
+
+        self.lights_buffer_pointer=pointer;
+        self.lights_buffer=IntegratedBuffer{
+            buffer_size:size,
+            item_count:1,
+            buffer,
+            memory,
+        };
+
+        self.lights_descriptor_set={
+            let descriptor_set_layouts=[
+                self.lights_descriptor_set_layout
+            ];
+            let descriptor_set_allocate_info=vk::DescriptorSetAllocateInfo{
+                descriptor_pool:self.descriptor_pool,
+                descriptor_set_count:descriptor_set_layouts.len() as u32,
+                p_set_layouts:descriptor_set_layouts.as_ptr(),
+                ..Default::default()
+            };
+
+            unsafe{
+                self.device.allocate_descriptor_sets(&descriptor_set_allocate_info)
+            }.unwrap()[0]
+        };
+
+        let descriptor_buffer_info=vk::DescriptorBufferInfo{
+            buffer,
+            offset:0,
+            range:size,
+        };
+        let write_descriptor_set=vk::WriteDescriptorSet{
+            dst_set:self.lights_descriptor_set,
+            dst_binding:0,
+            dst_array_element:0,
+            descriptor_count:1,
+            descriptor_type:vk::DescriptorType::UNIFORM_BUFFER,
+            p_buffer_info:&descriptor_buffer_info,
+            ..Default::default()
+        };
+        unsafe{
+            self.device.update_descriptor_sets(&[write_descriptor_set],&[])
+        };
+    }
+
+    //write instance data of all batches into the instance buffer, grouped by material, skipping instances outside the frustum
+    //returns the groups and the number of culled instances
+    //the instance buffer grows if it cannot hold all visible instances
+    //must only be called after the last frame that used the instance buffer has finished
+    fn upload_instance_batches(&mut self,instance_batches:&[InstanceBatch],frustum:&Frustum)->(Vec<InstanceGroup>,u32){
+        let mut instance_groups=Vec::new();
+        let mut instance_count:u64=0;
+        let mut culled_instances=0;
+        for batch in instance_batches.iter(){
+            let visible_instances:Vec<&InstanceData>=batch.instances.iter().filter(|instance|{
+                frustum.intersects(&batch.mesh.bounding_sphere,&batch.mesh.bounding_box,&instance.transform)
+            }).collect();
+            culled_instances+=(batch.instances.len()-visible_instances.len()) as u32;
+            for (material_index,material) in batch.materials.iter().enumerate(){
+                let mut instances:Vec<&InstanceData>=visible_instances.iter().copied().filter(|instance| instance.material_index as usize==material_index).collect();
+                if instances.is_empty(){
+                    continue;
+                }
+                if material.blend_mode.is_transparent(){
+                    instances.sort_by(|a,b|{
+                        let distance_a=self.camera.distance_to(&glm::vec4_to_vec3(&a.transform.column(3).into_owned()));
+                        let distance_b=self.camera.distance_to(&glm::vec4_to_vec3(&b.transform.column(3).into_owned()));
+                        distance_b.partial_cmp(&distance_a).unwrap_or(std::cmp::Ordering::Equal)
+                    });
+                }
+
+                if instance_count+instances.len() as u64>self.instance_buffer.item_count{
+                    self.grow_instance_buffer(instance_count+instances.len() as u64,instance_count);
+                }
+                for (i,instance) in instances.iter().enumerate(){
+                    unsafe{
+                        (self.instance_buffer_pointer as *mut InstanceData).add(instance_count as usize+i).write(**instance);
+                    }
+                }
+
+                //the nearest instance decides the level of detail, so that no instance is drawn with less detail than it needs
+                let screen_size=instances.iter().map(|instance| self.camera.projected_size(&batch.mesh.bounding_sphere.transformed(&instance.transform))).fold(0.0,f32::max);
+
+                instance_groups.push(InstanceGroup{
+                    mesh:batch.mesh.clone(),
+                    material:material.clone(),
+                    first_instance:instance_count as u32,
+                    instance_count:instances.len() as u32,
+                    screen_size,
+                });
+                instance_count+=instances.len() as u64;
+            }
+        }
+        (instance_groups,culled_instances)
+    }
+
+    //write the debug lines to the line buffer, and draw them with a single draw
+    //lines are pairs of vertices in world space, drawn depth tested with the scene
+    fn record_lines(&mut self,lines:&[ColorVertex],view:&glm::Mat4,projection:&glm::Mat4,viewport:&vk::Viewport,scissor:&vk::Rect2D){
+        if lines.is_empty(){
+            return;
+        }
+        if !lines.len().is_multiple_of(2){
+            panic!("debug lines have {} vertices, which are not pairs",lines.len());
+        }
+        if lines.len() as u64>MAX_LINE_VERTICES{
+            panic!("line buffer not big enough");
+        }
+        unsafe{
+            std::ptr::copy_nonoverlapping(lines.as_ptr(),self.line_buffer_pointer as *mut ColorVertex,lines.len());
+        }
+
+        let matrices=[glm::identity::<f32,4>(),*view,*projection];
+        let command_buffer=self.graphics_queue_command_buffers[0];
+        unsafe{
+            self.device.cmd_bind_pipeline(command_buffer,vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_lines.pipeline);
+            self.device.cmd_set_viewport(command_buffer,0,&[*viewport]);
+            self.device.cmd_set_scissor(command_buffer,0,&[*scissor]);
+            self.device.cmd_bind_vertex_buffers(command_buffer,0,&[self.line_buffer.buffer],&[0]);
+            self.device.cmd_push_constants(
+                command_buffer,
+                self.graphics_pipeline_lines.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                &*(&matrices as *const [glm::Mat4;3] as *const [u8;16*4*3]),
+            );
+            self.device.cmd_draw(command_buffer,lines.len() as u32,1,0,0);
+        }
+        self.statistics.pipeline_binds+=1;
+        self.statistics.vertex_buffer_binds+=1;
+        self.statistics.draws+=1;
+        self.statistics.instances+=1;
+    }
+
+    //write the quads of all sprites to the sprite buffer, and draw them in order with the 2d pipeline
+    //consecutive sprites on the same atlas page are drawn with a single draw
+    fn record_sprites(&mut self,sprites:&[Sprite],viewport:&vk::Viewport,scissor:&vk::Rect2D){
+        if sprites.is_empty(){
+            return;
+        }
+        if sprites.len() as u64>MAX_SPRITES{
+            panic!("sprite buffer not big enough");
+        }
+        for (sprite_index,sprite) in sprites.iter().enumerate(){
+            let (vertices,vertex_indices)=sprite.region.quad(sprite.min,sprite.max);
+            for (corner_index,corner) in vertex_indices.iter().flat_map(|triangle| triangle.corners()).enumerate(){
+                let vertex=&vertices[corner as usize];
+                unsafe{
+                    (self.sprite_buffer_pointer as *mut SpriteVertex).add(sprite_index*6+corner_index).write(SpriteVertex{
+                        position:[vertex.x,vertex.y],
+                        texcoord:[vertex.u,vertex.v],
+                    });
+                }
+            }
+        }
+
+        let command_buffer=self.graphics_queue_command_buffers[0];
+        unsafe{
+            self.device.cmd_bind_pipeline(command_buffer,vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_2d.pipeline);
+            self.device.cmd_set_viewport(command_buffer,0,&[*viewport]);
+            self.device.cmd_set_scissor(command_buffer,0,&[*scissor]);
+            self.device.cmd_bind_vertex_buffers(command_buffer,0,&[self.sprite_buffer.buffer],&[0]);
+        }
+        self.statistics.pipeline_binds+=1;
+        self.statistics.vertex_buffer_binds+=1;
+
+        let mut first_sprite=0;
+        while first_sprite<sprites.len(){
+            let texture=&sprites[first_sprite].texture;
+            let sprite_count=sprites[first_sprite..].iter().take_while(|sprite| std::sync::Arc::ptr_eq(&sprite.texture,texture)).count();
+            //sprites are clamped to their padding, which repeats their edge texels
+            let material=self.get_material(texture.clone(),SamplerDescription::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE),BlendMode::Alpha,Shading::Unlit);
+            unsafe{
+                self.device.cmd_bind_descriptor_sets(command_buffer,vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_2d.layout,0,&[material.descriptor_set],&[]);
+                self.device.cmd_draw(command_buffer,(sprite_count*6) as u32,1,(first_sprite*6) as u32,0);
+            }
+            self.statistics.descriptor_set_binds+=1;
+            self.statistics.draws+=1;
+            self.statistics.instances+=1;
+            first_sprite+=sprite_count;
+        }
+    }
+
+    pub fn create_attachment(&self,extent:vk::Extent2D,format:vk::Format,samples:vk::SampleCountFlags,usage:vk::ImageUsageFlags,aspect_mask:vk::ImageAspectFlags)->Attachment{
+        let image_create_info=vk::ImageCreateInfo{
+            image_type:vk::ImageType::TYPE_2D,
+            format,
+            extent:vk::Extent3D{
+                width:extent.width,
+                height:extent.height,
+                depth:1,
+            },
+            mip_levels:1,
+            array_layers:1,
+            samples,
+            tiling:vk::ImageTiling::OPTIMAL,
+            usage,
+            sharing_mode:vk::SharingMode::EXCLUSIVE,
+            initial_layout:vk::ImageLayout::UNDEFINED,
+            ..Default::default()
+        };
+        let image=unsafe{
+            self.device.create_image(&image_create_info,self.get_allocation_callbacks())
+        }.unwrap();
+
+        let image_memory_requirements=unsafe{
+            self.device.get_image_memory_requirements(image)
+        };
+
+        //transient attachments prefer lazily allocated memory, if the device has it
+        let mut memory=vk::DeviceMemory::null();
+        for required_flags in &[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL | vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        ]{
+            if required_flags.contains(vk::MemoryPropertyFlags::LAZILY_ALLOCATED) && !usage.contains(vk::ImageUsageFlags::TRANSIENT_ATTACHMENT){
+                continue;
+            }
+            for memory_type_index in 0..self.device_memory_properties.memory_type_count{
+                if (image_memory_requirements.memory_type_bits & (1<<memory_type_index))>0
+                && self.device_memory_properties.memory_types[memory_type_index as usize].property_flags.contains(*required_flags){
+                    let memory_allocate_info=vk::MemoryAllocateInfo{
+                        allocation_size:image_memory_requirements.size,
+                        memory_type_index,
+                        ..Default::default()
+                    };
+                    memory=unsafe{
+                        self.device.allocate_memory(&memory_allocate_info,self.get_allocation_callbacks())
+                    }.unwrap();
+                    unsafe{
+                        self.device.bind_image_memory(image,memory,0)
+                    }.unwrap();
+                    break;
+                }
+            }
+            if memory!=vk::DeviceMemory::null(){
+                break;
+            }
+        }
+        if memory==vk::DeviceMemory::null(){
+            panic!("no fit memory found for attachment");
+        }
+
+        let image_view_create_info=vk::ImageViewCreateInfo{
+            image,
+            view_type:vk::ImageViewType::TYPE_2D,
+            format,
+            components:vk::ComponentMapping{
+                r:vk::ComponentSwizzle::IDENTITY,
+                g:vk::ComponentSwizzle::IDENTITY,
+                b:vk::ComponentSwizzle::IDENTITY,
+                a:vk::ComponentSwizzle::IDENTITY,
+            },
+            subresource_range:vk::ImageSubresourceRange{
+                aspect_mask,
+                base_mip_level:0,
+                level_count:1,
+                base_array_layer:0,
+                layer_count:1,
+            },
+            ..Default::default()
+        };
+        let image_view=unsafe{
+            self.device.create_image_view(&image_view_create_info,self.get_allocation_callbacks())
+        }.unwrap();
+
+        Attachment{
+            format,
+            samples,
+            memory,
+            image,
+            image_view,
+        }
+    }
+
+    //create multisampled color (if required) and depth attachments for a framebuffer of the given size
+    pub fn create_transient_attachments(&self,extent:vk::Extent2D)->TransientAttachments{
+        let multisample_color=if self.sample_count!=vk::SampleCountFlags::TYPE_1{
+            Some(self.create_attachment(
+                extent,
+                self.swapchain_surface_format.format,
+                self.sample_count,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                vk::ImageAspectFlags::COLOR,
+            ))
+        }else{
+            None
+        };
+
+        let depth_aspect_mask=match self.depth_format{
+            vk::Format::D32_SFLOAT_S8_UINT | vk::Format::D24_UNORM_S8_UINT=>vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+            _=>vk::ImageAspectFlags::DEPTH,
+        };
+        let depth=self.create_attachment(
+            extent,
+            self.depth_format,
+            self.sample_count,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            depth_aspect_mask,
+        );
+
+        TransientAttachments{
+            multisample_color,
+            depth,
+        }
+    }
+
+    //create framebuffer for the current render pass, rendering (or resolving) into the given color image view
+    pub fn create_framebuffer(&self,extent:vk::Extent2D,color_image_view:vk::ImageView,transient_attachments:&TransientAttachments)->vk::Framebuffer{
+        let mut attachments=vec![
+            color_image_view,
+            transient_attachments.depth.image_view,
+        ];
+        if let Some(multisample_color)=transient_attachments.multisample_color{
+            attachments.push(multisample_color.image_view);
+        }
+        let framebuffer_create_info=vk::FramebufferCreateInfo{
+            render_pass:self.render_pass,
+            attachment_count:attachments.len() as u32,
+            p_attachments:attachments.as_ptr(),
+            width:extent.width,
+            height:extent.height,
+            layers:1,
+            ..Default::default()
+        };
+        unsafe{
+            self.device.create_framebuffer(&framebuffer_create_info,self.get_allocation_callbacks())
+        }.unwrap()
+    }
+
+    //create offscreen render target
+    //the render pass starts from an undefined layout, so the color image can be rendered to right away
+    pub fn create_render_target(&self,extent:vk::Extent2D)->RenderTarget{
+        let color=self.create_attachment(
+            extent,
+            self.swapchain_surface_format.format,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let transient_attachments=self.create_transient_attachments(extent);
+        let framebuffer=self.create_framebuffer(extent,color.image_view,&transient_attachments);
+
+        RenderTarget{
+            extent,
+            color,
+            transient_attachments,
+            framebuffer,
+        }
+    }
+    //draw all nodes of the scene that have a mesh and material, all instance batches, then the debug lines (which are cleared) and sprites
+    //the camera of the active scene camera node (if any) replaces the painter camera
+    pub fn draw(&mut self,framebuffer:vk::Framebuffer,window_extent:vk::Extent2D,scene:&mut Scene,instance_batches:&[InstanceBatch],sprites:&[Sprite],done:vk::Semaphore){
+        self.release_unused_materials();
+        scene.update_world_transforms();
+        if let Some(camera)=scene.active_camera(){
+            self.camera=camera;
+        }
+        let objects=scene.objects();
+
         //record graphics command buffer
         //begin
         /*
@@ -144,11 +1272,25 @@ impl Painter{
 
         //render quad
         //begin render pass
-        let clear_value=vk::ClearValue{
+        let clear_color=vk::ClearValue{
             color:vk::ClearColorValue{
                 float32:[0.9,0.5,0.2,1.0],
             },
         };
+        let clear_depth=vk::ClearValue{
+            depth_stencil:vk::ClearDepthStencilValue{
+                depth:1.0,
+                stencil:0,
+            },
+        };
+        //one clear value per attachment (see create_render_pass), values for attachments that are not cleared are ignored
+        let mut clear_values=vec![
+            clear_color,
+            clear_depth,
+        ];
+        if self.sample_count!=vk::SampleCountFlags::TYPE_1{
+            clear_values.push(clear_color);
+        }
         let render_pass_begin_info=vk::RenderPassBeginInfo{
             render_pass:self.render_pass,
             framebuffer:framebuffer,
@@ -159,17 +1301,15 @@ impl Painter{
                 },
                 extent:window_extent
             },
-            clear_value_count:1,
-            p_clear_values:&clear_value,
+            clear_value_count:clear_values.len() as u32,
+            p_clear_values:clear_values.as_ptr(),
             ..Default::default()
         };
         unsafe{
-            self.device.cmd_begin_render_pass(self.graphics_queue_command_buffers[0], &render_pass_begin_info, vk::SubpassContents::INLINE)
+            self.device.cmd_begin_render_pass(self.graphics_queue_command_buffers[0], &render_pass_begin_info, vk::SubpassContents::INLINE);
+            //stays bound while meshes bind their own vertex buffers to the lower bindings
+            self.device.cmd_bind_vertex_buffers(self.graphics_queue_command_buffers[0],vertex_layout::DEFAULT_ATTRIBUTES_BINDING,&[self.default_attributes_buffer.buffer],&[0]);
         };
-        //bind pipeline 2d
-        unsafe{
-            self.device.cmd_bind_pipeline(self.graphics_queue_command_buffers[0],vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_2d.pipeline);
-        }
         let viewport=vk::Viewport{
             x:0.0,
             y:0.0,
@@ -188,61 +1328,129 @@ impl Painter{
                 height:window_extent.height,
             }
         };
-        unsafe{
-            self.device.cmd_set_viewport(self.graphics_queue_command_buffers[0],0,&[viewport]);
-            self.device.cmd_set_scissor(self.graphics_queue_command_buffers[0],0,&[scissor]);
-        }
-        //bind descriptor set for fragment shader
-        unsafe{
-            self.device.cmd_bind_descriptor_sets(self.graphics_queue_command_buffers[0], vk::PipelineBindPoint::GRAPHICS, self.graphics_pipeline_2d.layout, 0, &[self.descriptor_set], &[]);
-        }
-        //draw
-        for obj in objects.iter(){
+        let view=self.camera.view();
+        let projection=self.camera.projection(window_extent);
+        //objects and instances outside the view frustum are not drawn
+        let frustum=Frustum::from_matrix(&(projection*view));
+
+        if let Some(skybox)=&self.skybox{
+            //model matrix is unused, the shader removes the translation from the view
+            let matrices=[glm::identity::<f32,4>(),view,projection];
             unsafe{
-                self.device.cmd_bind_vertex_buffers(self.graphics_queue_command_buffers[0],0,&[obj.mesh.vertices.buffer],&[0]);
-                self.device.cmd_bind_index_buffer(self.graphics_queue_command_buffers[0],obj.mesh.vertex_indices.buffer,0,vk::IndexType::UINT16);
-                //self.device.cmd_draw_indexed(self.graphics_queue_command_buffers[0],obj.mesh.vertex_indices.item_count as u32,1,0,0,0);
+                let command_buffer=self.graphics_queue_command_buffers[0];
+                self.device.cmd_bind_pipeline(command_buffer,vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_skybox.pipeline);
+                self.device.cmd_set_viewport(command_buffer,0,&[viewport]);
+                self.device.cmd_set_scissor(command_buffer,0,&[scissor]);
+                self.device.cmd_bind_descriptor_sets(command_buffer,vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_skybox.layout,0,&[skybox.descriptor_set],&[]);
+                self.device.cmd_push_constants(
+                    command_buffer,
+                    self.graphics_pipeline_skybox.layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &*(&matrices as *const [glm::Mat4;3] as *const [u8;16*4*3]),
+                );
+                //36 vertices of the cube
+                self.device.cmd_draw(command_buffer,36,1,0,0);
             }
         }
 
-        //bind pipeline 3d
-        unsafe{
-            self.device.cmd_bind_pipeline(self.graphics_queue_command_buffers[0],vk::PipelineBindPoint::GRAPHICS,self.graphics_pipeline_3d.pipeline);
-        }
+        //lights are written once per frame, the buffer is not in use because the last frame has finished
         unsafe{
-            self.device.cmd_set_viewport(self.graphics_queue_command_buffers[0],0,&[viewport]);
-            self.device.cmd_set_scissor(self.graphics_queue_command_buffers[0],0,&[scissor]);
+            (self.lights_buffer_pointer as *mut LightsUniform).write(self.lights.uniform(&self.camera.eye));
         }
-        //push constants
-        let eye=glm::vec3(2.0,2.0,2.0);
-        let target=glm::vec3(0.0,0.0,0.0);
-        let view=glm::look_at(&eye,&target,&glm::vec3(0.0,1.0,0.0));
-
-        let model=glm::rotate_x(&glm::identity::<f32,4>(),0.4);
 
-        let projection=glm::perspective_fov(glm::radians(&glm::vec1(80.0)).x,2.0,2.0,0.001,1000.0);
+        //instances are written to the instance buffer grouped by material (transparent instances back to front within their group)
+        let (instance_groups,culled_instances)=self.upload_instance_batches(instance_batches,&frustum);
 
-        //bind descriptor set for fragment shader
-        unsafe{
-            self.device.cmd_bind_descriptor_sets(self.graphics_queue_command_buffers[0], vk::PipelineBindPoint::GRAPHICS, self.graphics_pipeline_3d.layout, 0, &[self.descriptor_set], &[]);
+        //collect draw items, sorted by pipeline/material/mesh/depth (opaque) or back to front (transparent)
+        let mut render_queue=std::mem::take(&mut self.render_queue);
+        render_queue.clear();
+        let mut culled_objects=0;
+        for obj in objects.iter(){
+            if !frustum.intersects(&obj.mesh.bounding_sphere,&obj.mesh.bounding_box,&obj.transform){
+                culled_objects+=1;
+                continue;
+            }
+            let position=glm::vec4_to_vec3(&obj.transform.column(3).into_owned());
+            let (variant,lights_descriptor_set)=match obj.material.shading{
+                Shading::Unlit=>(PipelineVariant::Unlit,None),
+                Shading::Lit=>(PipelineVariant::Lit,Some(self.lights_descriptor_set)),
+            };
+            let (pipeline,pipeline_layout)=self.graphics_pipeline_for(variant,&obj.mesh.vertex_layout,obj.material.blend_mode);
+            let lod=obj.mesh.lod(self.camera.projected_size(&obj.mesh.bounding_sphere.transformed(&obj.transform)));
+            render_queue.push(
+                DrawItem{
+                    sort_key:0,
+                    pipeline,
+                    pipeline_layout,
+                    descriptor_set:obj.material.descriptor_set,
+                    lights_descriptor_set,
+                    vertex_buffer:obj.mesh.vertices.buffer,
+                    instance_buffer:None,
+                    index_buffer:obj.mesh.vertex_indices.buffer,
+                    index_type:obj.mesh.index_type,
+                    first_index:lod.first_index,
+                    index_count:lod.index_count,
+                    first_instance:0,
+                    instance_count:1,
+                    transform:obj.transform,
+                },
+                obj.material.blend_mode.is_transparent(),
+                self.camera.distance_to(&position)/self.camera.far,
+            );
         }
-        unsafe{
-            self.device.cmd_push_constants(
-                self.graphics_queue_command_buffers[0],
-                self.graphics_pipeline_3d.layout,
-                vk::ShaderStageFlags::VERTEX,
-                0,
-                std::mem::transmute::<&[glm::Mat4;3], &[u8;16*4*3]>(&[model,view,projection]),
+        for group in instance_groups.iter(){
+            //instance groups are sorted by the distance of their first instance, which is the farthest one for transparent groups
+            let first_instance=unsafe{
+                *(self.instance_buffer_pointer as *const InstanceData).add(group.first_instance as usize)
+            };
+            let position=glm::vec4_to_vec3(&first_instance.transform.column(3).into_owned());
+            let (pipeline,pipeline_layout)=self.graphics_pipeline_for(PipelineVariant::Instanced,&group.mesh.vertex_layout,group.material.blend_mode);
+            let lod=group.mesh.lod(group.screen_size);
+            render_queue.push(
+                DrawItem{
+                    sort_key:0,
+                    pipeline,
+                    pipeline_layout,
+                    descriptor_set:group.material.descriptor_set,
+                    lights_descriptor_set:None,//instanced pipelines are unlit
+                    vertex_buffer:group.mesh.vertices.buffer,
+                    instance_buffer:Some(self.instance_buffer.buffer),
+                    index_buffer:group.mesh.vertex_indices.buffer,
+                    index_type:group.mesh.index_type,
+                    first_index:lod.first_index,
+                    index_count:lod.index_count,
+                    first_instance:group.first_instance,
+                    instance_count:group.instance_count,
+                    transform:glm::identity::<f32,4>(),//model matrix is taken from instance data
+                },
+                group.material.blend_mode.is_transparent(),
+                self.camera.distance_to(&position)/self.camera.far,
             );
         }
-        //draw
-        for obj in objects.iter(){
-            unsafe{
-                self.device.cmd_bind_vertex_buffers(self.graphics_queue_command_buffers[0],0,&[obj.mesh.vertices.buffer],&[0]);
-                self.device.cmd_bind_index_buffer(self.graphics_queue_command_buffers[0],obj.mesh.vertex_indices.buffer,0,vk::IndexType::UINT16);
-                self.device.cmd_draw_indexed(self.graphics_queue_command_buffers[0],obj.mesh.vertex_indices.item_count as u32,1,0,0,0);
-            }
+        render_queue.sort();
+
+        self.statistics=FrameStatistics{
+            culled_objects,
+            culled_instances,
+            ..render_queue.record(&self.device,self.graphics_queue_command_buffers[0],&view,&projection,&viewport,&scissor)
+        };
+        self.render_queue=render_queue;
+        if self.skybox.is_some(){
+            self.statistics.pipeline_binds+=1;
+            self.statistics.descriptor_set_binds+=1;
+            self.statistics.draws+=1;
+            self.statistics.instances+=1;
         }
+
+        let lines=std::mem::take(&mut self.lines);
+        self.record_lines(&lines,&view,&projection,&viewport,&scissor);
+        //keep the allocation for the next frame
+        self.lines=lines;
+        self.lines.clear();
+        //sprites are drawn last, over the scene
+        self.record_sprites(sprites,&viewport,&scissor);
+
         //end render pass
         unsafe{
             self.device.cmd_end_render_pass(self.graphics_queue_command_buffers[0])
@@ -277,5 +1485,6 @@ impl Painter{
         unsafe{
             self.device.queue_submit(self.graphics_queue,&[submit_info_graphics],vk::Fence::null())
         }.unwrap();
+        self.frame+=1;
     }
 }
\ No newline at end of file
@@ -0,0 +1,311 @@
+use ash::{
+    vk,
+};
+
+use crate::{Decoder,Vertex};
+use crate::decoder::{VertexIndices,smooth_normals};
+use crate::model::{Model,Submesh};
+use crate::mesh_processing;
+use crate::color::srgb_to_linear;
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum PlyFormat{
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+enum PlyScalar{
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+impl PlyScalar{
+    fn parse(name:&str)->Self{
+        match name{
+            "char" | "int8"=>PlyScalar::I8,
+            "uchar" | "uint8"=>PlyScalar::U8,
+            "short" | "int16"=>PlyScalar::I16,
+            "ushort" | "uint16"=>PlyScalar::U16,
+            "int" | "int32"=>PlyScalar::I32,
+            "uint" | "uint32"=>PlyScalar::U32,
+            "float" | "float32"=>PlyScalar::F32,
+            "double" | "float64"=>PlyScalar::F64,
+            _=>panic!("unknown ply type {}",name),
+        }
+    }
+
+    fn size(&self)->usize{
+        match self{
+            PlyScalar::I8 | PlyScalar::U8=>1,
+            PlyScalar::I16 | PlyScalar::U16=>2,
+            PlyScalar::I32 | PlyScalar::U32 | PlyScalar::F32=>4,
+            PlyScalar::F64=>8,
+        }
+    }
+
+    //largest value of integer types, used to normalize colors
+    fn max(&self)->f64{
+        match self{
+            PlyScalar::I8=>i8::MAX as f64,
+            PlyScalar::U8=>u8::MAX as f64,
+            PlyScalar::I16=>i16::MAX as f64,
+            PlyScalar::U16=>u16::MAX as f64,
+            PlyScalar::I32=>i32::MAX as f64,
+            PlyScalar::U32=>u32::MAX as f64,
+            PlyScalar::F32 | PlyScalar::F64=>1.0,
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+struct PlyProperty{
+    name:String,
+    scalar:PlyScalar,
+    list_count:Option<PlyScalar>,//type of the element count, if this is a list
+}
+
+#[derive(Debug,Clone)]
+struct PlyElement{
+    name:String,
+    count:usize,
+    properties:Vec<PlyProperty>,
+}
+
+//reads values from the body of a ply file, in any format
+struct PlyReader<'a>{
+    format:PlyFormat,
+    bytes:&'a [u8],
+    position:usize,
+}
+impl<'a> PlyReader<'a>{
+    fn read(&mut self,scalar:PlyScalar)->f64{
+        if self.format==PlyFormat::Ascii{
+            while self.position<self.bytes.len() && self.bytes[self.position].is_ascii_whitespace(){
+                self.position+=1;
+            }
+            let start=self.position;
+            while self.position<self.bytes.len() && !self.bytes[self.position].is_ascii_whitespace(){
+                self.position+=1;
+            }
+            let word=std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+            return word.parse::<f64>().unwrap_or_else(|_| panic!("invalid ply value {}",word));
+        }
+
+        let size=scalar.size();
+        let mut value=[0u8;8];
+        value[..size].copy_from_slice(&self.bytes[self.position..self.position+size]);
+        self.position+=size;
+        if self.format==PlyFormat::BinaryBigEndian{
+            value[..size].reverse();
+        }
+        match scalar{
+            PlyScalar::I8=>value[0] as i8 as f64,
+            PlyScalar::U8=>value[0] as f64,
+            PlyScalar::I16=>i16::from_le_bytes([value[0],value[1]]) as f64,
+            PlyScalar::U16=>u16::from_le_bytes([value[0],value[1]]) as f64,
+            PlyScalar::I32=>i32::from_le_bytes([value[0],value[1],value[2],value[3]]) as f64,
+            PlyScalar::U32=>u32::from_le_bytes([value[0],value[1],value[2],value[3]]) as f64,
+            PlyScalar::F32=>f32::from_le_bytes([value[0],value[1],value[2],value[3]]) as f64,
+            PlyScalar::F64=>f64::from_le_bytes(value),
+        }
+    }
+}
+
+//parse ascii or binary ply file with positions and optional normals, texture coordinates and colors
+//polygons are fan triangulated, smooth normals are generated if the file has none
+fn parse_ply(path:&str,bytes:&[u8])->(Vec<Vertex>,Vec<VertexIndices>){
+    //header is ascii, terminated by "end_header"
+    let header_end=bytes.windows(10).position(|w| w==b"end_header").expect("ply file has no end_header");
+    let body_start=bytes[header_end..].iter().position(|b| *b==b'\n').map(|p| header_end+p+1).unwrap_or(bytes.len());
+    let header=String::from_utf8_lossy(&bytes[..header_end]);
+
+    let mut format=None;
+    let mut elements:Vec<PlyElement>=Vec::new();
+    for line in header.lines(){
+        let words:Vec<&str>=line.split_whitespace().collect();
+        match words.as_slice(){
+            ["format",name,_version]=>format=Some(match *name{
+                "ascii"=>PlyFormat::Ascii,
+                "binary_little_endian"=>PlyFormat::BinaryLittleEndian,
+                "binary_big_endian"=>PlyFormat::BinaryBigEndian,
+                _=>panic!("unknown ply format {}",name),
+            }),
+            ["element",name,count]=>elements.push(PlyElement{
+                name:String::from(*name),
+                count:count.parse().unwrap(),
+                properties:Vec::new(),
+            }),
+            ["property","list",count_type,scalar,name]=>elements.last_mut().expect("ply property outside of element").properties.push(PlyProperty{
+                name:String::from(*name),
+                scalar:PlyScalar::parse(scalar),
+                list_count:Some(PlyScalar::parse(count_type)),
+            }),
+            ["property",scalar,name]=>elements.last_mut().expect("ply property outside of element").properties.push(PlyProperty{
+                name:String::from(*name),
+                scalar:PlyScalar::parse(scalar),
+                list_count:None,
+            }),
+            _=>{},//"ply", comments and obj_info
+        }
+    }
+
+    let mut reader=PlyReader{
+        format:format.expect("ply file has no format"),
+        bytes:&bytes[body_start..],
+        position:0,
+    };
+
+    let mut vertices=Vec::new();
+    let mut polygons:Vec<Vec<u32>>=Vec::new();
+    let mut has_normals=false;
+    for element in elements.iter(){
+        for _ in 0..element.count{
+            //scalar properties by name, and the first list property (face indices)
+            let mut values=std::collections::HashMap::new();
+            let mut list=Vec::new();
+            for property in element.properties.iter(){
+                match property.list_count{
+                    Some(count_type)=>{
+                        let count=reader.read(count_type) as usize;
+                        let items:Vec<f64>=(0..count).map(|_| reader.read(property.scalar)).collect();
+                        if list.is_empty() && (property.name=="vertex_indices" || property.name=="vertex_index"){
+                            list=items;
+                        }
+                    },
+                    None=>{
+                        values.insert(property.name.as_str(),(reader.read(property.scalar),property.scalar));
+                    },
+                }
+            }
+
+            match element.name.as_str(){
+                "vertex"=>{
+                    let value=|name:&str| values.get(name).map(|(value,_)| *value as f32);
+                    let position=glm::vec3(value("x").unwrap_or(0.0),value("y").unwrap_or(0.0),value("z").unwrap_or(0.0));
+                    let u=value("u").or_else(|| value("s")).or_else(|| value("texture_u")).unwrap_or(0.0);
+                    let v=value("v").or_else(|| value("t")).or_else(|| value("texture_v")).unwrap_or(0.0);
+                    let mut vertex=Vertex::new(position.x,position.y,position.z,1.0,u,v);
+                    if let (Some(nx),Some(ny),Some(nz))=(value("nx"),value("ny"),value("nz")){
+                        vertex=vertex.with_normal(nx,ny,nz);
+                        has_normals=true;
+                    }
+                    //integer colors are normalized to [0;1], red, green and blue are sRGB encoded and converted to linear
+                    let color=|names:&[&str],default:f32| names.iter().find_map(|name| values.get(name)).map(|(value,scalar)| (*value/scalar.max()) as f32).unwrap_or(default);
+                    vertex=vertex.with_color(
+                        srgb_to_linear(color(&["red","r","diffuse_red"],1.0)),
+                        srgb_to_linear(color(&["green","g","diffuse_green"],1.0)),
+                        srgb_to_linear(color(&["blue","b","diffuse_blue"],1.0)),
+                        color(&["alpha","a"],1.0),
+                    );
+                    vertices.push(vertex);
+                },
+                "face"=>polygons.push(list.iter().map(|index| *index as u32).collect()),
+                _=>{},
+            }
+        }
+    }
+
+    if let Some((face,index))=polygons.iter().enumerate().find_map(|(face,polygon)| polygon.iter().find(|index| **index as usize>=vertices.len()).map(|index| (face,index))){
+        panic!("{}: face {} refers to vertex {}, but the file has {} vertices",path,face,index,vertices.len());
+    }
+
+    let vertex_indices:Vec<VertexIndices>=polygons.iter().flat_map(|polygon|{
+        (1..polygon.len().saturating_sub(1)).map(move |i| VertexIndices::new(polygon[0],polygon[i],polygon[i+1]))
+    }).collect();
+    if vertex_indices.is_empty(){
+        panic!("{} contains no triangles",path);
+    }
+
+    if !has_normals{
+        let positions:Vec<glm::Vec3>=vertices.iter().map(|v| glm::vec3(v.x,v.y,v.z)).collect();
+        let triangles:Vec<[usize;3]>=polygons.iter().flat_map(|polygon|{
+            (1..polygon.len().saturating_sub(1)).map(move |i| [polygon[0] as usize,polygon[i] as usize,polygon[i+1] as usize])
+        }).collect();
+        for (vertex,normal) in vertices.iter_mut().zip(smooth_normals(&positions,&triangles)){
+            *vertex=vertex.clone().with_normal(normal.x,normal.y,normal.z);
+        }
+    }
+
+    (vertices,vertex_indices)
+}
+
+impl Decoder{
+    //load ply file, see parse_ply
+    pub(crate) fn load_ply(&mut self,path:&str,command_buffer:vk::CommandBuffer)->Model{
+        let bytes=std::fs::read(path).unwrap_or_else(|e| panic!("failed to read {}: {}",path,e));
+        let (vertices,mut vertex_indices)=parse_ply(path,&bytes);
+        mesh_processing::optimize_vertex_cache(&mut vertex_indices,vertices.len());
+
+        Model::new(vec![Submesh{
+            name:String::from(path),
+            material_name:None,
+            material:None,
+            mesh:std::sync::Arc::new(self.create_mesh(&vertices,&vertex_indices,true,command_buffer)),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn ascii(){
+        let file=b"ply\nformat ascii 1.0\ncomment a quad\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0 255 0 0\n1 0 0 0 255 0\n1 1 0 0 0 128\n0 1 0 255 255 255\n4 0 1 2 3\n";
+        let (vertices,vertex_indices)=parse_ply("quad.ply",file);
+        assert_eq!(vertices.len(),4);
+        assert_eq!([vertices[2].x,vertices[2].y,vertices[2].z],[1.0,1.0,0.0]);
+        //quad is fan triangulated
+        assert_eq!(vertex_indices.iter().map(|t| t.corners()).collect::<Vec<_>>(),vec![[0,1,2],[0,2,3]]);
+        //generated normals face the viewer of the counterclockwise quad
+        assert!(vertices.iter().all(|v| (v.nz-1.0).abs()<1e-6));
+        //sRGB colors are converted to linear
+        assert_eq!([vertices[0].r,vertices[0].g,vertices[0].b,vertices[0].a],[1.0,0.0,0.0,1.0]);
+        assert!((vertices[2].b-srgb_to_linear(128.0/255.0)).abs()<1e-6);
+        assert!((vertices[2].b-0.2158).abs()<1e-3);
+    }
+
+    #[test]
+    fn binary(){
+        let header="ply\nformat binary_big_endian 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nproperty float nx\nproperty float ny\nproperty float nz\nelement face 1\nproperty list uchar ushort vertex_index\nend_header\n";
+        let mut big_endian=header.as_bytes().to_vec();
+        let mut little_endian=header.replace("binary_big_endian","binary_little_endian").into_bytes();
+        for position in [[0.0f32,0.0,0.0],[0.0,0.0,-1.0],[1.0,0.0,0.0]].iter(){
+            for value in position.iter().chain([0.0,1.0,0.0].iter()){
+                big_endian.extend_from_slice(&value.to_be_bytes());
+                little_endian.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        big_endian.push(3);
+        little_endian.push(3);
+        for index in [0u16,1,2].iter(){
+            big_endian.extend_from_slice(&index.to_be_bytes());
+            little_endian.extend_from_slice(&index.to_le_bytes());
+        }
+
+        for file in [big_endian,little_endian].iter(){
+            let (vertices,vertex_indices)=parse_ply("triangle.ply",file);
+            assert_eq!(vertices.len(),3);
+            assert_eq!([vertices[1].x,vertices[1].y,vertices[1].z],[0.0,0.0,-1.0]);
+            //normals from the file are kept
+            assert!(vertices.iter().all(|v| [v.nx,v.ny,v.nz]==[0.0,1.0,0.0]));
+            //no colors is white
+            assert!(vertices.iter().all(|v| [v.r,v.g,v.b,v.a]==[1.0;4]));
+            assert_eq!(vertex_indices.iter().map(|t| t.corners()).collect::<Vec<_>>(),vec![[0,1,2]]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected="bad.ply: face 0 refers to vertex 3")]
+    fn index_out_of_range(){
+        let file=b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n";
+        parse_ply("bad.ply",file);
+    }
+}
@@ -0,0 +1,393 @@
+use ash::{
+    vk,
+};
+
+use crate::{Decoder,Vertex,Mesh};
+use crate::decoder::VertexIndices;
+use crate::mesh_processing;
+
+//procedurally generated mesh, centered at the origin with y up
+//triangles are wound counter clockwise when seen from outside, like in model files
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum Primitive{
+    //in the xz plane, facing +y
+    Plane{width:f32,depth:f32,subdivisions:u32},
+    Cube{size:f32,subdivisions:u32},
+    UvSphere{radius:f32,segments:u32,rings:u32},
+    Icosphere{radius:f32,subdivisions:u32},
+    //with caps
+    Cylinder{radius:f32,height:f32,segments:u32},
+    Cone{radius:f32,height:f32,segments:u32},
+    //around the y axis
+    Torus{major_radius:f32,minor_radius:f32,major_segments:u32,minor_segments:u32},
+    //cylinder of the given height with a hemisphere at each end, rings are per hemisphere
+    Capsule{radius:f32,height:f32,segments:u32,rings:u32},
+}
+impl Primitive{
+    //vertices with positions, normals, texture coordinates and tangents
+    pub fn geometry(&self)->(Vec<Vertex>,Vec<VertexIndices>){
+        let mut geometry=Geometry::default();
+        match *self{
+            Primitive::Plane{width,depth,subdivisions}=>{
+                geometry.grid(glm::vec3(-width*0.5,0.0,depth*0.5),glm::vec3(width,0.0,0.0),glm::vec3(0.0,0.0,-depth),subdivisions.max(1));
+            },
+            Primitive::Cube{size,subdivisions}=>{
+                let half=size*0.5;
+                //face normal and the two axes spanning the face, with cross(u,v)=normal
+                let faces=[
+                    (glm::vec3(0.0,0.0,1.0),glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,1.0,0.0)),
+                    (glm::vec3(0.0,0.0,-1.0),glm::vec3(-1.0,0.0,0.0),glm::vec3(0.0,1.0,0.0)),
+                    (glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,0.0,-1.0),glm::vec3(0.0,1.0,0.0)),
+                    (glm::vec3(-1.0,0.0,0.0),glm::vec3(0.0,0.0,1.0),glm::vec3(0.0,1.0,0.0)),
+                    (glm::vec3(0.0,1.0,0.0),glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,0.0,-1.0)),
+                    (glm::vec3(0.0,-1.0,0.0),glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,0.0,1.0)),
+                ];
+                for (normal,u,v) in faces.iter(){
+                    geometry.grid((normal-u-v)*half,u*size,v*size,subdivisions.max(1));
+                }
+            },
+            Primitive::UvSphere{radius,segments,rings}=>{
+                let rings=rings.max(2);
+                let profile=(0..=rings).map(|ring|{
+                    let theta=std::f32::consts::PI*ring as f32/rings as f32;
+                    ProfilePoint::on_circle(glm::vec2(0.0,0.0),radius,theta,1.0-ring as f32/rings as f32)
+                }).collect();
+                geometry.lathe(&[profile],segments.max(3));
+            },
+            Primitive::Icosphere{radius,subdivisions}=>{
+                geometry.icosphere(radius,subdivisions);
+            },
+            Primitive::Cylinder{radius,height,segments}=>{
+                let half=height*0.5;
+                geometry.lathe(&[
+                    vec![ProfilePoint::new(0.0,half,0.0,1.0,1.0),ProfilePoint::new(radius,half,0.0,1.0,0.0)],
+                    vec![ProfilePoint::new(radius,half,1.0,0.0,1.0),ProfilePoint::new(radius,-half,1.0,0.0,0.0)],
+                    vec![ProfilePoint::new(radius,-half,0.0,-1.0,1.0),ProfilePoint::new(0.0,-half,0.0,-1.0,0.0)],
+                ],segments.max(3));
+            },
+            Primitive::Cone{radius,height,segments}=>{
+                let half=height*0.5;
+                //side normal is perpendicular to the slope
+                let slope=glm::normalize(&glm::vec2(height,radius));
+                geometry.lathe(&[
+                    vec![ProfilePoint::new(0.0,half,slope.x,slope.y,1.0),ProfilePoint::new(radius,-half,slope.x,slope.y,0.0)],
+                    vec![ProfilePoint::new(radius,-half,0.0,-1.0,1.0),ProfilePoint::new(0.0,-half,0.0,-1.0,0.0)],
+                ],segments.max(3));
+            },
+            Primitive::Torus{major_radius,minor_radius,major_segments,minor_segments}=>{
+                let minor_segments=minor_segments.max(3);
+                //tube cross section, starting at the top of the tube and going around the outside first
+                let profile=(0..=minor_segments).map(|segment|{
+                    let angle=2.0*std::f32::consts::PI*segment as f32/minor_segments as f32;
+                    let direction=glm::vec2(angle.sin(),angle.cos());
+                    let position=glm::vec2(major_radius,0.0)+direction*minor_radius;
+                    ProfilePoint::new(position.x,position.y,direction.x,direction.y,1.0-segment as f32/minor_segments as f32)
+                }).collect();
+                geometry.lathe(&[profile],major_segments.max(3));
+            },
+            Primitive::Capsule{radius,height,segments,rings}=>{
+                let rings=rings.max(1);
+                let half=height*0.5;
+                //texture v runs along the whole length
+                let length=height+2.0*radius;
+                let hemisphere=|from:u32,to:u32,center_y:f32|->Vec<ProfilePoint>{
+                    (from..=to).map(|ring|{
+                        let theta=std::f32::consts::FRAC_PI_2*ring as f32/rings as f32;
+                        let mut point=ProfilePoint::on_circle(glm::vec2(0.0,center_y),radius,theta,0.0);
+                        point.v=(point.y+length*0.5)/length;
+                        point
+                    }).collect()
+                };
+                let mut profile=hemisphere(0,rings,half);
+                profile.extend(hemisphere(rings,2*rings,-half));
+                geometry.lathe(&[profile],segments.max(3));
+            },
+        }
+
+        let Geometry{mut vertices,mut vertex_indices}=geometry;
+        mesh_processing::generate_tangents(&mut vertices,&mut vertex_indices);
+        (vertices,vertex_indices)
+    }
+}
+
+//point of the profile that is rotated around the y axis, with the normal in the same (radius,y) plane
+#[derive(Debug,Clone,Copy)]
+struct ProfilePoint{
+    radius:f32,
+    y:f32,
+    normal_radius:f32,
+    normal_y:f32,
+    v:f32,
+}
+impl ProfilePoint{
+    fn new(radius:f32,y:f32,normal_radius:f32,normal_y:f32,v:f32)->Self{
+        Self{
+            radius,
+            y,
+            normal_radius,
+            normal_y,
+            v,
+        }
+    }
+
+    //point on a circle in the (radius,y) plane, theta is the angle from the top
+    fn on_circle(center:glm::Vec2,radius:f32,theta:f32,v:f32)->Self{
+        let (sin,cos)=theta.sin_cos();
+        //the sine at theta=PI is not exactly 0, which would leave a tiny hole at the pole
+        let sin=if sin.abs()<1.0e-6{0.0}else{sin};
+        Self::new(center.x+radius*sin,center.y+radius*cos,sin,cos,v)
+    }
+}
+
+#[derive(Default)]
+struct Geometry{
+    vertices:Vec<Vertex>,
+    vertex_indices:Vec<VertexIndices>,
+}
+impl Geometry{
+    //subdivided parallelogram spanned by u and v from origin, facing cross(u,v)
+    fn grid(&mut self,origin:glm::Vec3,u:glm::Vec3,v:glm::Vec3,subdivisions:u32){
+        let normal=glm::normalize(&glm::cross(&u,&v));
+        let first=self.vertices.len() as u32;
+        let columns=subdivisions+1;
+        for row in 0..=subdivisions{
+            for column in 0..=subdivisions{
+                let (s,t)=(column as f32/subdivisions as f32,row as f32/subdivisions as f32);
+                let position=origin+u*s+v*t;
+                self.vertices.push(Vertex::new(position.x,position.y,position.z,1.0,s,t).with_normal(normal.x,normal.y,normal.z));
+            }
+        }
+        for row in 0..subdivisions{
+            for column in 0..subdivisions{
+                let a=first+row*columns+column;
+                self.vertex_indices.push(VertexIndices::new(a,a+1,a+columns+1));
+                self.vertex_indices.push(VertexIndices::new(a,a+columns+1,a+columns));
+            }
+        }
+    }
+
+    //rotate each strip of profile points (from top to bottom) around the y axis.
+    //texture u goes around the axis, with a duplicated column of vertices at the seam.
+    fn lathe(&mut self,strips:&[Vec<ProfilePoint>],segments:u32){
+        let columns=segments+1;
+        for strip in strips.iter(){
+            let first=self.vertices.len() as u32;
+            for point in strip.iter(){
+                for segment in 0..=segments{
+                    let u=segment as f32/segments as f32;
+                    //angle increases clockwise seen from above, so that the quads below are counter clockwise from outside
+                    let (sin,cos)=(2.0*std::f32::consts::PI*u).sin_cos();
+                    self.vertices.push(
+                        Vertex::new(point.radius*cos,point.y,-point.radius*sin,1.0,u,point.v)
+                            .with_normal(point.normal_radius*cos,point.normal_y,-point.normal_radius*sin)
+                    );
+                }
+            }
+            for row in 0..strip.len().saturating_sub(1){
+                for segment in 0..segments{
+                    let a=first+row as u32*columns+segment;
+                    let (b,c,d)=(a+columns,a+columns+1,a+1);
+                    //rows on the axis collapse into a point, so one of the two triangles of the quad is empty
+                    if strip[row+1].radius!=0.0{
+                        self.vertex_indices.push(VertexIndices::new(a,b,c));
+                    }
+                    if strip[row].radius!=0.0{
+                        self.vertex_indices.push(VertexIndices::new(a,c,d));
+                    }
+                }
+            }
+        }
+    }
+
+    //subdivided icosahedron, with spherical texture coordinates
+    fn icosphere(&mut self,radius:f32,subdivisions:u32){
+        let t=(1.0+5.0f32.sqrt())*0.5;
+        let mut positions:Vec<glm::Vec3>=[
+            (-1.0,t,0.0),(1.0,t,0.0),(-1.0,-t,0.0),(1.0,-t,0.0),
+            (0.0,-1.0,t),(0.0,1.0,t),(0.0,-1.0,-t),(0.0,1.0,-t),
+            (t,0.0,-1.0),(t,0.0,1.0),(-t,0.0,-1.0),(-t,0.0,1.0),
+        ].iter().map(|(x,y,z)| glm::normalize(&glm::vec3(*x,*y,*z))).collect();
+        let mut triangles:Vec<[u32;3]>=vec![
+            [0,11,5],[0,5,1],[0,1,7],[0,7,10],[0,10,11],
+            [1,5,9],[5,11,4],[11,10,2],[10,7,6],[7,1,8],
+            [3,9,4],[3,4,2],[3,2,6],[3,6,8],[3,8,9],
+            [4,9,5],[2,4,11],[6,2,10],[8,6,7],[9,8,1],
+        ];
+
+        //split every triangle into 4, sharing the new edge midpoints between neighbouring triangles
+        for _ in 0..subdivisions{
+            let mut midpoints:std::collections::HashMap<(u32,u32),u32>=std::collections::HashMap::new();
+            let mut midpoint=|a:u32,b:u32,positions:&mut Vec<glm::Vec3>|->u32{
+                *midpoints.entry((a.min(b),a.max(b))).or_insert_with(||{
+                    positions.push(glm::normalize(&(positions[a as usize]+positions[b as usize])));
+                    (positions.len()-1) as u32
+                })
+            };
+            triangles=triangles.iter().flat_map(|[a,b,c]|{
+                let ab=midpoint(*a,*b,&mut positions);
+                let bc=midpoint(*b,*c,&mut positions);
+                let ca=midpoint(*c,*a,&mut positions);
+                vec![[*a,ab,ca],[*b,bc,ab],[*c,ca,bc],[ab,bc,ca]]
+            }).collect();
+        }
+
+        //u follows the same direction around the y axis as the uv sphere, v goes from bottom to top
+        let texture_coordinate=|position:&glm::Vec3| glm::vec2(
+            (-position.z).atan2(position.x)/(2.0*std::f32::consts::PI)+0.5,
+            1.0-position.y.clamp(-1.0,1.0).acos()/std::f32::consts::PI,
+        );
+        //vertices are duplicated where triangles cross the texture seam, or use a pole with a different u
+        let mut vertex_ids:std::collections::HashMap<(u32,u32),u32>=std::collections::HashMap::new();
+        for triangle in triangles.iter(){
+            let mut uvs=triangle.map(|i| texture_coordinate(&positions[i as usize]));
+            let max_u=uvs.iter().map(|uv| uv.x).fold(f32::MIN,f32::max);
+            for uv in uvs.iter_mut(){
+                if max_u-uv.x>0.5{
+                    uv.x+=1.0;
+                }
+            }
+            for corner in 0..3{
+                let position=positions[triangle[corner] as usize];
+                if position.x.abs()<1.0e-6 && position.z.abs()<1.0e-6{
+                    //u at the poles is undefined, use the center of the other corners
+                    uvs[corner].x=(uvs[(corner+1)%3].x+uvs[(corner+2)%3].x)*0.5;
+                }
+            }
+            let corners=[0,1,2].map(|corner|{
+                let (index,uv)=(triangle[corner],uvs[corner]);
+                let vertices=&mut self.vertices;
+                *vertex_ids.entry((index,uv.x.to_bits())).or_insert_with(||{
+                    let position=positions[index as usize];
+                    vertices.push(Vertex::new(position.x*radius,position.y*radius,position.z*radius,1.0,uv.x,uv.y).with_normal(position.x,position.y,position.z));
+                    (vertices.len()-1) as u32
+                })
+            });
+            self.vertex_indices.push(VertexIndices::new(corners[0],corners[1],corners[2]));
+        }
+    }
+}
+
+impl Decoder{
+    //upload a procedural mesh, meshes are cached by their parameters
+    //the tessellation is chosen by the caller, so no levels of detail are generated
+    pub fn get_primitive(&mut self,primitive:&Primitive,command_buffer:vk::CommandBuffer)->std::sync::Arc<Mesh>{
+        let name=format!("primitive:{:?}",primitive);
+        if let Some(mesh)=self.meshes.get(&name){
+            return mesh.clone();
+        }
+        let (vertices,vertex_indices)=primitive.geometry();
+        self.add_mesh(&name,&vertices,&vertex_indices,false,command_buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn position(vertex:&Vertex)->glm::Vec3{
+        glm::vec3(vertex.x,vertex.y,vertex.z)
+    }
+
+    fn normal(vertex:&Vertex)->glm::Vec3{
+        glm::vec3(vertex.nx,vertex.ny,vertex.nz)
+    }
+
+    //check vertex and triangle counts, unit normals and tangents, and that every triangle is wound counter clockwise from outside:
+    //its face normal agrees with the vertex normals and points away from the surface center returned by inside (for a centroid)
+    fn check(primitive:Primitive,vertex_count:usize,triangle_count:usize,inside:&dyn Fn(&glm::Vec3)->glm::Vec3){
+        let (vertices,vertex_indices)=primitive.geometry();
+        assert_eq!(vertices.len(),vertex_count,"vertices of {:?}",primitive);
+        assert_eq!(vertex_indices.len(),triangle_count,"triangles of {:?}",primitive);
+        //the last vertex of a row on the axis is not used by any triangle, so it keeps the default tangent
+        for vertex in vertex_indices.iter().flat_map(|triangle| triangle.corners()).map(|i| &vertices[i as usize]){
+            assert!((glm::length(&normal(vertex))-1.0).abs()<1e-5,"normal of {:?}",primitive);
+            let tangent=glm::vec3(vertex.tx,vertex.ty,vertex.tz);
+            assert!((glm::length(&tangent)-1.0).abs()<1e-3,"tangent of {:?}",primitive);
+            assert!(glm::dot(&tangent,&normal(vertex)).abs()<1e-3,"tangent of {:?}",primitive);
+        }
+        for triangle in vertex_indices.iter(){
+            let corners=triangle.corners().map(|i| &vertices[i as usize]);
+            let [a,b,c]=corners.map(position);
+            let face_normal=glm::cross(&(b-a),&(c-a));
+            assert!(glm::length(&face_normal)>1e-7,"degenerate triangle in {:?}",primitive);
+            for corner in corners.iter(){
+                assert!(glm::dot(&face_normal,&normal(corner))>0.0,"normal against winding in {:?}",primitive);
+            }
+            let centroid=(a+b+c)/3.0;
+            assert!(glm::dot(&face_normal,&(centroid-inside(&centroid)))>0.0,"inward triangle in {:?}",primitive);
+        }
+    }
+
+    fn origin(_:&glm::Vec3)->glm::Vec3{
+        glm::vec3(0.0,0.0,0.0)
+    }
+
+    #[test]
+    fn plane(){
+        check(Primitive::Plane{width:2.0,depth:3.0,subdivisions:4},25,32,&|p| p-glm::vec3(0.0,1.0,0.0));
+        let (vertices,_)=Primitive::Plane{width:2.0,depth:3.0,subdivisions:1}.geometry();
+        assert!(vertices.iter().all(|v| normal(v)==glm::vec3(0.0,1.0,0.0) && v.y==0.0 && v.x.abs()==1.0 && v.z.abs()==1.5));
+    }
+
+    #[test]
+    fn cube(){
+        check(Primitive::Cube{size:2.0,subdivisions:1},24,12,&origin);
+        check(Primitive::Cube{size:1.0,subdivisions:3},96,108,&origin);
+        let (vertices,_)=Primitive::Cube{size:2.0,subdivisions:2}.geometry();
+        assert!(vertices.iter().all(|v| [v.x,v.y,v.z].iter().all(|c| c.abs()<=1.0) && glm::dot(&position(v),&normal(v))==1.0));
+    }
+
+    #[test]
+    fn uv_sphere(){
+        //one vertex column more than segments for the seam, and single triangles at the poles
+        check(Primitive::UvSphere{radius:2.0,segments:8,rings:6},63,80,&origin);
+        let (vertices,_)=Primitive::UvSphere{radius:2.0,segments:8,rings:6}.geometry();
+        assert!(vertices.iter().all(|v| (glm::length(&position(v))-2.0).abs()<1e-5 && glm::distance(&(position(v)*0.5),&normal(v))<1e-5));
+    }
+
+    #[test]
+    fn icosphere(){
+        for subdivisions in 0..3{
+            let (vertices,vertex_indices)=Primitive::Icosphere{radius:1.5,subdivisions}.geometry();
+            let triangle_count=20*4usize.pow(subdivisions);
+            //distinct positions, plus the vertices duplicated at the seam and poles
+            let position_count=10*4usize.pow(subdivisions)+2;
+            assert!(vertices.len()>=position_count);
+            check(Primitive::Icosphere{radius:1.5,subdivisions},vertices.len(),triangle_count,&origin);
+            assert_eq!(vertex_indices.len(),triangle_count);
+            assert!(vertices.iter().all(|v| (glm::length(&position(v))-1.5).abs()<1e-5));
+        }
+    }
+
+    #[test]
+    fn cylinder(){
+        //top cap, side and bottom cap with 2 rows of 13 vertices each, the caps have one triangle per segment
+        //the triangles of a cap fan have different tangents, so the 11 inner rim vertices of each cap are split by tangent generation
+        check(Primitive::Cylinder{radius:1.0,height:2.0,segments:12},78+22,48,&|p| glm::vec3(0.0,p.y*0.5,0.0));
+    }
+
+    #[test]
+    fn cone(){
+        //side and bottom cap, both fans whose inner rim vertices are split like those of the cylinder caps
+        check(Primitive::Cone{radius:1.0,height:2.0,segments:12},52+22,24,&|p| glm::vec3(0.0,p.y.min(-0.5)+0.5,0.0));
+        let (vertices,_)=Primitive::Cone{radius:1.0,height:2.0,segments:12}.geometry();
+        //side normals are perpendicular to the slope
+        let slope=glm::vec3(1.0,-2.0,0.0);
+        assert!(vertices.iter().filter(|v| v.z==0.0 && v.x>0.0 && v.ny>0.0).all(|v| glm::dot(&normal(v),&slope).abs()<1e-5));
+    }
+
+    #[test]
+    fn torus(){
+        //inside is the center of the tube
+        check(Primitive::Torus{major_radius:2.0,minor_radius:0.5,major_segments:16,minor_segments:8},153,256,&|p| glm::normalize(&glm::vec3(p.x,0.0,p.z))*2.0);
+    }
+
+    #[test]
+    fn capsule(){
+        //inside is the axis segment between the hemisphere centers
+        check(Primitive::Capsule{radius:0.5,height:1.0,segments:8,rings:4},90,128,&|p| glm::vec3(0.0,p.y.clamp(-0.5,0.5),0.0));
+        let (vertices,_)=Primitive::Capsule{radius:0.5,height:1.0,segments:8,rings:4}.geometry();
+        assert!(vertices.iter().all(|v| (0.0..=1.0).contains(&v.v)));
+        assert!(vertices.iter().any(|v| v.y==1.0) && vertices.iter().any(|v| v.y==-1.0));
+    }
+}
@@ -0,0 +1,277 @@
+use ash::{
+    vk,
+    version::{
+        DeviceV1_0,
+    },
+    Device,
+};
+
+//everything required to record a single (possibly instanced) indexed draw call
+#[derive(Debug,Clone,Copy)]
+pub struct DrawItem{
+    pub sort_key:u64,
+
+    pub pipeline:vk::Pipeline,
+    pub pipeline_layout:vk::PipelineLayout,
+    pub descriptor_set:vk::DescriptorSet,
+    pub lights_descriptor_set:Option<vk::DescriptorSet>,//bound as set 1, for lit pipelines
+
+    pub vertex_buffer:vk::Buffer,
+    pub instance_buffer:Option<vk::Buffer>,
+    pub index_buffer:vk::Buffer,
+    pub index_type:vk::IndexType,
+
+    pub first_index:u32,
+    pub index_count:u32,
+    pub first_instance:u32,
+    pub instance_count:u32,
+
+    pub transform:glm::Mat4,//model matrix
+}
+
+//number of state changes and draws recorded for the last frame
+#[derive(Debug,Clone,Copy,Default,PartialEq)]
+pub struct FrameStatistics{
+    pub pipeline_binds:u32,
+    pub descriptor_set_binds:u32,
+    pub vertex_buffer_binds:u32,
+    pub index_buffer_binds:u32,
+    pub draws:u32,
+    pub instances:u32,
+    //objects and instances outside of the view frustum, which were not drawn
+    pub culled_objects:u32,
+    pub culled_instances:u32,
+}
+
+//draw items collected for one frame, sorted before recording to minimize state changes
+//
+//sort key layout (most significant bits first):
+//  1 bit   transparent (transparent items are drawn after all opaque ones)
+//  opaque:      8 bits pipeline, 12 bits material, 12 bits mesh, 31 bits depth (front to back)
+//  transparent: 31 bits inverted depth (back to front), 8 bits pipeline, 12 bits material, 12 bits mesh
+//
+//pipeline, material and mesh ids are assigned per frame, in the order they are first seen
+#[derive(Default)]
+pub struct RenderQueue{
+    pub items:Vec<DrawItem>,
+    pipeline_ids:std::collections::HashMap<vk::Pipeline,u64>,
+    material_ids:std::collections::HashMap<vk::DescriptorSet,u64>,
+    mesh_ids:std::collections::HashMap<vk::Buffer,u64>,
+}
+impl RenderQueue{
+    const PIPELINE_BITS:u64=8;
+    const MATERIAL_BITS:u64=12;
+    const MESH_BITS:u64=12;
+    const DEPTH_BITS:u64=31;
+
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    pub fn clear(&mut self){
+        self.items.clear();
+        self.pipeline_ids.clear();
+        self.material_ids.clear();
+        self.mesh_ids.clear();
+    }
+
+    fn id<K:std::hash::Hash+Eq>(ids:&mut std::collections::HashMap<K,u64>,key:K,bits:u64)->u64{
+        let next_id=ids.len() as u64;
+        //ids that do not fit into the key wrap around, which only makes sorting less effective
+        *ids.entry(key).or_insert(next_id) & ((1<<bits)-1)
+    }
+
+    //depth is normalized to [0;1] (e.g. camera distance divided by far plane distance), and clamped to that range
+    pub fn sort_key(&mut self,item:&DrawItem,transparent:bool,depth:f32)->u64{
+        let pipeline=Self::id(&mut self.pipeline_ids,item.pipeline,Self::PIPELINE_BITS);
+        let material=Self::id(&mut self.material_ids,item.descriptor_set,Self::MATERIAL_BITS);
+        let mesh=Self::id(&mut self.mesh_ids,item.vertex_buffer,Self::MESH_BITS);
+
+        let max_depth=(1u64<<Self::DEPTH_BITS)-1;
+        let depth=(depth.clamp(0.0,1.0) as f64*max_depth as f64) as u64;
+
+        let state=(pipeline<<(Self::MATERIAL_BITS+Self::MESH_BITS)) | (material<<Self::MESH_BITS) | mesh;
+        let state_bits=Self::PIPELINE_BITS+Self::MATERIAL_BITS+Self::MESH_BITS;
+        if transparent{
+            (1<<63) | ((max_depth-depth)<<state_bits) | state
+        }else{
+            (state<<Self::DEPTH_BITS) | depth
+        }
+    }
+
+    pub fn push(&mut self,mut item:DrawItem,transparent:bool,depth:f32){
+        item.sort_key=self.sort_key(&item,transparent,depth);
+        self.items.push(item);
+    }
+
+    pub fn sort(&mut self){
+        //stable, so that items with identical keys keep submission order
+        self.items.sort_by_key(|item| item.sort_key);
+    }
+
+    //record all items into the command buffer, skipping binds of state that is already bound
+    //view and projection are pushed together with the model matrix of each item
+    pub fn record(&self,device:&Device,command_buffer:vk::CommandBuffer,view:&glm::Mat4,projection:&glm::Mat4,viewport:&vk::Viewport,scissor:&vk::Rect2D)->FrameStatistics{
+        let mut statistics=FrameStatistics::default();
+
+        let mut bound_pipeline=vk::Pipeline::null();
+        let mut bound_pipeline_layout=vk::PipelineLayout::null();
+        let mut bound_descriptor_set=vk::DescriptorSet::null();
+        let mut bound_lights_descriptor_set=None;
+        let mut bound_vertex_buffers=(vk::Buffer::null(),None);
+        let mut bound_index_buffer=(vk::Buffer::null(),vk::IndexType::UINT16);
+
+        for item in self.items.iter(){
+            if item.pipeline!=bound_pipeline{
+                unsafe{
+                    device.cmd_bind_pipeline(command_buffer,vk::PipelineBindPoint::GRAPHICS,item.pipeline);
+                    device.cmd_set_viewport(command_buffer,0,&[*viewport]);
+                    device.cmd_set_scissor(command_buffer,0,&[*scissor]);
+                }
+                bound_pipeline=item.pipeline;
+                statistics.pipeline_binds+=1;
+            }
+            //descriptor sets stay bound across pipelines, as long as the pipeline layout does not change
+            if item.pipeline_layout!=bound_pipeline_layout{
+                bound_pipeline_layout=item.pipeline_layout;
+                bound_descriptor_set=vk::DescriptorSet::null();
+                bound_lights_descriptor_set=None;
+            }
+            if item.descriptor_set!=bound_descriptor_set{
+                unsafe{
+                    device.cmd_bind_descriptor_sets(command_buffer,vk::PipelineBindPoint::GRAPHICS,item.pipeline_layout,0,&[item.descriptor_set],&[]);
+                }
+                bound_descriptor_set=item.descriptor_set;
+                statistics.descriptor_set_binds+=1;
+            }
+            if let Some(lights_descriptor_set)=item.lights_descriptor_set{
+                if Some(lights_descriptor_set)!=bound_lights_descriptor_set{
+                    unsafe{
+                        device.cmd_bind_descriptor_sets(command_buffer,vk::PipelineBindPoint::GRAPHICS,item.pipeline_layout,1,&[lights_descriptor_set],&[]);
+                    }
+                    bound_lights_descriptor_set=Some(lights_descriptor_set);
+                    statistics.descriptor_set_binds+=1;
+                }
+            }
+            if (item.vertex_buffer,item.instance_buffer)!=bound_vertex_buffers{
+                unsafe{
+                    match item.instance_buffer{
+                        Some(instance_buffer)=>device.cmd_bind_vertex_buffers(command_buffer,0,&[item.vertex_buffer,instance_buffer],&[0,0]),
+                        None=>device.cmd_bind_vertex_buffers(command_buffer,0,&[item.vertex_buffer],&[0]),
+                    }
+                }
+                bound_vertex_buffers=(item.vertex_buffer,item.instance_buffer);
+                statistics.vertex_buffer_binds+=1;
+            }
+            if (item.index_buffer,item.index_type)!=bound_index_buffer{
+                unsafe{
+                    device.cmd_bind_index_buffer(command_buffer,item.index_buffer,0,item.index_type);
+                }
+                bound_index_buffer=(item.index_buffer,item.index_type);
+                statistics.index_buffer_binds+=1;
+            }
+            //push constants
+            unsafe{
+                device.cmd_push_constants(
+                    command_buffer,
+                    item.pipeline_layout,
+                    vk::ShaderStageFlags::VERTEX,
+                    0,
+                    &*(&[item.transform,*view,*projection] as *const [glm::Mat4;3] as *const [u8;16*4*3]),
+                );
+            }
+            //draw
+            unsafe{
+                device.cmd_draw_indexed(command_buffer,item.index_count,item.instance_count,item.first_index,0,item.first_instance);
+            }
+            statistics.draws+=1;
+            statistics.instances+=item.instance_count;
+        }
+
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use ash::vk::Handle;
+
+    //item with handles made from raw values, which is enough for sorting
+    fn item(pipeline:u64,material:u64,mesh:u64)->DrawItem{
+        DrawItem{
+            sort_key:0,
+            pipeline:vk::Pipeline::from_raw(pipeline),
+            pipeline_layout:vk::PipelineLayout::null(),
+            descriptor_set:vk::DescriptorSet::from_raw(material),
+            lights_descriptor_set:None,
+            vertex_buffer:vk::Buffer::from_raw(mesh),
+            instance_buffer:None,
+            index_buffer:vk::Buffer::null(),
+            index_type:vk::IndexType::UINT32,
+            first_index:0,
+            index_count:3,
+            first_instance:0,
+            instance_count:1,
+            transform:glm::identity(),
+        }
+    }
+
+    #[test]
+    fn key_packing(){
+        let mut render_queue=RenderQueue::new();
+        let max_depth=(1u64<<31)-1;
+        //ids are assigned in the order handles are first seen
+        assert_eq!(render_queue.sort_key(&item(10,20,30),false,0.0),0);
+        assert_eq!(render_queue.sort_key(&item(11,21,31),false,1.0),(1<<(24+31)) | (1<<(12+31)) | (1<<31) | max_depth);
+        assert_eq!(render_queue.sort_key(&item(10,22,32),false,0.5),(2<<(12+31)) | (2<<31) | (max_depth/2));
+        //depth is clamped
+        assert_eq!(render_queue.sort_key(&item(10,20,30),false,-1.0),0);
+        assert_eq!(render_queue.sort_key(&item(10,20,30),false,2.0),max_depth);
+
+        //transparent items have the top bit, and inverted depth above the state
+        assert_eq!(render_queue.sort_key(&item(10,20,30),true,1.0),1<<63);
+        assert_eq!(render_queue.sort_key(&item(11,21,31),true,0.0),(1<<63) | (max_depth<<32) | (1<<24) | (1<<12) | 1);
+    }
+
+    #[test]
+    fn transparent_after_opaque(){
+        let mut render_queue=RenderQueue::new();
+        render_queue.push(item(1,1,1),true,0.2);
+        render_queue.push(item(2,2,2),false,1.0);
+        render_queue.push(item(1,1,1),true,0.9);
+        render_queue.push(item(3,3,3),false,0.1);
+        render_queue.push(item(2,2,2),false,0.3);
+        render_queue.sort();
+
+        let order:Vec<(u64,bool)>=render_queue.items.iter().map(|item| (item.pipeline.as_raw(),item.sort_key>>63==1)).collect();
+        //opaque by pipeline (in order of first use), then transparent
+        assert_eq!(order,vec![(2,false),(2,false),(3,false),(1,true),(1,true)]);
+        //opaque front to back within a pipeline, transparent back to front
+        assert!(render_queue.items[0].sort_key<render_queue.items[1].sort_key);
+        assert_eq!(render_queue.items[0].sort_key&((1<<31)-1),(0.3f32 as f64*((1u64<<31)-1) as f64) as u64);
+        let transparent_depth=|item:&DrawItem| (1u64<<31)-1-((item.sort_key>>32)&((1<<31)-1));
+        assert_eq!(transparent_depth(&render_queue.items[3]),(0.9f32 as f64*((1u64<<31)-1) as f64) as u64);
+        assert_eq!(transparent_depth(&render_queue.items[4]),(0.2f32 as f64*((1u64<<31)-1) as f64) as u64);
+    }
+
+    #[test]
+    fn ids_wrap(){
+        let mut render_queue=RenderQueue::new();
+        let key=render_queue.sort_key(&item(0,0,0),false,0.0);
+        assert_eq!(key,0);
+        //pipeline ids wrap after 8 bits, material and mesh ids after 12 bits
+        for i in 1..256{
+            render_queue.sort_key(&item(i,0,0),false,0.0);
+        }
+        assert_eq!(render_queue.sort_key(&item(255,0,0),false,0.0),255<<(24+31));
+        assert_eq!(render_queue.sort_key(&item(256,0,0),false,0.0),key);
+        for i in 1..4097{
+            render_queue.sort_key(&item(0,i,i),false,0.0);
+        }
+        assert_eq!(render_queue.sort_key(&item(0,4095,4095),false,0.0),(4095<<(12+31)) | (4095<<31));
+        assert_eq!(render_queue.sort_key(&item(0,4096,4096),false,0.0),key);
+        //wrapped ids do not spill into the transparent bit or the neighbouring fields
+        assert_eq!(render_queue.sort_key(&item(256,4096,4096),true,0.0)>>32,(1<<31) | ((1<<31)-1));
+    }
+}
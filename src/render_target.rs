@@ -0,0 +1,99 @@
+use ash::{
+    vk,
+    version::{
+        InstanceV1_0,
+        DeviceV1_0,
+    },
+    Instance,
+    Device,
+};
+
+//image with bound memory and a view, used as render pass attachment
+#[derive(Debug,Clone,Copy)]
+pub struct Attachment{
+    pub format:vk::Format,
+    pub samples:vk::SampleCountFlags,
+    pub memory:vk::DeviceMemory,
+    pub image:vk::Image,
+    pub image_view:vk::ImageView,
+}
+impl Attachment{
+    pub fn destroy(&self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>){
+        unsafe{
+            device.destroy_image_view(self.image_view,allocation_callbacks);
+            device.destroy_image(self.image,allocation_callbacks);
+            device.free_memory(self.memory,allocation_callbacks);
+        }
+    }
+}
+
+//attachments that are rendered to, but not presented/sampled themselves
+//
+//the multisampled color attachment is only present if the sample count is above 1, and is resolved into the
+//final color image (swapchain image or offscreen color image) at the end of the render pass
+#[derive(Debug,Clone,Copy)]
+pub struct TransientAttachments{
+    pub multisample_color:Option<Attachment>,
+    pub depth:Attachment,
+}
+impl TransientAttachments{
+    pub fn destroy(&self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>){
+        if let Some(multisample_color)=self.multisample_color{
+            multisample_color.destroy(device,allocation_callbacks);
+        }
+        self.depth.destroy(device,allocation_callbacks);
+    }
+}
+
+//render target that is not presented to a window, rendered with the same render pass as windows
+//
+//the color image is in layout COLOR_ATTACHMENT_OPTIMAL before and after rendering (like swapchain images)
+pub struct RenderTarget{
+    pub extent:vk::Extent2D,
+    pub color:Attachment,
+    pub transient_attachments:TransientAttachments,
+    pub framebuffer:vk::Framebuffer,
+}
+impl RenderTarget{
+    pub fn destroy(&self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>){
+        unsafe{
+            device.destroy_framebuffer(self.framebuffer,allocation_callbacks);
+        }
+        self.transient_attachments.destroy(device,allocation_callbacks);
+        self.color.destroy(device,allocation_callbacks);
+    }
+}
+
+//pick first depth format from preference list that supports being used as depth attachment
+pub fn find_depth_format(instance:&Instance,physical_device:vk::PhysicalDevice)->vk::Format{
+    for format in &[
+        vk::Format::D32_SFLOAT,
+        vk::Format::D32_SFLOAT_S8_UINT,
+        vk::Format::D24_UNORM_S8_UINT,
+        vk::Format::D16_UNORM,
+    ]{
+        let format_properties=unsafe{
+            instance.get_physical_device_format_properties(physical_device,*format)
+        };
+        if format_properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT){
+            return *format;
+        }
+    }
+    panic!("no supported depth format found");
+}
+
+//return highest sample count that is supported and not higher than the requested number of samples
+//
+//valid requests are 1, 2, 4 and 8 (other values are rounded down to the next one of those)
+pub fn clamp_sample_count(requested_samples:u32,supported_sample_counts:vk::SampleCountFlags)->vk::SampleCountFlags{
+    for (samples,flag) in &[
+        (8,vk::SampleCountFlags::TYPE_8),
+        (4,vk::SampleCountFlags::TYPE_4),
+        (2,vk::SampleCountFlags::TYPE_2),
+    ]{
+        if requested_samples>=*samples && supported_sample_counts.contains(*flag){
+            return *flag;
+        }
+    }
+    vk::SampleCountFlags::TYPE_1
+}
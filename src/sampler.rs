@@ -0,0 +1,104 @@
+use ash::{
+    vk,
+    version::{
+        DeviceV1_0,
+    },
+    Device,
+};
+
+//how a texture is sampled, samplers are shared between all textures with the same description
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub struct SamplerDescription{
+    pub mag_filter:vk::Filter,
+    pub min_filter:vk::Filter,
+    pub mipmap_mode:vk::SamplerMipmapMode,
+    pub address_mode_u:vk::SamplerAddressMode,
+    pub address_mode_v:vk::SamplerAddressMode,
+    pub address_mode_w:vk::SamplerAddressMode,
+    pub border_color:vk::BorderColor,//used with CLAMP_TO_BORDER
+    //1 disables anisotropic filtering, clamped to what the device supports
+    pub max_anisotropy:u32,
+    //depth comparison, e.g. for shadow maps
+    pub compare_op:Option<vk::CompareOp>,
+}
+impl Default for SamplerDescription{
+    fn default()->Self{
+        Self{
+            mag_filter:vk::Filter::LINEAR,
+            min_filter:vk::Filter::LINEAR,
+            mipmap_mode:vk::SamplerMipmapMode::LINEAR,
+            address_mode_u:vk::SamplerAddressMode::REPEAT,
+            address_mode_v:vk::SamplerAddressMode::REPEAT,
+            address_mode_w:vk::SamplerAddressMode::REPEAT,
+            border_color:vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            max_anisotropy:16,
+            compare_op:None,
+        }
+    }
+}
+impl SamplerDescription{
+    //same address mode in all directions
+    pub fn with_address_mode(self,address_mode:vk::SamplerAddressMode)->Self{
+        Self{
+            address_mode_u:address_mode,
+            address_mode_v:address_mode,
+            address_mode_w:address_mode,
+            ..self
+        }
+    }
+}
+
+//samplers by description, created on first use
+pub struct SamplerCache{
+    samplers:std::collections::HashMap<SamplerDescription,vk::Sampler>,
+    //0 if the samplerAnisotropy device feature is not enabled
+    pub max_anisotropy:f32,
+}
+impl SamplerCache{
+    pub fn new(max_anisotropy:f32)->Self{
+        Self{
+            samplers:std::collections::HashMap::new(),
+            max_anisotropy,
+        }
+    }
+
+    pub fn get(&mut self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>,description:&SamplerDescription)->vk::Sampler{
+        if let Some(sampler)=self.samplers.get(description){
+            return *sampler;
+        }
+
+        let max_anisotropy=(description.max_anisotropy as f32).min(self.max_anisotropy);
+        let sampler_create_info=vk::SamplerCreateInfo{
+            mag_filter:description.mag_filter,
+            min_filter:description.min_filter,
+            mipmap_mode:description.mipmap_mode,
+            address_mode_u:description.address_mode_u,
+            address_mode_v:description.address_mode_v,
+            address_mode_w:description.address_mode_w,
+            mip_lod_bias:0.0,
+            anisotropy_enable:(max_anisotropy>1.0) as u32,
+            max_anisotropy:max_anisotropy.max(1.0),
+            compare_enable:description.compare_op.is_some() as u32,
+            compare_op:description.compare_op.unwrap_or(vk::CompareOp::ALWAYS),
+            min_lod:0.0,
+            max_lod:vk::LOD_CLAMP_NONE,//all mip levels of the texture
+            border_color:description.border_color,
+            unnormalized_coordinates:false as u32,
+            ..Default::default()
+        };
+        let sampler=unsafe{
+            device.create_sampler(&sampler_create_info,allocation_callbacks)
+        }.unwrap();
+
+        self.samplers.insert(*description,sampler);
+        sampler
+    }
+
+    pub fn destroy(&mut self,device:&Device,allocation_callbacks:Option<&vk::AllocationCallbacks>){
+        for (_description,sampler) in self.samplers.drain(){
+            unsafe{
+                device.destroy_sampler(sampler,allocation_callbacks);
+            }
+        }
+    }
+}
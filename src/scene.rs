@@ -0,0 +1,336 @@
+use crate::{Mesh,Material,Camera,Object,Model};
+
+//index of a node in the scene
+pub type NodeId=usize;
+
+pub struct Node{
+    pub name:String,
+
+    local_transform:glm::Mat4,//relative to parent
+    world_transform:glm::Mat4,//cached, valid after Scene::update_world_transforms if not dirty
+    dirty:bool,//local transform changed since world transform was last computed
+
+    parent:Option<NodeId>,
+    children:Vec<NodeId>,
+
+    //node is drawn if it has both mesh and material
+    pub mesh:Option<std::sync::Arc<Mesh>>,
+    pub material:Option<std::sync::Arc<Material>>,
+    //camera in node space
+    pub camera:Option<Camera>,
+}
+impl Node{
+    fn new(name:&str,parent:Option<NodeId>)->Self{
+        Self{
+            name:String::from(name),
+
+            local_transform:glm::identity(),
+            world_transform:glm::identity(),
+            dirty:true,
+
+            parent,
+            children:Vec::new(),
+
+            mesh:None,
+            material:None,
+            camera:None,
+        }
+    }
+
+    pub fn local_transform(&self)->&glm::Mat4{
+        &self.local_transform
+    }
+
+    //only up to date after Scene::update_world_transforms
+    pub fn world_transform(&self)->&glm::Mat4{
+        &self.world_transform
+    }
+
+    pub fn parent(&self)->Option<NodeId>{
+        self.parent
+    }
+
+    pub fn children(&self)->&[NodeId]{
+        &self.children
+    }
+}
+
+//tree of nodes, stored in a list (removed nodes leave an empty slot, which is reused)
+pub struct Scene{
+    nodes:Vec<Option<Node>>,
+    free_nodes:Vec<NodeId>,
+    pub root:NodeId,
+    //node whose camera is used for drawing, if any
+    pub active_camera:Option<NodeId>,
+}
+impl Scene{
+    pub fn new()->Self{
+        Self{
+            nodes:vec![Some(Node::new("root",None))],
+            free_nodes:Vec::new(),
+            root:0,
+            active_camera:None,
+        }
+    }
+
+    pub fn node(&self,id:NodeId)->&Node{
+        self.nodes[id].as_ref().expect("node was removed")
+    }
+    pub fn node_mut(&mut self,id:NodeId)->&mut Node{
+        self.nodes[id].as_mut().expect("node was removed")
+    }
+
+    //number of nodes, including root
+    pub fn len(&self)->usize{
+        self.nodes.len()-self.free_nodes.len()
+    }
+    //true if the scene only contains the root node
+    pub fn is_empty(&self)->bool{
+        self.len()==1
+    }
+
+    pub fn add_node(&mut self,name:&str,parent:NodeId)->NodeId{
+        let node=Node::new(name,Some(parent));
+        let id=match self.free_nodes.pop(){
+            Some(id)=>{
+                self.nodes[id]=Some(node);
+                id
+            },
+            None=>{
+                self.nodes.push(Some(node));
+                self.nodes.len()-1
+            }
+        };
+        self.node_mut(parent).children.push(id);
+        id
+    }
+
+    //remove node and all of its descendants
+    pub fn remove_node(&mut self,id:NodeId){
+        if id==self.root{
+            panic!("cannot remove root node");
+        }
+        if let Some(parent)=self.node(id).parent{
+            self.node_mut(parent).children.retain(|child| *child!=id);
+        }
+        let mut stack=vec![id];
+        while let Some(id)=stack.pop(){
+            let node=self.nodes[id].take().expect("node was removed");
+            stack.extend(node.children);
+            self.free_nodes.push(id);
+            if self.active_camera==Some(id){
+                self.active_camera=None;
+            }
+        }
+    }
+
+    //add a node for the model, with the model node hierarchy below it (if it has one)
+    //every submesh gets its own node, drawn with the material at the same index
+    pub fn add_model(&mut self,name:&str,parent:NodeId,model:&Model,materials:&[std::sync::Arc<Material>])->NodeId{
+        assert!(materials.len()==model.submeshes.len());
+        let id=self.add_node(name,parent);
+        let add_submeshes=|scene:&mut Self,parent:NodeId,submeshes:&mut dyn Iterator<Item=usize>|{
+            for submesh_index in submeshes{
+                let child=scene.add_node(&model.submeshes[submesh_index].name,parent);
+                let node=scene.node_mut(child);
+                node.mesh=Some(model.submeshes[submesh_index].mesh.clone());
+                node.material=Some(materials[submesh_index].clone());
+            }
+        };
+        if model.nodes.is_empty(){
+            add_submeshes(self,id,&mut (0..model.submeshes.len()));
+            return id;
+        }
+        //(model node, scene parent node)
+        let mut stack:Vec<(usize,NodeId)>=model.root_nodes.iter().rev().map(|model_node| (*model_node,id)).collect();
+        while let Some((model_node,parent))=stack.pop(){
+            let model_node=&model.nodes[model_node];
+            let node=self.add_node(&model_node.name,parent);
+            self.set_local_transform(node,model_node.local_transform);
+            add_submeshes(self,node,&mut model_node.submeshes.iter().copied());
+            stack.extend(model_node.children.iter().rev().map(|child| (*child,node)));
+        }
+        id
+    }
+
+    //move node (with its descendants) to a new parent, keeping its local transform
+    pub fn set_parent(&mut self,id:NodeId,parent:NodeId){
+        let mut ancestor=Some(parent);
+        while let Some(a)=ancestor{
+            if a==id{
+                panic!("node cannot be parented to its own descendant");
+            }
+            ancestor=self.node(a).parent;
+        }
+        if let Some(old_parent)=self.node(id).parent{
+            self.node_mut(old_parent).children.retain(|child| *child!=id);
+        }
+        self.node_mut(parent).children.push(id);
+        let node=self.node_mut(id);
+        node.parent=Some(parent);
+        node.dirty=true;
+    }
+
+    pub fn set_local_transform(&mut self,id:NodeId,local_transform:glm::Mat4){
+        let node=self.node_mut(id);
+        node.local_transform=local_transform;
+        node.dirty=true;
+    }
+
+    //recompute world transforms of dirty nodes and their descendants
+    pub fn update_world_transforms(&mut self){
+        //(node, parent world transform if it changed)
+        let mut stack:Vec<(NodeId,Option<glm::Mat4>)>=vec![(self.root,None)];
+        while let Some((id,changed_parent_world_transform))=stack.pop(){
+            let parent_world_transform=match self.node(id).parent{
+                Some(parent)=>self.node(parent).world_transform,
+                None=>glm::identity(),
+            };
+            let node=self.node_mut(id);
+            let changed_world_transform=if node.dirty || changed_parent_world_transform.is_some(){
+                node.world_transform=parent_world_transform*node.local_transform;
+                node.dirty=false;
+                Some(node.world_transform)
+            }else{
+                None
+            };
+            for child in node.children.iter(){
+                stack.push((*child,changed_world_transform));
+            }
+        }
+    }
+
+    //camera of the active camera node, transformed into world space
+    pub fn active_camera(&self)->Option<Camera>{
+        let node=self.node(self.active_camera?);
+        let camera=node.camera?;
+        let transform_point=|p:&glm::Vec3| glm::vec4_to_vec3(&(node.world_transform*glm::vec4(p.x,p.y,p.z,1.0)));
+        let transform_direction=|d:&glm::Vec3| glm::vec4_to_vec3(&(node.world_transform*glm::vec4(d.x,d.y,d.z,0.0)));
+        Some(Camera{
+            eye:transform_point(&camera.eye),
+            target:transform_point(&camera.target),
+            up:transform_direction(&camera.up),
+            ..camera
+        })
+    }
+
+    //walk the scene depth first, and return all drawable nodes with their world transforms
+    //world transforms must be up to date (see update_world_transforms)
+    pub fn objects(&self)->Vec<Object>{
+        let mut objects=Vec::new();
+        let mut stack=vec![self.root];
+        while let Some(id)=stack.pop(){
+            let node=self.node(id);
+            if let (Some(mesh),Some(material))=(&node.mesh,&node.material){
+                objects.push(Object{
+                    mesh:mesh.clone(),
+                    material:material.clone(),
+                    transform:node.world_transform,
+                });
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        objects
+    }
+}
+impl Default for Scene{
+    fn default()->Self{
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn translation(x:f32,y:f32,z:f32)->glm::Mat4{
+        glm::translation(&glm::vec3(x,y,z))
+    }
+
+    fn position(scene:&Scene,id:NodeId)->glm::Vec3{
+        glm::vec4_to_vec3(&scene.node(id).world_transform().column(3).into_owned())
+    }
+
+    #[test]
+    fn world_transforms(){
+        let mut scene=Scene::new();
+        let a=scene.add_node("a",scene.root);
+        let b=scene.add_node("b",a);
+        let c=scene.add_node("c",b);
+        scene.set_local_transform(a,translation(1.0,0.0,0.0));
+        scene.set_local_transform(b,translation(0.0,2.0,0.0));
+        scene.set_local_transform(c,glm::scaling(&glm::vec3(2.0,2.0,2.0))*translation(0.0,0.0,3.0));
+        scene.update_world_transforms();
+        assert_eq!(position(&scene,a),glm::vec3(1.0,0.0,0.0));
+        assert_eq!(position(&scene,b),glm::vec3(1.0,2.0,0.0));
+        assert_eq!(position(&scene,c),glm::vec3(1.0,2.0,6.0));
+        assert!([a,b,c].iter().all(|id| !scene.node(*id).dirty));
+
+        //changing an ancestor updates all descendants, but only the changed node is marked dirty
+        scene.set_local_transform(a,translation(-1.0,0.0,0.0));
+        assert!(scene.node(a).dirty && !scene.node(b).dirty && !scene.node(c).dirty);
+        //world transforms are cached until updated
+        assert_eq!(position(&scene,c),glm::vec3(1.0,2.0,6.0));
+        scene.update_world_transforms();
+        assert_eq!(position(&scene,b),glm::vec3(-1.0,2.0,0.0));
+        assert_eq!(position(&scene,c),glm::vec3(-1.0,2.0,6.0));
+    }
+
+    #[test]
+    fn reparenting(){
+        let mut scene=Scene::new();
+        let a=scene.add_node("a",scene.root);
+        let b=scene.add_node("b",scene.root);
+        let child=scene.add_node("child",a);
+        let grandchild=scene.add_node("grandchild",child);
+        scene.set_local_transform(a,translation(1.0,0.0,0.0));
+        scene.set_local_transform(b,translation(0.0,0.0,5.0));
+        scene.set_local_transform(child,translation(0.0,1.0,0.0));
+        scene.set_local_transform(grandchild,translation(0.0,1.0,0.0));
+        scene.update_world_transforms();
+        assert_eq!(position(&scene,grandchild),glm::vec3(1.0,2.0,0.0));
+
+        //the local transform is kept, so the world transforms of the moved subtree change
+        scene.set_parent(child,b);
+        assert_eq!(scene.node(a).children(),&[] as &[NodeId]);
+        assert_eq!(scene.node(b).children(),&[child]);
+        assert_eq!(scene.node(child).parent(),Some(b));
+        assert!(scene.node(child).dirty);
+        scene.update_world_transforms();
+        assert_eq!(position(&scene,child),glm::vec3(0.0,1.0,5.0));
+        assert_eq!(position(&scene,grandchild),glm::vec3(0.0,2.0,5.0));
+        assert_eq!(*scene.node(grandchild).local_transform(),translation(0.0,1.0,0.0));
+
+        //the new parent propagates changes, the old one does not anymore
+        scene.set_local_transform(a,translation(7.0,0.0,0.0));
+        scene.set_local_transform(b,translation(0.0,0.0,-5.0));
+        scene.update_world_transforms();
+        assert_eq!(position(&scene,grandchild),glm::vec3(0.0,2.0,-5.0));
+    }
+
+    #[test]
+    #[should_panic(expected="node cannot be parented to its own descendant")]
+    fn reparenting_to_descendant(){
+        let mut scene=Scene::new();
+        let a=scene.add_node("a",scene.root);
+        let b=scene.add_node("b",a);
+        scene.set_parent(a,b);
+    }
+
+    #[test]
+    fn removed_slots_are_reused(){
+        let mut scene=Scene::new();
+        let a=scene.add_node("a",scene.root);
+        let b=scene.add_node("b",a);
+        scene.add_node("c",b);
+        scene.active_camera=Some(b);
+        assert_eq!(scene.len(),4);
+        scene.remove_node(a);
+        assert!(scene.is_empty());
+        assert_eq!(scene.active_camera,None);
+        assert!(scene.node(scene.root).children().is_empty());
+        let d=scene.add_node("d",scene.root);
+        assert!(d<=3);
+        assert_eq!(scene.len(),2);
+    }
+}
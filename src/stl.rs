@@ -0,0 +1,171 @@
+use ash::{
+    vk,
+};
+
+use crate::{Decoder,Vertex};
+use crate::decoder::VertexIndices;
+use crate::model::{Model,Submesh};
+use crate::mesh_processing;
+
+//triangle corners and facet normal
+type Facet=([glm::Vec3;3],glm::Vec3);
+
+fn read_f32(bytes:&[u8],offset:usize)->f32{
+    f32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+
+//binary stl: 80 byte header, triangle count, then 50 bytes per triangle (normal, 3 corners, attribute byte count)
+fn parse_binary(bytes:&[u8])->Vec<Facet>{
+    let count=u32::from_le_bytes([bytes[80],bytes[81],bytes[82],bytes[83]]) as usize;
+    (0..count).map(|i|{
+        let offset=84+i*50;
+        let vec3=|at:usize| glm::vec3(read_f32(bytes,at),read_f32(bytes,at+4),read_f32(bytes,at+8));
+        ([vec3(offset+12),vec3(offset+24),vec3(offset+36)],vec3(offset))
+    }).collect()
+}
+
+fn parse_ascii(text:&str)->Vec<Facet>{
+    let mut facets=Vec::new();
+    let mut normal=glm::vec3(0.0,0.0,0.0);
+    let mut corners=Vec::with_capacity(3);
+    for line in text.lines(){
+        let mut words=line.split_whitespace();
+        let vec3=|words:&mut std::str::SplitWhitespace|{
+            let mut component=|| words.next().and_then(|w| w.parse::<f32>().ok()).expect("invalid stl coordinate");
+            glm::vec3(component(),component(),component())
+        };
+        match words.next(){
+            Some("facet")=>{
+                //"facet normal nx ny nz"
+                words.next();
+                normal=vec3(&mut words);
+                corners.clear();
+            },
+            Some("vertex")=>corners.push(vec3(&mut words)),
+            Some("endfacet")=>{
+                //polygons with more than 3 corners are fan triangulated
+                for i in 1..corners.len().saturating_sub(1){
+                    facets.push(([corners[0],corners[i],corners[i+1]],normal));
+                }
+            },
+            _=>{},
+        }
+    }
+    facets
+}
+
+//binary files may also start with "solid", so a file is binary if its size matches the triangle count
+fn is_binary(bytes:&[u8])->bool{
+    bytes.len()>=84 && {
+        let count=u32::from_le_bytes([bytes[80],bytes[81],bytes[82],bytes[83]]) as usize;
+        bytes.len()==84+count*50
+    }
+}
+
+fn parse(bytes:&[u8])->Vec<Facet>{
+    if is_binary(bytes){
+        parse_binary(bytes)
+    }else{
+        parse_ascii(&String::from_utf8_lossy(bytes))
+    }
+}
+
+impl Decoder{
+    //load binary or ascii stl file, with flat normals (one vertex per triangle corner)
+    pub(crate) fn load_stl(&mut self,path:&str,command_buffer:vk::CommandBuffer)->Model{
+        let bytes=std::fs::read(path).unwrap_or_else(|e| panic!("failed to read {}: {}",path,e));
+        let facets=parse(&bytes);
+        if facets.is_empty(){
+            panic!("{} contains no triangles",path);
+        }
+
+        let mut vertices=Vec::with_capacity(facets.len()*3);
+        let mut vertex_indices=Vec::with_capacity(facets.len());
+        for (corners,normal) in facets.iter(){
+            //stored normals are often missing (zero) or inconsistent with the winding order, so they are recomputed
+            let computed_normal=glm::cross(&(corners[1]-corners[0]),&(corners[2]-corners[0]));
+            let normal=if glm::length(&computed_normal)>0.0{
+                glm::normalize(&computed_normal)
+            }else{
+                *normal
+            };
+            let first=vertices.len() as u32;
+            for corner in corners.iter(){
+                vertices.push(Vertex::new(corner.x,corner.y,corner.z,1.0,0.0,0.0).with_normal(normal.x,normal.y,normal.z));
+            }
+            vertex_indices.push(VertexIndices::new(first,first+1,first+2));
+        }
+        //corners of adjacent coplanar facets have the same normal, so they can share a vertex
+        mesh_processing::weld_vertices(&mut vertices,&mut vertex_indices,0.0);
+        mesh_processing::optimize_vertex_cache(&mut vertex_indices,vertices.len());
+
+        Model::new(vec![Submesh{
+            name:String::from(path),
+            material_name:None,
+            material:None,
+            mesh:std::sync::Arc::new(self.create_mesh(&vertices,&vertex_indices,true,command_buffer)),
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn binary_stl(header:&[u8],facets:&[Facet])->Vec<u8>{
+        let mut bytes=header.to_vec();
+        bytes.resize(80,0);
+        bytes.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for (corners,normal) in facets.iter(){
+            for v in std::iter::once(normal).chain(corners.iter()){
+                for c in v.iter(){
+                    bytes.extend_from_slice(&c.to_le_bytes());
+                }
+            }
+            bytes.extend_from_slice(&[0,0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii(){
+        let text="solid square\n  facet normal 0 0 1\n    outer loop\n      vertex 0 0 0\n      vertex 1 0 0\n      vertex 1 1 0\n      vertex 0 1 0\n    endloop\n  endfacet\n  facet normal 0 0 0\n    outer loop\n      vertex 0 0 1\n      vertex 1 0 1e0\n      vertex 0 1 1\n    endloop\n  endfacet\nendsolid square\n";
+        assert!(!is_binary(text.as_bytes()));
+        let facets=parse(text.as_bytes());
+        //the quad is fan triangulated
+        assert_eq!(facets.len(),3);
+        assert_eq!(facets[0],([glm::vec3(0.0,0.0,0.0),glm::vec3(1.0,0.0,0.0),glm::vec3(1.0,1.0,0.0)],glm::vec3(0.0,0.0,1.0)));
+        assert_eq!(facets[1].0,[glm::vec3(0.0,0.0,0.0),glm::vec3(1.0,1.0,0.0),glm::vec3(0.0,1.0,0.0)]);
+        assert_eq!(facets[2].0[1],glm::vec3(1.0,0.0,1.0));
+        assert_eq!(facets[2].1,glm::vec3(0.0,0.0,0.0));
+    }
+
+    #[test]
+    fn binary(){
+        let facets=vec![
+            ([glm::vec3(0.0,0.0,0.0),glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,1.0,0.0)],glm::vec3(0.0,0.0,1.0)),
+            ([glm::vec3(0.0,0.0,0.0),glm::vec3(0.0,0.0,1.0),glm::vec3(0.5,0.0,0.0)],glm::vec3(0.0,-1.0,0.0)),
+        ];
+        //binary files may start with "solid" as well
+        let bytes=binary_stl(b"solid but binary",&facets);
+        assert_eq!(bytes.len(),84+2*50);
+        assert!(is_binary(&bytes));
+        assert_eq!(parse(&bytes),facets);
+    }
+
+    #[test]
+    fn binary_detection_by_exact_size(){
+        let facets=vec![([glm::vec3(0.0,0.0,0.0),glm::vec3(1.0,0.0,0.0),glm::vec3(0.0,1.0,0.0)],glm::vec3(0.0,0.0,1.0))];
+        let bytes=binary_stl(b"",&facets);
+        assert!(is_binary(&bytes));
+        //one byte more or less than the triangle count implies means ascii
+        let mut longer=bytes.clone();
+        longer.push(b'\n');
+        assert!(!is_binary(&longer));
+        assert!(!is_binary(&bytes[..bytes.len()-1]));
+        //too short for the header
+        assert!(!is_binary(&bytes[..83]));
+        //an empty binary file
+        assert!(is_binary(&binary_stl(b"",&[])));
+    }
+}
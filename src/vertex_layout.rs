@@ -0,0 +1,230 @@
+use ash::{
+    vk,
+};
+
+pub use dyra_derive::VertexLayout;
+//used by code generated by #[derive(VertexLayout)], so that crates deriving it need neither ash nor memoffset
+pub use ash::vk::Format;
+pub use memoffset::offset_of;
+
+//shader input locations of vertex attributes, shared by all shaders
+pub const POSITION:u32=0;
+pub const TEXCOORD:u32=1;
+pub const NORMAL:u32=2;
+pub const TANGENT:u32=3;
+pub const COLOR:u32=4;
+
+//attributes missing from a vertex layout are read from a constant buffer bound at this binding, with stride 0
+pub const DEFAULT_ATTRIBUTES_BINDING:u32=2;
+//contents of that buffer, one vec4 per location (position has no default, and is never read from it)
+pub const DEFAULT_ATTRIBUTE_VALUES:[[f32;4];5]=[
+    [0.0,0.0,0.0,1.0],//position
+    [0.0,0.0,0.0,0.0],//texcoord
+    [0.0,0.0,1.0,0.0],//normal
+    [1.0,0.0,0.0,1.0],//tangent
+    [1.0,1.0,1.0,1.0],//color
+];
+
+#[derive(PartialEq,Eq,Hash,Debug,Clone,Copy)]
+pub struct VertexAttribute{
+    pub location:u32,
+    pub format:vk::Format,
+    pub offset:u32,//in bytes, from the start of the vertex
+}
+
+//memory layout of a vertex type, used to create matching pipeline vertex input state
+#[derive(PartialEq,Eq,Hash,Debug,Clone)]
+pub struct VertexLayoutDescription{
+    pub stride:u32,
+    pub attributes:Vec<VertexAttribute>,
+}
+impl VertexLayoutDescription{
+    pub fn attribute(&self,location:u32)->Option<&VertexAttribute>{
+        self.attributes.iter().find(|attribute| attribute.location==location)
+    }
+
+    pub fn binding_description(&self,binding:u32,input_rate:vk::VertexInputRate)->vk::VertexInputBindingDescription{
+        vk::VertexInputBindingDescription{
+            binding,
+            stride:self.stride,
+            input_rate,
+        }
+    }
+
+    //attribute descriptions for the given shader input locations
+    //locations the layout does not contain are read from DEFAULT_ATTRIBUTE_VALUES instead, except for the position, which every layout must contain
+    pub fn attribute_descriptions(&self,binding:u32,locations:&[u32])->Vec<vk::VertexInputAttributeDescription>{
+        locations.iter().map(|location|{
+            match self.attribute(*location){
+                Some(attribute)=>vk::VertexInputAttributeDescription{
+                    location:*location,
+                    binding,
+                    format:attribute.format,
+                    offset:attribute.offset,
+                },
+                None=>{
+                    if *location==POSITION || *location as usize>=DEFAULT_ATTRIBUTE_VALUES.len(){
+                        panic!("vertex layout has no attribute at location {}",location);
+                    }
+                    vk::VertexInputAttributeDescription{
+                        location:*location,
+                        binding:DEFAULT_ATTRIBUTES_BINDING,
+                        format:vk::Format::R32G32B32A32_SFLOAT,
+                        offset:*location*std::mem::size_of::<[f32;4]>() as u32,
+                    }
+                },
+            }
+        }).collect()
+    }
+
+    //true if the layout contains all given shader input locations
+    pub fn provides(&self,locations:&[u32])->bool{
+        locations.iter().all(|location| self.attribute(*location).is_some())
+    }
+}
+
+//vertex types that can be uploaded by Decoder::create_mesh
+//implement with #[derive(VertexLayout)] on a #[repr(C)] struct, marking the fields read by shaders with their location, e.g.
+//  #[vertex(POSITION)] position:[f32;3],
+//  #[vertex(COLOR,format=R8G8B8A8_UNORM)] color:[u8;4],
+//the format is inferred from the field type if not given. an attribute that spans several fields (like x,y,z,w) is marked on its first field, with the format of all of them
+pub trait VertexLayout:Sized{
+    fn layout()->VertexLayoutDescription;
+
+    //model space position, read from the POSITION attribute (which must have 2 to 4 f32 components, z is 0 for 2)
+    fn position(&self)->glm::Vec3{
+        let layout=Self::layout();
+        let attribute=layout.attribute(POSITION).expect("vertex layout has no position attribute");
+        let pointer=unsafe{
+            (self as *const Self as *const u8).add(attribute.offset as usize)
+        };
+        match attribute.format{
+            vk::Format::R32G32_SFLOAT=>{
+                let position=unsafe{
+                    (pointer as *const [f32;2]).read_unaligned()
+                };
+                glm::vec3(position[0],position[1],0.0)
+            },
+            vk::Format::R32G32B32_SFLOAT | vk::Format::R32G32B32A32_SFLOAT=>{
+                let position=unsafe{
+                    (pointer as *const [f32;3]).read_unaligned()
+                };
+                glm::vec3(position[0],position[1],position[2])
+            },
+            format=>panic!("unsupported position format {:?}",format),
+        }
+    }
+}
+
+//vertex with only a position, e.g. for meshes drawn with a single color texture, or depth only passes
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq,VertexLayout)]
+pub struct PositionVertex{
+    #[vertex(POSITION)]
+    pub position:[f32;3],
+}
+
+//vertex with a position and a color, used for debug lines (see Painter::draw)
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq,VertexLayout)]
+pub struct ColorVertex{
+    #[vertex(POSITION)]
+    pub position:[f32;3],
+    #[vertex(COLOR)]
+    pub color:[f32;4],
+}
+
+//vertex of a sprite quad, with the position in normalized device coordinates
+#[repr(C)]
+#[derive(Debug,Clone,Copy,PartialEq,VertexLayout)]
+pub struct SpriteVertex{
+    #[vertex(POSITION)]
+    pub position:[f32;2],
+    #[vertex(TEXCOORD)]
+    pub texcoord:[f32;2],
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //attributes out of location order, a color in bytes, a position spanning several fields, and a field that is not an attribute
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    struct PackedVertex{
+        #[vertex(COLOR,format=R8G8B8A8_UNORM)]
+        color:[u8;4],
+        #[vertex(POSITION,format=R32G32B32_SFLOAT)]
+        x:f32,
+        y:f32,
+        z:f32,
+        id:u16,
+        #[vertex(TEXCOORD)]
+        texcoord:[f32;2],
+    }
+
+    fn attributes(layout:&VertexLayoutDescription)->Vec<(u32,vk::Format,u32)>{
+        layout.attributes.iter().map(|attribute| (attribute.location,attribute.format,attribute.offset)).collect()
+    }
+
+    #[test]
+    fn derived_layouts(){
+        //the texture coordinates are aligned to 4 bytes after the id
+        let layout=PackedVertex::layout();
+        assert_eq!(layout.stride,28);
+        assert_eq!(attributes(&layout),vec![
+            (COLOR,vk::Format::R8G8B8A8_UNORM,0),
+            (POSITION,vk::Format::R32G32B32_SFLOAT,4),
+            (TEXCOORD,vk::Format::R32G32_SFLOAT,20),
+        ]);
+
+        assert_eq!(PositionVertex::layout(),VertexLayoutDescription{
+            stride:12,
+            attributes:vec![VertexAttribute{location:POSITION,format:vk::Format::R32G32B32_SFLOAT,offset:0}],
+        });
+        let layout=ColorVertex::layout();
+        assert_eq!(layout.stride,28);
+        assert_eq!(attributes(&layout),vec![(POSITION,vk::Format::R32G32B32_SFLOAT,0),(COLOR,vk::Format::R32G32B32A32_SFLOAT,12)]);
+        let layout=SpriteVertex::layout();
+        assert_eq!(layout.stride,16);
+        assert_eq!(attributes(&layout),vec![(POSITION,vk::Format::R32G32_SFLOAT,0),(TEXCOORD,vk::Format::R32G32_SFLOAT,8)]);
+
+        let layout=crate::Vertex::layout();
+        assert_eq!(layout.stride,68);
+        assert_eq!(attributes(&layout),vec![
+            (POSITION,vk::Format::R32G32B32A32_SFLOAT,0),
+            (TEXCOORD,vk::Format::R32G32_SFLOAT,16),
+            (NORMAL,vk::Format::R32G32B32_SFLOAT,24),
+            (TANGENT,vk::Format::R32G32B32A32_SFLOAT,36),
+            (COLOR,vk::Format::R32G32B32A32_SFLOAT,52),
+        ]);
+    }
+
+    #[test]
+    fn missing_attributes_use_defaults(){
+        let descriptions=PositionVertex::layout().attribute_descriptions(0,&[POSITION,TEXCOORD,NORMAL,COLOR]);
+        let descriptions:Vec<(u32,u32,vk::Format,u32)>=descriptions.iter().map(|description| (description.location,description.binding,description.format,description.offset)).collect();
+        assert_eq!(descriptions,vec![
+            (POSITION,0,vk::Format::R32G32B32_SFLOAT,0),
+            (TEXCOORD,DEFAULT_ATTRIBUTES_BINDING,vk::Format::R32G32B32A32_SFLOAT,16),
+            (NORMAL,DEFAULT_ATTRIBUTES_BINDING,vk::Format::R32G32B32A32_SFLOAT,32),
+            (COLOR,DEFAULT_ATTRIBUTES_BINDING,vk::Format::R32G32B32A32_SFLOAT,64),
+        ]);
+        assert!(ColorVertex::layout().provides(&[POSITION,COLOR]));
+        assert!(!ColorVertex::layout().provides(&[POSITION,TEXCOORD]));
+    }
+
+    #[test]
+    #[should_panic(expected="no attribute at location 0")]
+    fn position_is_required(){
+        VertexLayoutDescription{stride:16,attributes:Vec::new()}.attribute_descriptions(0,&[POSITION]);
+    }
+
+    #[test]
+    fn positions(){
+        let vertex=PackedVertex{color:[255;4],x:1.0,y:2.0,z:3.0,id:7,texcoord:[0.0;2]};
+        assert_eq!(vertex.position(),glm::vec3(1.0,2.0,3.0));
+        assert_eq!(vertex.id,7);
+        assert_eq!(SpriteVertex{position:[0.5,-0.5],texcoord:[0.0;2]}.position(),glm::vec3(0.5,-0.5,0.0));
+    }
+}
@@ -100,5 +100,6 @@ pub struct Window{
     pub swapchain_handle:vk::SwapchainKHR,
     pub swapchain_images:Vec<vk::Image>,
     pub swapchain_image_views:Vec<vk::ImageView>,
+    pub transient_attachments:crate::TransientAttachments,
     pub swapchain_image_framebuffers:Vec<vk::Framebuffer>,
 }
\ No newline at end of file
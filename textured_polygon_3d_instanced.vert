@@ -0,0 +1,29 @@
+#version 450
+
+layout(location=0) in vec4 i_Position;
+layout(location=1) in vec2 i_Texcoord;
+
+//per instance attributes, a mat4 occupies 4 locations
+layout(location=2) in mat4 i_Model;
+layout(location=6) in vec4 i_Tint;
+layout(location=7) in uint i_MaterialIndex;
+
+layout(push_constant) uniform PushConstants{
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+}constants;
+
+out gl_PerVertex{
+    vec4 gl_Position;
+};
+
+layout(location=0) out vec2 v_Texcoord;
+layout(location=1) out vec4 v_Tint;
+
+void main(){
+    gl_Position=constants.projection*constants.view*constants.model*i_Model*i_Position;
+
+    v_Texcoord=i_Texcoord;
+    v_Tint=i_Tint;
+}
\ No newline at end of file
@@ -0,0 +1,67 @@
+#version 450
+
+//must match lighting::MAX_POINT_LIGHTS
+#define MAX_POINT_LIGHTS 8
+
+layout(set=0,binding=0) uniform sampler2D u_Texture;
+
+struct PointLight{
+    vec4 position_range;//xyz position, w range (light has no effect beyond)
+    vec4 color_intensity;//rgb color, a intensity
+};
+layout(set=1,binding=0) uniform Lights{
+    vec4 ambient;//rgb color
+    vec4 directional_direction;//xyz direction the light travels in, w 1 if enabled
+    vec4 directional_color_intensity;
+    vec4 camera_position;
+    PointLight point_lights[MAX_POINT_LIGHTS];
+    uint point_light_count;
+}u_Lights;
+
+layout(location=0) in vec2 v_Texcoord;
+layout(location=1) in vec3 v_WorldPosition;
+layout(location=2) in vec3 v_WorldNormal;
+layout(location=3) in vec4 v_Color;
+
+layout(location=0) out vec4 o_Color;
+
+const float SHININESS=32.0;
+
+//blinn-phong diffuse and specular contribution of a light arriving from direction to_light
+vec3 shade(vec3 normal,vec3 to_light,vec3 to_camera,vec3 light_color,vec3 albedo){
+    float diffuse=max(dot(normal,to_light),0.0);
+    vec3 halfway=normalize(to_light+to_camera);
+    float specular=diffuse>0.0?pow(max(dot(normal,halfway),0.0),SHININESS):0.0;
+    return light_color*(diffuse*albedo+specular);
+}
+
+void main(){
+    vec4 albedo=texture(u_Texture,v_Texcoord)*v_Color;
+
+    vec3 normal=normalize(v_WorldNormal);
+    vec3 to_camera=normalize(u_Lights.camera_position.xyz-v_WorldPosition);
+
+    vec3 color=u_Lights.ambient.rgb*albedo.rgb;
+
+    if(u_Lights.directional_direction.w>0.0){
+        vec3 light_color=u_Lights.directional_color_intensity.rgb*u_Lights.directional_color_intensity.a;
+        color+=shade(normal,normalize(-u_Lights.directional_direction.xyz),to_camera,light_color,albedo.rgb);
+    }
+
+    for(uint i=0;i<u_Lights.point_light_count && i<MAX_POINT_LIGHTS;i++){
+        PointLight light=u_Lights.point_lights[i];
+        vec3 to_light=light.position_range.xyz-v_WorldPosition;
+        float distance=length(to_light);
+        float range=light.position_range.w;
+        if(distance>=range){
+            continue;
+        }
+        //smooth falloff that reaches zero at the light range
+        float falloff=clamp(1.0-pow(distance/range,4.0),0.0,1.0);
+        float attenuation=falloff*falloff/(distance*distance+1.0);
+        vec3 light_color=light.color_intensity.rgb*light.color_intensity.a*attenuation;
+        color+=shade(normal,to_light/distance,to_camera,light_color,albedo.rgb);
+    }
+
+    o_Color=vec4(color,albedo.a);
+}
\ No newline at end of file
@@ -0,0 +1,31 @@
+#version 450
+
+layout(location=0) in vec4 i_Position;
+layout(location=1) in vec2 i_Texcoord;
+layout(location=2) in vec3 i_Normal;
+layout(location=4) in vec4 i_Color;
+
+layout(push_constant) uniform PushConstants{
+    mat4 model;
+    mat4 view;
+    mat4 projection;
+}constants;
+
+out gl_PerVertex{
+    vec4 gl_Position;
+};
+
+layout(location=0) out vec2 v_Texcoord;
+layout(location=1) out vec3 v_WorldPosition;
+layout(location=2) out vec3 v_WorldNormal;
+layout(location=3) out vec4 v_Color;
+
+void main(){
+    vec4 world_position=constants.model*i_Position;
+    gl_Position=constants.projection*constants.view*world_position;
+
+    v_Texcoord=i_Texcoord;
+    v_WorldPosition=world_position.xyz/world_position.w;
+    v_WorldNormal=mat3(transpose(inverse(constants.model)))*i_Normal;
+    v_Color=i_Color;
+}
\ No newline at end of file